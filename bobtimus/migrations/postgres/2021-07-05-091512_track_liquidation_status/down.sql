@@ -0,0 +1,2 @@
+ALTER TABLE liquidations DROP COLUMN liquidation_txid;
+ALTER TABLE liquidations DROP COLUMN status;
@@ -0,0 +1,11 @@
+CREATE TABLE trades
+(
+       txid             TEXT NOT NULL PRIMARY KEY,
+       created_at       BIGINT NOT NULL,
+       pair             TEXT NOT NULL,
+       direction        TEXT NOT NULL,
+       base_amount      BIGINT NOT NULL,
+       quote_amount     BIGINT NOT NULL,
+       rate             BIGINT NOT NULL
+);
+CREATE INDEX trades_created_at ON trades (created_at);
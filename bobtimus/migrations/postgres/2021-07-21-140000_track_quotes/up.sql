@@ -0,0 +1,15 @@
+CREATE TABLE quotes
+(
+       id                       TEXT NOT NULL PRIMARY KEY,
+       created_at               BIGINT NOT NULL,
+       expires_at               BIGINT NOT NULL,
+       pair                     TEXT NOT NULL,
+       direction                TEXT NOT NULL,
+       rate                     BIGINT NOT NULL,
+       base_amount              BIGINT NOT NULL,
+       quote_amount             BIGINT NOT NULL,
+       requested_amount         BIGINT NOT NULL,
+       status                   TEXT NOT NULL DEFAULT 'created',
+       txid                     TEXT,
+       failure                  TEXT
+);
@@ -0,0 +1,8 @@
+CREATE TABLE rates
+(
+       recorded_at      BIGINT NOT NULL,
+       pair             TEXT NOT NULL,
+       ask              BIGINT NOT NULL,
+       bid              BIGINT NOT NULL,
+       PRIMARY KEY (recorded_at, pair)
+);
@@ -0,0 +1,5 @@
+DROP TABLE liquidation_fee_bumps;
+ALTER TABLE liquidations DROP COLUMN last_error;
+ALTER TABLE liquidations DROP COLUMN last_attempt_height;
+ALTER TABLE liquidations DROP COLUMN attempts;
+ALTER TABLE liquidations DROP COLUMN fee_level;
@@ -0,0 +1,2 @@
+ALTER TABLE loans DROP COLUMN repayment_address;
+ALTER TABLE loans DROP COLUMN created_at;
@@ -0,0 +1,2 @@
+ALTER TABLE loans ADD COLUMN created_at BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE loans ADD COLUMN repayment_address TEXT;
@@ -0,0 +1,15 @@
+CREATE TABLE limit_orders
+(
+       id                       TEXT NOT NULL PRIMARY KEY,
+       created_at               BIGINT NOT NULL,
+       expires_at               BIGINT NOT NULL,
+       pair                     TEXT NOT NULL,
+       direction                TEXT NOT NULL,
+       limit_rate               BIGINT NOT NULL,
+       amount                   BIGINT NOT NULL,
+       payload                  TEXT NOT NULL,
+       status                   TEXT NOT NULL DEFAULT 'open',
+       txid                     TEXT,
+       tx_hex                   TEXT,
+       failure                  TEXT
+);
@@ -0,0 +1 @@
+ALTER TABLE loans ADD COLUMN interest_amount BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,9 @@
+CREATE TABLE liquidations_without_status
+(
+       id               TEXT NOT NULL PRIMARY KEY,
+       tx_hex           TEXT NOT NULL,
+       locktime         BIGINT NOT NULL
+);
+INSERT INTO liquidations_without_status SELECT id, tx_hex, locktime FROM liquidations;
+DROP TABLE liquidations;
+ALTER TABLE liquidations_without_status RENAME TO liquidations;
@@ -0,0 +1,2 @@
+ALTER TABLE liquidations ADD COLUMN status TEXT NOT NULL DEFAULT 'pending';
+ALTER TABLE liquidations ADD COLUMN liquidation_txid TEXT;
@@ -0,0 +1,7 @@
+CREATE TABLE inventory
+(
+       recorded_at      BIGINT NOT NULL,
+       asset            TEXT NOT NULL,
+       balance          BIGINT NOT NULL,
+       PRIMARY KEY (recorded_at, asset)
+);
@@ -0,0 +1,8 @@
+CREATE TABLE loans
+(
+       id                       TEXT NOT NULL PRIMARY KEY,
+       collateral_amount        BIGINT NOT NULL,
+       principal_amount         BIGINT NOT NULL,
+       interest_amount          BIGINT NOT NULL,
+       liquidation_ltv_bps      BIGINT NOT NULL
+);
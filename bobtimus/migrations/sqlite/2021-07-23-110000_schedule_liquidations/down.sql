@@ -0,0 +1,12 @@
+DROP TABLE liquidation_fee_bumps;
+CREATE TABLE liquidations_without_attempts
+(
+       id               TEXT NOT NULL PRIMARY KEY,
+       tx_hex           TEXT NOT NULL,
+       locktime         BIGINT NOT NULL,
+       status           TEXT NOT NULL DEFAULT 'pending',
+       liquidation_txid TEXT
+);
+INSERT INTO liquidations_without_attempts SELECT id, tx_hex, locktime, status, liquidation_txid FROM liquidations;
+DROP TABLE liquidations;
+ALTER TABLE liquidations_without_attempts RENAME TO liquidations;
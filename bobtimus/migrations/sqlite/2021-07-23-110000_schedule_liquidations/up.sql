@@ -0,0 +1,11 @@
+ALTER TABLE liquidations ADD COLUMN fee_level BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE liquidations ADD COLUMN attempts BIGINT NOT NULL DEFAULT 0;
+ALTER TABLE liquidations ADD COLUMN last_attempt_height BIGINT;
+ALTER TABLE liquidations ADD COLUMN last_error TEXT;
+CREATE TABLE liquidation_fee_bumps
+(
+       loan_id          TEXT NOT NULL,
+       fee_level        BIGINT NOT NULL,
+       tx_hex           TEXT NOT NULL,
+       PRIMARY KEY (loan_id, fee_level)
+);
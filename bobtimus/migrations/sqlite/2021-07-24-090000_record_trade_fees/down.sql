@@ -0,0 +1,14 @@
+CREATE TABLE trades_without_fee
+(
+       txid             TEXT NOT NULL PRIMARY KEY,
+       created_at       BIGINT NOT NULL,
+       pair             TEXT NOT NULL,
+       direction        TEXT NOT NULL,
+       base_amount      BIGINT NOT NULL,
+       quote_amount     BIGINT NOT NULL,
+       rate             BIGINT NOT NULL
+);
+INSERT INTO trades_without_fee SELECT txid, created_at, pair, direction, base_amount, quote_amount, rate FROM trades;
+DROP TABLE trades;
+ALTER TABLE trades_without_fee RENAME TO trades;
+CREATE INDEX trades_created_at ON trades (created_at);
@@ -0,0 +1 @@
+ALTER TABLE trades ADD COLUMN fee BIGINT NOT NULL DEFAULT 0;
@@ -0,0 +1,11 @@
+CREATE TABLE loans_without_book
+(
+       id                       TEXT NOT NULL PRIMARY KEY,
+       collateral_amount        BIGINT NOT NULL,
+       principal_amount         BIGINT NOT NULL,
+       interest_amount          BIGINT NOT NULL,
+       liquidation_ltv_bps      BIGINT NOT NULL
+);
+INSERT INTO loans_without_book SELECT id, collateral_amount, principal_amount, interest_amount, liquidation_ltv_bps FROM loans;
+DROP TABLE loans;
+ALTER TABLE loans_without_book RENAME TO loans;
@@ -0,0 +1,12 @@
+CREATE TABLE loans_without_interest
+(
+       id                       TEXT NOT NULL PRIMARY KEY,
+       collateral_amount        BIGINT NOT NULL,
+       principal_amount         BIGINT NOT NULL,
+       liquidation_ltv_bps      BIGINT NOT NULL,
+       created_at               BIGINT NOT NULL DEFAULT 0,
+       repayment_address        TEXT
+);
+INSERT INTO loans_without_interest SELECT id, collateral_amount, principal_amount, liquidation_ltv_bps, created_at, repayment_address FROM loans;
+DROP TABLE loans;
+ALTER TABLE loans_without_interest RENAME TO loans;
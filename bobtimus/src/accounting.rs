@@ -0,0 +1,296 @@
+use crate::database::{
+    queries, ClosedLoan, Database, LiquidationStatus, TradeDirection, TradeRecord,
+};
+use anyhow::{Context, Result};
+use elements::{confidential::Value, Transaction};
+use serde::Serialize;
+use std::{collections::BTreeMap, convert::TryFrom, fmt::Write};
+
+/// Label of L-BTC in reports, in which all fees are paid.
+const BTC: &str = "lbtc";
+/// Label of L-USDt in reports, in which all loans are made.
+const USDT: &str = "lusdt";
+
+/// Profit and loss realized from the trades and loans we recorded.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Report {
+    /// Realized profit per asset, negative for a loss, in the smallest
+    /// unit of the asset.
+    pub realized: BTreeMap<String, i64>,
+    pub pairs: Vec<PairReport>,
+    pub loans: LoanReport,
+    /// Fees of all swap and liquidation transactions, in satoshi of
+    /// L-BTC.
+    ///
+    /// Alice pays part of the swap transaction fees, so this is an
+    /// upper bound of what we paid.
+    pub fees: u64,
+}
+
+/// What we traded in a pair, in the smallest unit of each asset.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct PairReport {
+    pub pair: String,
+    /// Base asset we received from Alice.
+    pub base_bought: u64,
+    /// Base asset we gave to Alice.
+    pub base_sold: u64,
+    /// Quote asset we gave to Alice for the base asset we bought.
+    pub quote_paid: u64,
+    /// Quote asset we received from Alice for the base asset we sold.
+    pub quote_received: u64,
+    /// Profit of the part of the volume we both bought and sold, at
+    /// the average prices we bought and sold at, in the quote asset.
+    ///
+    /// The rest is still part of our inventory and not realized yet.
+    pub realized: i64,
+}
+
+/// Outcome of the loans we closed.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct LoanReport {
+    /// Number of loans the borrower repaid.
+    pub repaid: u64,
+    /// Number of loans we liquidated.
+    pub liquidated: u64,
+    /// Collateral we kept from liquidated loans, in satoshi.
+    pub collateral_seized: u64,
+    /// Principal of liquidated loans which was never repaid, in
+    /// satodollar.
+    pub principal_lost: u64,
+}
+
+/// Work out the profit and loss realized from everything in the `db`.
+pub async fn report(db: &Database) -> Result<Report> {
+    let (trades, closed_loans) = db
+        .do_in_transaction(|conn| {
+            let trades = queries::get_all_trades(conn)?;
+            let closed_loans = queries::get_closed_loans(conn)?;
+
+            Ok((trades, closed_loans))
+        })
+        .await
+        .context("failed to load trade and loan history")?;
+
+    let mut liquidation_fees = 0;
+    for loan in closed_loans.iter() {
+        liquidation_fees += liquidation_fee(db, loan).await?;
+    }
+
+    let mut report = Report {
+        pairs: trading_pnl(&trades),
+        loans: loan_pnl(&closed_loans),
+        fees: trades.iter().map(|trade| trade.fee).sum::<u64>() + liquidation_fees,
+        ..Report::default()
+    };
+
+    for pair in report.pairs.iter() {
+        let quote = pair.pair.split('-').nth(1).unwrap_or(&pair.pair);
+        *report.realized.entry(quote.to_owned()).or_default() += pair.realized;
+    }
+    *report.realized.entry(USDT.to_owned()).or_default() -=
+        i64::try_from(report.loans.principal_lost)?;
+    *report.realized.entry(BTC.to_owned()).or_default() +=
+        i64::try_from(report.loans.collateral_seized)?;
+    *report.realized.entry(BTC.to_owned()).or_default() -= i64::try_from(report.fees)?;
+
+    Ok(report)
+}
+
+/// All recorded trades as CSV, oldest first, with all amounts in the
+/// smallest unit of their asset.
+pub async fn export_trades(db: &Database) -> Result<String> {
+    let trades = db
+        .do_in_transaction(queries::get_all_trades)
+        .await
+        .context("failed to load trades")?;
+
+    Ok(trades_csv(&trades))
+}
+
+fn trades_csv(trades: &[TradeRecord]) -> String {
+    let mut csv = "txid,created_at,pair,direction,base_amount,quote_amount,rate,fee\n".to_owned();
+    for trade in trades {
+        let direction = match trade.direction {
+            TradeDirection::Buy => "buy",
+            TradeDirection::Sell => "sell",
+        };
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{},{}",
+            trade.txid,
+            trade.created_at,
+            trade.pair,
+            direction,
+            trade.base_amount,
+            trade.quote_amount,
+            trade.rate.as_satodollar(),
+            trade.fee
+        )
+        .expect("writing to a string cannot fail");
+    }
+
+    csv
+}
+
+/// Sum of the explicit fee outputs of the `transaction`, in satoshi of
+/// L-BTC.
+pub fn transaction_fee(transaction: &Transaction) -> u64 {
+    transaction
+        .output
+        .iter()
+        .filter(|txout| txout.is_fee())
+        .map(|txout| match txout.value {
+            Value::Explicit(fee) => fee,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Fee of the liquidation transaction which confirmed for the `loan`,
+/// if any.
+async fn liquidation_fee(db: &Database, loan: &ClosedLoan) -> Result<u64> {
+    let liquidation_txid = match (loan.status, loan.liquidation_txid) {
+        (LiquidationStatus::Confirmed, Some(liquidation_txid)) => liquidation_txid,
+        _ => return Ok(0),
+    };
+
+    let loan_txid = loan.loan_txid;
+    let txs = db
+        .do_in_transaction(|conn| {
+            let mut txs = vec![queries::get_liquidation_tx(conn, loan_txid)?];
+            txs.extend(queries::get_fee_bumps(conn, loan_txid)?);
+
+            Ok(txs)
+        })
+        .await?;
+
+    Ok(txs
+        .iter()
+        .find(|tx| tx.txid() == liquidation_txid)
+        .map(transaction_fee)
+        .unwrap_or(0))
+}
+
+fn trading_pnl(trades: &[TradeRecord]) -> Vec<PairReport> {
+    let mut pairs = BTreeMap::<&str, PairReport>::new();
+
+    for trade in trades {
+        let pair = pairs
+            .entry(trade.pair.as_str())
+            .or_insert_with(|| PairReport {
+                pair: trade.pair.clone(),
+                ..PairReport::default()
+            });
+
+        match trade.direction {
+            // Alice bought the base asset from us
+            TradeDirection::Buy => {
+                pair.base_sold += trade.base_amount;
+                pair.quote_received += trade.quote_amount;
+            }
+            TradeDirection::Sell => {
+                pair.base_bought += trade.base_amount;
+                pair.quote_paid += trade.quote_amount;
+            }
+        }
+    }
+
+    pairs
+        .into_iter()
+        .map(|(_, mut pair)| {
+            pair.realized = realized(&pair);
+            pair
+        })
+        .collect()
+}
+
+fn realized(pair: &PairReport) -> i64 {
+    let matched = pair.base_bought.min(pair.base_sold) as i128;
+    if matched == 0 {
+        return 0;
+    }
+
+    let proceeds = matched * pair.quote_received as i128 / pair.base_sold as i128;
+    let cost = matched * pair.quote_paid as i128 / pair.base_bought as i128;
+
+    (proceeds - cost) as i64
+}
+
+fn loan_pnl(closed_loans: &[ClosedLoan]) -> LoanReport {
+    let mut report = LoanReport::default();
+
+    for loan in closed_loans {
+        match loan.status {
+            LiquidationStatus::Repaid => {
+                report.repaid += 1;
+            }
+            LiquidationStatus::Confirmed => {
+                report.liquidated += 1;
+                report.collateral_seized += loan.terms.collateral.as_sat();
+                report.principal_lost += loan.terms.principal.as_satodollar();
+            }
+            LiquidationStatus::Pending
+            | LiquidationStatus::Broadcast
+            | LiquidationStatus::Unknown => {}
+        }
+    }
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiquidUsdt;
+    use elements::Txid;
+
+    #[test]
+    fn realizes_profit_of_matched_volume_only() {
+        let trade = |direction, base_amount, quote_amount| TradeRecord {
+            txid: Txid::default(),
+            created_at: 0,
+            pair: "lbtc-lusdt".to_owned(),
+            direction,
+            base_amount,
+            quote_amount,
+            rate: LiquidUsdt::from_satodollar(0),
+            fee: 0,
+        };
+
+        let pairs = trading_pnl(&[
+            // we buy 1 BTC at 39,000
+            trade(TradeDirection::Sell, 100_000_000, 39_000 * 100_000_000),
+            // and sell half of it at 40,000
+            trade(TradeDirection::Buy, 50_000_000, 20_000 * 100_000_000),
+        ]);
+
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].realized, 500 * 100_000_000);
+    }
+
+    #[test]
+    fn exports_one_csv_line_per_trade() {
+        let trade = TradeRecord {
+            txid: Txid::default(),
+            created_at: 1_626_000_000,
+            pair: "lbtc-lusdt".to_owned(),
+            direction: TradeDirection::Buy,
+            base_amount: 100_000,
+            quote_amount: 40_000_000_000,
+            rate: LiquidUsdt::from_satodollar(4_000_000_000_000),
+            fee: 4_991,
+        };
+
+        let csv = trades_csv(&[trade]);
+
+        assert_eq!(
+            csv,
+            format!(
+                "txid,created_at,pair,direction,base_amount,quote_amount,rate,fee\n\
+                 {},1626000000,lbtc-lusdt,buy,100000,40000000000,4000000000000,4991\n",
+                Txid::default()
+            )
+        );
+    }
+}
@@ -81,7 +81,10 @@ impl LiquidUsdt {
         Ok(Self(amount))
     }
 
-    fn serialize_to_nominal<S>(amount: &LiquidUsdt, serializer: S) -> Result<S::Ok, S::Error>
+    pub(crate) fn serialize_to_nominal<S>(
+        amount: &LiquidUsdt,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
     where
         S: serde::Serializer,
     {
@@ -0,0 +1,345 @@
+use crate::{
+    accounting,
+    cli::Config,
+    cold_storage,
+    database::Database,
+    elements_rpc::Client,
+    http,
+    idempotency::SwapRequests,
+    inventory::{self, InventoryLimits},
+    limit_order, liquidate_loan, liquidate_loans, loan_book,
+    oracle::{self, Oracle},
+    rate_history, run_liquidation_engine,
+    shutdown::Shutdown,
+    supervisor::supervise,
+    wallet::Wallet,
+    Bobtimus, LatestRate,
+};
+use anyhow::Result;
+use elements::{
+    bitcoin::secp256k1::Secp256k1,
+    secp256k1_zkp::{
+        rand::{rngs::StdRng, thread_rng, SeedableRng},
+        SecretKey,
+    },
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+use warp::{filters::BoxedFilter, reply::Response, Filter};
+
+/// Carry out the command of the `config`, taking rates from the
+/// service started by `rate_service`.
+pub async fn run<RS, F, Fut>(config: Config, rate_service: F) -> Result<()>
+where
+    RS: LatestRate + Clone + Send + Sync + 'static,
+    F: FnOnce(Shutdown) -> Fut,
+    Fut: Future<Output = Result<RS>>,
+{
+    run_with_routes(config, rate_service, |_, _| async { Ok(None) }).await
+}
+
+/// Like [`run`], but also serves the routes returned by
+/// `extra_routes`, if any, which is handed the shared [`Bobtimus`]
+/// and the API key.
+pub async fn run_with_routes<RS, F, Fut, E, EFut>(
+    config: Config,
+    rate_service: F,
+    extra_routes: E,
+) -> Result<()>
+where
+    RS: LatestRate + Clone + Send + Sync + 'static,
+    F: FnOnce(Shutdown) -> Fut,
+    Fut: Future<Output = Result<RS>>,
+    E: FnOnce(Arc<Bobtimus<StdRng, RS>>, Option<String>) -> EFut,
+    EFut: Future<Output = Result<Option<BoxedFilter<(Response,)>>>>,
+{
+    match config {
+        Config::Start {
+            elementsd_url,
+            api_port,
+            usdt_asset_id,
+            native_asset_id,
+            database,
+            quote_params,
+            btc_inventory,
+            usdt_inventory,
+            rebalance_alerts,
+            webhooks,
+            lending_params,
+            pairs,
+            api_key,
+            oracle_key,
+            cors,
+            sweep_policy,
+            dry_run,
+        } => {
+            let db = Database::connect(&database)?;
+
+            let elementsd = Client::new(elementsd_url.into())?.with_dry_run(dry_run);
+            let btc_asset_id = match native_asset_id {
+                Some(native_asset_id) => native_asset_id,
+                None => elementsd.get_bitcoin_asset_id().await?,
+            };
+            tracing::info!("Using {} as the native asset", btc_asset_id);
+            if dry_run {
+                tracing::warn!("Running in dry-run mode, no transaction will be broadcast");
+            }
+
+            let shutdown = Shutdown::on_signal();
+
+            let rate_service = rate_service(shutdown.clone()).await?;
+            let subscription = rate_service.subscribe().quoted_with(quote_params);
+
+            let (oracle_task, attestations) = match oracle_key {
+                Some(oracle_key) => {
+                    let (oracle, attestations) = Oracle::new(oracle_key);
+                    let task = supervise("oracle", shutdown.clone(), {
+                        let rate_service = rate_service.clone();
+                        let shutdown = shutdown.clone();
+                        move || {
+                            oracle::run_oracle(
+                                oracle.clone(),
+                                rate_service.clone(),
+                                shutdown.clone(),
+                            )
+                        }
+                    });
+
+                    (Some(task), Some(attestations))
+                }
+                None => (None, None),
+            };
+            let mut assets = vec![btc_asset_id, usdt_asset_id];
+            for pair in &pairs {
+                for asset in &[pair.base_asset_id, pair.quote_asset_id] {
+                    if !assets.contains(asset) {
+                        assets.push(*asset);
+                    }
+                }
+            }
+            let (wallet, wallet_task) = Wallet::new(elementsd.clone(), assets);
+            let wallet_actor = supervise("wallet", shutdown.clone(), move || {
+                wallet_task.clone().run()
+            });
+            let liquidation_engine = supervise("liquidation engine", shutdown.clone(), {
+                let elementsd = elementsd.clone();
+                let db = db.clone();
+                let rate_service = rate_service.clone();
+                let webhooks = webhooks.clone();
+                let shutdown = shutdown.clone();
+                move || {
+                    run_liquidation_engine(
+                        elementsd.clone(),
+                        db.clone(),
+                        rate_service.clone(),
+                        webhooks.clone(),
+                        shutdown.clone(),
+                    )
+                }
+            });
+            let rate_recorder = supervise("rate recorder", shutdown.clone(), {
+                let db = db.clone();
+                let rate_service = rate_service.clone();
+                let shutdown = shutdown.clone();
+                move || {
+                    rate_history::run_rate_recorder(
+                        db.clone(),
+                        rate_service.clone(),
+                        quote_params,
+                        shutdown.clone(),
+                    )
+                }
+            });
+            let loan_watcher = supervise("loan watcher", shutdown.clone(), {
+                let elementsd = elementsd.clone();
+                let db = db.clone();
+                let webhooks = webhooks.clone();
+                let shutdown = shutdown.clone();
+                move || {
+                    loan_book::run_loan_watcher(
+                        elementsd.clone(),
+                        db.clone(),
+                        usdt_asset_id,
+                        webhooks.clone(),
+                        shutdown.clone(),
+                    )
+                }
+            });
+            let inventory_tracker = supervise("inventory tracker", shutdown.clone(), {
+                let elementsd = elementsd.clone();
+                let db = db.clone();
+                let shutdown = shutdown.clone();
+                move || {
+                    inventory::run_inventory_tracker(
+                        elementsd.clone(),
+                        db.clone(),
+                        rebalance_alerts.clone(),
+                        btc_asset_id,
+                        usdt_asset_id,
+                        shutdown.clone(),
+                    )
+                }
+            });
+            let sweeper = sweep_policy.map(|sweep_policy| {
+                supervise("sweeper", shutdown.clone(), {
+                    let elementsd = elementsd.clone();
+                    let shutdown = shutdown.clone();
+                    move || {
+                        cold_storage::run_sweeper(
+                            elementsd.clone(),
+                            sweep_policy.clone(),
+                            btc_asset_id,
+                            usdt_asset_id,
+                            shutdown.clone(),
+                        )
+                    }
+                })
+            });
+
+            let mut markets = HashMap::new();
+            for pair in pairs {
+                let (name, market) = pair.into_market(shutdown.clone()).await?;
+                markets.insert(name, market);
+            }
+
+            let bobtimus = Bobtimus {
+                rng: Mutex::new(StdRng::from_rng(&mut thread_rng()).unwrap()),
+                rate_service,
+                quote_params,
+                inventory_limits: InventoryLimits::default()
+                    .with(btc_asset_id, btc_inventory)
+                    .with(usdt_asset_id, usdt_inventory),
+                lending_params,
+                secp: Secp256k1::new(),
+                elementsd,
+                btc_asset_id,
+                usdt_asset_id,
+                db,
+                lender_states: Mutex::new(HashMap::new()),
+                markets,
+                quote_key: SecretKey::new(&mut thread_rng()),
+                swap_requests: SwapRequests::default(),
+                wallet,
+                webhooks,
+            };
+            let bobtimus = Arc::new(bobtimus);
+            let order_matcher = supervise("order matcher", shutdown.clone(), {
+                let bobtimus = bobtimus.clone();
+                let shutdown = shutdown.clone();
+                move || limit_order::run_order_matcher(bobtimus.clone(), shutdown.clone())
+            });
+
+            let routes = http::routes(
+                bobtimus.clone(),
+                subscription,
+                attestations,
+                api_key.clone(),
+                shutdown.clone(),
+            );
+            let routes = match extra_routes(bobtimus, api_key).await? {
+                Some(extra_routes) => routes.or(extra_routes).unify().boxed(),
+                None => routes,
+            };
+            let (_, server) = warp::serve(routes.with(cors.filter()))
+                .bind_with_graceful_shutdown(([127, 0, 0, 1], api_port), shutdown.triggered());
+            server.await;
+
+            // let the background tasks finish what they are doing, the
+            // database is closed once the last handle to it is dropped
+            liquidation_engine.await?;
+            loan_watcher.await?;
+            inventory_tracker.await?;
+            rate_recorder.await?;
+            order_matcher.await?;
+            wallet_actor.await?;
+            if let Some(sweeper) = sweeper {
+                sweeper.await?;
+            }
+            if let Some(oracle_task) = oracle_task {
+                oracle_task.await?;
+            }
+
+            tracing::info!("Shut down");
+        }
+        Config::LiquidateLoans {
+            elementsd_url,
+            database,
+            webhooks,
+            dry_run,
+        } => {
+            let db = Database::connect(&database)?;
+            let elementsd = Client::new(elementsd_url.into())?.with_dry_run(dry_run);
+
+            liquidate_loans(&elementsd, db, &webhooks).await?;
+        }
+        Config::Liquidate {
+            loan_id,
+            elementsd_url,
+            database,
+            webhooks,
+            dry_run,
+        } => {
+            let db = Database::connect(&database)?;
+            let elementsd = Client::new(elementsd_url.into())?.with_dry_run(dry_run);
+
+            liquidate_loan(&elementsd, db, &webhooks, loan_id).await?;
+        }
+        Config::Balances {
+            elementsd_url,
+            usdt_asset_id,
+            native_asset_id,
+        } => {
+            let elementsd = Client::new(elementsd_url.into())?;
+            let btc_asset_id = match native_asset_id {
+                Some(native_asset_id) => native_asset_id,
+                None => elementsd.get_bitcoin_asset_id().await?,
+            };
+
+            for (label, asset_id) in [("L-BTC", btc_asset_id), ("L-USDt", usdt_asset_id)].iter() {
+                let balance = elementsd.get_balance(*asset_id).await?;
+                println!("{}: {}", label, balance.as_btc());
+            }
+        }
+        Config::ExportTrades { database, output } => {
+            let db = Database::connect(&database)?;
+            let csv = accounting::export_trades(&db).await?;
+
+            match output {
+                Some(output) => std::fs::write(output, csv)?,
+                None => print!("{}", csv),
+            }
+        }
+        Config::Report { database } => {
+            let db = Database::connect(&database)?;
+            let report = accounting::report(&db).await?;
+
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Config::Withdraw {
+            elementsd_url,
+            address,
+            asset_id,
+            amount,
+            dry_run,
+        } => {
+            let elementsd = Client::new(elementsd_url.into())?.with_dry_run(dry_run);
+            let asset_id = match asset_id {
+                Some(asset_id) => asset_id,
+                None => elementsd.get_bitcoin_asset_id().await?,
+            };
+
+            let txid = cold_storage::withdraw(&elementsd, &address, asset_id, amount).await?;
+            tracing::info!(
+                "Withdrew {} of asset {} in transaction {}",
+                amount,
+                asset_id,
+                txid
+            );
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,161 @@
+use anyhow::{bail, Context, Result};
+use bobtimus::{
+    elements_rpc::{Client, ElementsRpc},
+    logging,
+    shutdown::Shutdown,
+};
+use elements::AssetId;
+use elements_harness::Elementsd;
+use reqwest::Url;
+use std::{path::PathBuf, process::Stdio, time::Duration};
+use structopt::StructOpt;
+use testcontainers::clients::Cli;
+use tokio::{
+    process::Command,
+    time::{sleep, timeout},
+};
+
+/// The elementsd version the harness starts, the same the tests use.
+const ELEMENTSD_VERSION: &str = "0.18.1.9";
+
+/// How long bobtimus gets to finish in-flight requests once we shut
+/// down.
+const SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// How much L-USDt we issue to the wallet bobtimus trades from.
+const USDT_ISSUANCE: f64 = 1_000_000.0;
+
+#[derive(structopt::StructOpt, Debug)]
+#[structopt(
+    name = "devnet",
+    about = "Run elementsd and a funded bobtimus for local development"
+)]
+struct Opts {
+    /// Use this elementsd, e.g. one which an esplora indexes, instead
+    /// of starting one in a container
+    #[structopt(long = "elementsd")]
+    elementsd_url: Option<Url>,
+    /// Port the API of bobtimus listens on
+    #[structopt(default_value = "3030", long = "api-port")]
+    api_port: u16,
+    /// Esplora instance indexing the node, which the extension talks to
+    #[structopt(default_value = "http://127.0.0.1:3001", long = "esplora")]
+    esplora_url: Url,
+    /// Seconds between the blocks we mine, so that swaps and loans
+    /// confirm without anyone mining by hand
+    #[structopt(default_value = "10", long = "block-time")]
+    block_time: u64,
+    /// Also write the environment of the extension to this file, e.g.
+    /// `extension/.env.development.local`
+    #[structopt(long = "env-file", parse(from_os_str))]
+    env_file: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    logging::init()?;
+
+    let opts = Opts::from_args();
+    let shutdown = Shutdown::on_signal();
+
+    let docker = Cli::default();
+    // the container is removed once the node is dropped
+    let (node_url, _node) = match opts.elementsd_url.clone() {
+        Some(node_url) => (node_url, None),
+        None => {
+            let node =
+                Elementsd::new(&docker, ELEMENTSD_VERSION).context("failed to start elementsd")?;
+            tracing::info!("Started elementsd at {}", node.node_url);
+
+            (node.node_url.clone(), Some(node))
+        }
+    };
+    let elementsd = Client::new(node_url.clone().into())?;
+
+    let btc_asset_id = elementsd.get_bitcoin_asset_id().await?;
+    let usdt_asset_id = elementsd
+        .issueasset(USDT_ISSUANCE, 0.0, true)
+        .await
+        .context("failed to issue L-USDt")?
+        .asset;
+    let mining_address = elementsd.get_new_segwit_confidential_address().await?;
+    elementsd.generatetoaddress(1, &mining_address).await?;
+    tracing::info!("Issued {} L-USDt as {}", USDT_ISSUANCE, usdt_asset_id);
+
+    let data_dir = tempfile::tempdir()?;
+    let mut bobtimus = Command::new(sibling_binary("fake_bobtimus")?)
+        .arg("start")
+        .args(&["--elementsd", node_url.as_str()])
+        .args(&["--network", "elements"])
+        .args(&["--usdt", &usdt_asset_id.to_string()])
+        .args(&["--native-asset-id", &btc_asset_id.to_string()])
+        .arg("-d")
+        .arg(data_dir.path().join("bobtimus.sqlite"))
+        .arg("--cors-allow-any-origin")
+        .arg(opts.api_port.to_string())
+        .stdin(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to start bobtimus")?;
+
+    let env = extension_env(&opts.esplora_url, btc_asset_id, usdt_asset_id);
+    if let Some(env_file) = &opts.env_file {
+        std::fs::write(env_file, &env)
+            .with_context(|| format!("failed to write {}", env_file.display()))?;
+    }
+    println!(
+        "\nbobtimus is serving http://127.0.0.1:{} with this environment:\n\n{}",
+        opts.api_port, env
+    );
+
+    loop {
+        tokio::select! {
+            _ = sleep(Duration::from_secs(opts.block_time)) => {
+                if let Err(e) = elementsd.generatetoaddress(1, &mining_address).await {
+                    tracing::warn!("failed to mine block: {:#}", e);
+                }
+            }
+            status = bobtimus.wait() => bail!("bobtimus exited with {}", status?),
+            _ = shutdown.clone().triggered() => break,
+        }
+    }
+
+    // bobtimus usually got the same signal from the terminal, give it
+    // a chance to shut down before the node goes away
+    if timeout(SHUTDOWN_GRACE_PERIOD, bobtimus.wait())
+        .await
+        .is_err()
+    {
+        bobtimus.kill().await?;
+    }
+
+    Ok(())
+}
+
+/// The binary called `name` which was built alongside this one.
+fn sibling_binary(name: &str) -> Result<PathBuf> {
+    let path = std::env::current_exe()?.with_file_name(name);
+    if !path.exists() {
+        bail!(
+            "{} not found, build it with `cargo build --bin {}`",
+            path.display(),
+            name
+        )
+    }
+
+    Ok(path)
+}
+
+/// Environment variables the extension and the waves frontend are
+/// built with, in the format of a `.env` file.
+fn extension_env(esplora_url: &Url, btc_asset_id: AssetId, usdt_asset_id: AssetId) -> String {
+    format!(
+        "REACT_APP_CHAIN=\"ELEMENTS\"\n\
+         REACT_APP_ESPLORA_API_URL=\"{}\"\n\
+         REACT_APP_LBTC_ASSET_ID=\"{}\"\n\
+         REACT_APP_LUSDT_ASSET_ID=\"{}\"\n",
+        esplora_url.as_str().trim_end_matches('/'),
+        btc_asset_id,
+        usdt_asset_id,
+    )
+}
@@ -1,84 +1,101 @@
 use anyhow::Result;
 use bobtimus::{
-    cli::Config,
-    database::Sqlite,
-    elements_rpc::{Client, ElementsRpc},
-    fixed_rate, http, liquidate_loans, Bobtimus, LiquidUsdt,
+    app, cli::Config, elements_rpc::ElementsRpc, fixed_rate, http, logging, problem,
+    rate_limit::RateLimiter, Bobtimus, LiquidUsdt,
 };
-use elements::{
-    bitcoin::{secp256k1::Secp256k1, Amount},
-    secp256k1_zkp::rand::{rngs::StdRng, thread_rng, SeedableRng},
-    Address,
+use elements::{bitcoin::Amount, Address};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
-use std::{collections::HashMap, sync::Arc};
-use tokio::sync::Mutex;
 use warp::{Filter, Rejection, Reply};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-
-    match Config::parse()? {
-        Config::Start {
-            elementsd_url,
-            api_port,
-            usdt_asset_id,
-            db_file,
-        } => {
-            let db = Sqlite::new(db_file.as_path())?;
-
-            let elementsd = Client::new(elementsd_url.into())?;
-            let btc_asset_id = elementsd.get_bitcoin_asset_id().await?;
-
-            let rate_service = fixed_rate::Service::new();
-            let subscription = rate_service.subscribe();
-
-            let bobtimus = Bobtimus {
-                rng: StdRng::from_rng(&mut thread_rng()).unwrap(),
-                rate_service,
-                secp: Secp256k1::new(),
-                elementsd,
-                btc_asset_id,
-                usdt_asset_id,
-                db,
-                lender_states: HashMap::new(),
-            };
-            let bobtimus = Arc::new(Mutex::new(bobtimus));
-
-            let routes = http::routes(bobtimus.clone(), subscription);
-
-            let cors = warp::cors().allow_any_origin();
-
+    logging::init()?;
+
+    app::run_with_routes(
+        Config::parse()?,
+        |_| async { Ok(fixed_rate::Service::new()) },
+        |bobtimus, api_key| async move {
+            // anyone can drain the faucet, so we only ever run it
+            // against a regtest node
+            let chain = bobtimus.elementsd.getblockchaininfo().await?.chain;
+            let faucet_enabled = chain == REGTEST_CHAIN;
+            if !faucet_enabled {
+                tracing::warn!("Faucet disabled because elementsd runs on {}", chain);
+            }
+
+            let faucet_limits = Arc::new(Mutex::new(FaucetLimits::default()));
             let faucet = warp::post()
                 .and(warp::path!("api" / "faucet" / Address))
-                .and_then(move |address| {
+                .and(warp::addr::remote())
+                .and(http::authenticate(api_key))
+                .and_then(move |address, remote: Option<SocketAddr>| {
                     let bobtimus = bobtimus.clone();
+                    let faucet_limits = faucet_limits.clone();
                     async move {
-                        let mut bobtimus = bobtimus.lock().await;
-                        faucet(&mut bobtimus, address).await
+                        if !faucet_enabled {
+                            return Err(warp::reject::not_found());
+                        }
+
+                        faucet_limits
+                            .lock()
+                            .expect("lock is never poisoned")
+                            .check(remote.map(|remote| remote.ip()), &address)
+                            .map_err(problem::from_anyhow)
+                            .map_err(warp::reject::custom)?;
+
+                        faucet(&bobtimus, address).await
                     }
                 });
 
-            warp::serve(routes.or(faucet).with(cors))
-                .run(([127, 0, 0, 1], api_port))
-                .await;
+            Result::<_, anyhow::Error>::Ok(Some(http::traced(
+                faucet.recover(problem::unpack_problem),
+            )))
+        },
+    )
+    .await
+}
+
+/// Name of the chain elementsd reports in regtest mode.
+const REGTEST_CHAIN: &str = "elementsregtest";
+
+/// How long a client has to wait before it can use the faucet again.
+const FAUCET_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Limits how often the faucet can be used, both per client IP and
+/// per funded address.
+struct FaucetLimits {
+    by_ip: RateLimiter<IpAddr>,
+    by_address: RateLimiter<String>,
+}
+
+impl Default for FaucetLimits {
+    fn default() -> Self {
+        Self {
+            by_ip: RateLimiter::new(FAUCET_COOLDOWN),
+            by_address: RateLimiter::new(FAUCET_COOLDOWN),
         }
-        Config::LiquidateLoans {
-            elementsd_url,
-            db_file,
-        } => {
-            let db = Sqlite::new(db_file.as_path())?;
-            let elementsd = Client::new(elementsd_url.into())?;
-
-            liquidate_loans(&elementsd, db).await?;
+    }
+}
+
+impl FaucetLimits {
+    fn check(&mut self, ip: Option<IpAddr>, address: &Address) -> Result<()> {
+        let now = Instant::now();
+
+        if let Some(ip) = ip {
+            self.by_ip.try_acquire(ip, now)?;
         }
-    };
+        self.by_address.try_acquire(address.to_string(), now)?;
 
-    Ok(())
+        Ok(())
+    }
 }
 
 async fn faucet<R, RS>(
-    bobtimus: &mut Bobtimus<R, RS>,
+    bobtimus: &Bobtimus<R, RS>,
     address: Address,
 ) -> Result<impl Reply, Rejection> {
     let mut txids = Vec::new();
@@ -1,10 +1,26 @@
-use crate::USDT_ASSET_ID;
-use anyhow::{Context, Result};
+use crate::{
+    cold_storage::SweepPolicy,
+    database::DatabaseConfig,
+    http::{AllowedOrigins, CorsConfig},
+    inventory::{AssetLimits, RebalanceAlerts, RebalanceThresholds},
+    lending::LendingParameters,
+    market::PairConfig,
+    quote::QuoteParameters,
+    webhooks::Webhooks,
+    LiquidUsdt,
+};
+use anyhow::{anyhow, Context, Result};
 use directories::ProjectDirs;
-use elements::AssetId;
+use elements::{
+    bitcoin::{Amount, Denomination},
+    secp256k1_zkp::SecretKey,
+    Address, AssetId, Txid,
+};
+use network_config::Network;
 use reqwest::Url;
 use std::path::PathBuf;
 use structopt::StructOpt;
+use warp::http::{header::HeaderName, Method};
 
 #[derive(structopt::StructOpt, Debug)]
 #[structopt(name = "bobtimus", about = "Auto-trader for L-BTC/L-USDt")]
@@ -12,21 +28,284 @@ pub enum Command {
     Start {
         #[structopt(default_value = "http://127.0.0.1:7042", long = "elementsd")]
         elementsd_url: Url,
+        /// Use this wallet of elementsd instead of the default one, if it
+        /// has several loaded
+        #[structopt(long = "wallet-name")]
+        wallet_name: Option<String>,
         #[structopt(default_value = "3030")]
         api_port: u16,
-        #[structopt(
-        default_value = USDT_ASSET_ID,
-        long = "usdt"
-    )]
-        usdt_asset_id: AssetId,
+        /// Network elementsd runs on: liquid, liquid-testnet or elements
+        #[structopt(default_value = "liquid", long = "network")]
+        network: Network,
+        /// Asset id of L-USDt, required unless the network has a
+        /// well-known one
+        #[structopt(long = "usdt")]
+        usdt_asset_id: Option<AssetId>,
+        /// Asset id of the chain's native asset, discovered from elementsd
+        /// if not given
+        #[structopt(long = "native-asset-id")]
+        native_asset_id: Option<AssetId>,
         #[structopt(short, parse(from_os_str))]
         db_file: Option<PathBuf>,
+        /// Use the Postgres database at this URL instead of an SQLite file
+        #[structopt(long = "postgres-url", conflicts_with = "db-file")]
+        postgres_url: Option<String>,
+        /// Spread applied to each side of the market rate, in basis points
+        #[structopt(default_value = "0", long = "spread-bps")]
+        spread_bps: u16,
+        /// Minimum swap size we quote for, in L-USDt
+        #[structopt(
+            default_value = "0",
+            long = "min-quote",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        min_quote: LiquidUsdt,
+        /// Maximum swap size we quote for, in L-USDt
+        #[structopt(long = "max-quote", parse(try_from_str = LiquidUsdt::from_str_in_dollar))]
+        max_quote: Option<LiquidUsdt>,
+        /// Minimum L-BTC balance we keep, in L-BTC
+        #[structopt(long = "min-btc-inventory", parse(try_from_str = parse_btc))]
+        min_btc_inventory: Option<Amount>,
+        /// Maximum L-BTC balance we hold, in L-BTC
+        #[structopt(long = "max-btc-inventory", parse(try_from_str = parse_btc))]
+        max_btc_inventory: Option<Amount>,
+        /// Minimum L-USDt balance we keep, in L-USDt
+        #[structopt(
+            long = "min-usdt-inventory",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        min_usdt_inventory: Option<LiquidUsdt>,
+        /// Maximum L-USDt balance we hold, in L-USDt
+        #[structopt(
+            long = "max-usdt-inventory",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        max_usdt_inventory: Option<LiquidUsdt>,
+        /// Warn when our L-BTC balance drops below this, in L-BTC
+        #[structopt(long = "rebalance-low-btc", parse(try_from_str = parse_btc))]
+        rebalance_low_btc: Option<Amount>,
+        /// Warn when our L-BTC balance rises above this, in L-BTC
+        #[structopt(long = "rebalance-high-btc", parse(try_from_str = parse_btc))]
+        rebalance_high_btc: Option<Amount>,
+        /// Warn when our L-USDt balance drops below this, in L-USDt
+        #[structopt(
+            long = "rebalance-low-usdt",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        rebalance_low_usdt: Option<LiquidUsdt>,
+        /// Warn when our L-USDt balance rises above this, in L-USDt
+        #[structopt(
+            long = "rebalance-high-usdt",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        rebalance_high_usdt: Option<LiquidUsdt>,
+        /// URL to POST rebalance alerts to as JSON
+        #[structopt(long = "rebalance-webhook")]
+        rebalance_webhook: Option<Url>,
+        /// URL to POST trade and loan events to as JSON, can be given
+        /// multiple times
+        #[structopt(long = "webhook", number_of_values = 1)]
+        webhooks: Vec<Url>,
+        /// Secret to sign the events sent to webhooks with
+        #[structopt(long = "webhook-secret")]
+        webhook_secret: Option<String>,
+        /// Round quoted prices to a multiple of this amount, in L-USDt
+        #[structopt(
+            default_value = "0.01",
+            long = "rate-rounding",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        rate_rounding: LiquidUsdt,
+        /// Upper bound on the ratio between loan principal and collateral value, in basis
+        /// points. baru picks the principal itself, we only refuse loans above this ratio
+        #[structopt(default_value = "7000", long = "loan-max-ltv-bps")]
+        loan_max_ltv_bps: u16,
+        /// Ratio between loan principal and collateral value at which we liquidate, in basis
+        /// points
+        #[structopt(default_value = "8500", long = "loan-liquidation-ltv-bps")]
+        loan_liquidation_ltv_bps: u16,
+        /// Shortest loan duration we accept, in blocks
+        #[structopt(default_value = "1440", long = "loan-min-timelock")]
+        loan_min_timelock: u32,
+        /// Longest loan duration we accept, in blocks
+        #[structopt(default_value = "43200", long = "loan-max-timelock")]
+        loan_max_timelock: u32,
+        /// Biggest principal we lend in a single loan, in L-USDt
+        #[structopt(
+            default_value = "100000",
+            long = "loan-max-principal",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        loan_max_principal: LiquidUsdt,
+        /// Additional pair to make a market in, can be repeated
+        ///
+        /// Format: <name>,<base-asset-id>,<quote-asset-id>,<rate-source>,<spread-bps>,
+        /// <base-limits>,<quote-limits>[,<quote-size>], where the rate source is either
+        /// kraken:<PAIR> or fixed:<ask>:<bid>, and the inventory limits of each asset and
+        /// the bounds of the swap size are <min>:<max> in whole units, either of which may
+        /// be empty
+        #[structopt(long = "pair", number_of_values = 1)]
+        pairs: Vec<PairConfig>,
+        /// Require this API key on endpoints which move funds
+        #[structopt(long = "api-key")]
+        api_key: Option<String>,
+        /// Hex-encoded key to sign price attestations with, for loan
+        /// covenants which trust us as their oracle
+        #[structopt(long = "oracle-key")]
+        oracle_key: Option<SecretKey>,
+        /// Origin allowed to make cross-origin requests, can be repeated
+        #[structopt(long = "cors-origin", number_of_values = 1)]
+        cors_origins: Vec<Url>,
+        /// Allow cross-origin requests from any origin
+        #[structopt(long = "cors-allow-any-origin", conflicts_with = "cors-origins")]
+        cors_allow_any_origin: bool,
+        /// Methods allowed in cross-origin requests
+        #[structopt(
+            long = "cors-methods",
+            default_value = "GET,POST,DELETE",
+            use_delimiter = true
+        )]
+        cors_methods: Vec<Method>,
+        /// Headers allowed in cross-origin requests
+        #[structopt(
+            long = "cors-headers",
+            default_value = "accept,authorization,content-type,idempotency-key,x-request-id",
+            use_delimiter = true
+        )]
+        cors_headers: Vec<HeaderName>,
+        /// Address to sweep funds above the hot wallet limits to
+        #[structopt(long = "cold-address")]
+        cold_address: Option<Address>,
+        /// Maximum L-BTC balance to keep in the hot wallet, in L-BTC
+        #[structopt(
+            long = "max-hot-btc",
+            requires = "cold-address",
+            parse(try_from_str = parse_btc)
+        )]
+        max_hot_btc: Option<Amount>,
+        /// Maximum L-USDt balance to keep in the hot wallet, in L-USDt
+        #[structopt(
+            long = "max-hot-usdt",
+            requires = "cold-address",
+            parse(try_from_str = LiquidUsdt::from_str_in_dollar)
+        )]
+        max_hot_usdt: Option<LiquidUsdt>,
+        /// Sign transactions but never broadcast them, to check the
+        /// configuration before putting real funds behind it
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
     },
     LiquidateLoans {
         #[structopt(default_value = "http://127.0.0.1:7042", long = "elementsd")]
         elementsd_url: Url,
+        /// Use this wallet of elementsd instead of the default one, if it
+        /// has several loaded
+        #[structopt(long = "wallet-name")]
+        wallet_name: Option<String>,
+        #[structopt(short, parse(from_os_str))]
+        db_file: Option<PathBuf>,
+        /// Use the Postgres database at this URL instead of an SQLite file
+        #[structopt(long = "postgres-url", conflicts_with = "db-file")]
+        postgres_url: Option<String>,
+        /// URL to POST trade and loan events to as JSON, can be given
+        /// multiple times
+        #[structopt(long = "webhook", number_of_values = 1)]
+        webhooks: Vec<Url>,
+        /// Secret to sign the events sent to webhooks with
+        #[structopt(long = "webhook-secret")]
+        webhook_secret: Option<String>,
+        /// Sign transactions but never broadcast them, to check the
+        /// configuration before putting real funds behind it
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Broadcast the liquidation transaction of a single loan now
+    Liquidate {
+        /// Transaction ID of the loan to liquidate
+        loan_id: Txid,
+        #[structopt(default_value = "http://127.0.0.1:7042", long = "elementsd")]
+        elementsd_url: Url,
+        /// Use this wallet of elementsd instead of the default one, if it
+        /// has several loaded
+        #[structopt(long = "wallet-name")]
+        wallet_name: Option<String>,
+        #[structopt(short, parse(from_os_str))]
+        db_file: Option<PathBuf>,
+        /// Use the Postgres database at this URL instead of an SQLite file
+        #[structopt(long = "postgres-url", conflicts_with = "db-file")]
+        postgres_url: Option<String>,
+        /// URL to POST trade and loan events to as JSON, can be given
+        /// multiple times
+        #[structopt(long = "webhook", number_of_values = 1)]
+        webhooks: Vec<Url>,
+        /// Secret to sign the events sent to webhooks with
+        #[structopt(long = "webhook-secret")]
+        webhook_secret: Option<String>,
+        /// Sign transactions but never broadcast them, to check the
+        /// configuration before putting real funds behind it
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
+    },
+    /// Print the balance of the hot wallet per asset
+    Balances {
+        #[structopt(default_value = "http://127.0.0.1:7042", long = "elementsd")]
+        elementsd_url: Url,
+        /// Use this wallet of elementsd instead of the default one, if it
+        /// has several loaded
+        #[structopt(long = "wallet-name")]
+        wallet_name: Option<String>,
+        /// Network elementsd runs on: liquid, liquid-testnet or elements
+        #[structopt(default_value = "liquid", long = "network")]
+        network: Network,
+        /// Asset id of L-USDt, required unless the network has a
+        /// well-known one
+        #[structopt(long = "usdt")]
+        usdt_asset_id: Option<AssetId>,
+        /// Asset id of the chain's native asset, discovered from elementsd
+        /// if not given
+        #[structopt(long = "native-asset-id")]
+        native_asset_id: Option<AssetId>,
+    },
+    /// Print all recorded trades as CSV
+    ExportTrades {
         #[structopt(short, parse(from_os_str))]
         db_file: Option<PathBuf>,
+        /// Use the Postgres database at this URL instead of an SQLite file
+        #[structopt(long = "postgres-url", conflicts_with = "db-file")]
+        postgres_url: Option<String>,
+        /// Write the CSV to this file instead of stdout
+        #[structopt(long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Print the profit and loss realized from the recorded trades and
+    /// loans
+    Report {
+        #[structopt(short, parse(from_os_str))]
+        db_file: Option<PathBuf>,
+        /// Use the Postgres database at this URL instead of an SQLite file
+        #[structopt(long = "postgres-url", conflicts_with = "db-file")]
+        postgres_url: Option<String>,
+    },
+    /// Send funds from the hot wallet to an address, e.g. cold storage
+    Withdraw {
+        #[structopt(default_value = "http://127.0.0.1:7042", long = "elementsd")]
+        elementsd_url: Url,
+        /// Use this wallet of elementsd instead of the default one, if it
+        /// has several loaded
+        #[structopt(long = "wallet-name")]
+        wallet_name: Option<String>,
+        #[structopt(long = "address")]
+        address: Address,
+        /// Asset to withdraw, L-BTC if not given
+        #[structopt(long = "asset")]
+        asset_id: Option<AssetId>,
+        /// Amount to withdraw, in whole units of the asset
+        #[structopt(long = "amount", parse(try_from_str = parse_btc))]
+        amount: Amount,
+        /// Sign transactions but never broadcast them, to check the
+        /// configuration before putting real funds behind it
+        #[structopt(long = "dry-run")]
+        dry_run: bool,
     },
 }
 
@@ -35,11 +314,54 @@ pub enum Config {
         elementsd_url: Url,
         api_port: u16,
         usdt_asset_id: AssetId,
-        db_file: PathBuf,
+        native_asset_id: Option<AssetId>,
+        database: DatabaseConfig,
+        quote_params: QuoteParameters,
+        /// Inventory limits of the L-BTC of the default pair.
+        btc_inventory: AssetLimits,
+        /// Inventory limits of the L-USDt of the default pair.
+        usdt_inventory: AssetLimits,
+        rebalance_alerts: RebalanceAlerts,
+        webhooks: Webhooks,
+        lending_params: LendingParameters,
+        pairs: Vec<PairConfig>,
+        api_key: Option<String>,
+        oracle_key: Option<SecretKey>,
+        cors: CorsConfig,
+        sweep_policy: Option<SweepPolicy>,
+        dry_run: bool,
     },
     LiquidateLoans {
         elementsd_url: Url,
-        db_file: PathBuf,
+        database: DatabaseConfig,
+        webhooks: Webhooks,
+        dry_run: bool,
+    },
+    Liquidate {
+        loan_id: Txid,
+        elementsd_url: Url,
+        database: DatabaseConfig,
+        webhooks: Webhooks,
+        dry_run: bool,
+    },
+    Balances {
+        elementsd_url: Url,
+        usdt_asset_id: AssetId,
+        native_asset_id: Option<AssetId>,
+    },
+    ExportTrades {
+        database: DatabaseConfig,
+        output: Option<PathBuf>,
+    },
+    Report {
+        database: DatabaseConfig,
+    },
+    Withdraw {
+        elementsd_url: Url,
+        address: Address,
+        asset_id: Option<AssetId>,
+        amount: Amount,
+        dry_run: bool,
     },
 }
 
@@ -48,21 +370,177 @@ impl Config {
         let config = match Command::from_args() {
             Command::Start {
                 elementsd_url,
+                wallet_name,
                 api_port,
+                network,
                 usdt_asset_id,
+                native_asset_id,
                 db_file,
+                postgres_url,
+                spread_bps,
+                min_quote,
+                max_quote,
+                min_btc_inventory,
+                max_btc_inventory,
+                min_usdt_inventory,
+                max_usdt_inventory,
+                rebalance_low_btc,
+                rebalance_high_btc,
+                rebalance_low_usdt,
+                rebalance_high_usdt,
+                rebalance_webhook,
+                webhooks,
+                webhook_secret,
+                rate_rounding,
+                loan_max_ltv_bps,
+                loan_liquidation_ltv_bps,
+                loan_min_timelock,
+                loan_max_timelock,
+                loan_max_principal,
+                pairs,
+                api_key,
+                oracle_key,
+                cors_origins,
+                cors_allow_any_origin,
+                cors_methods,
+                cors_headers,
+                cold_address,
+                max_hot_btc,
+                max_hot_usdt,
+                dry_run,
             } => Config::Start {
-                elementsd_url,
+                elementsd_url: wallet_url(elementsd_url, wallet_name)?,
                 api_port,
-                usdt_asset_id,
-                db_file: resolve_db_file(db_file)?,
+                usdt_asset_id: resolve_usdt_asset_id(network, usdt_asset_id)?,
+                native_asset_id,
+                database: resolve_database(db_file, postgres_url)?,
+                quote_params: QuoteParameters {
+                    spread_bps,
+                    min_quote: min_quote.into(),
+                    max_quote: max_quote.map(Amount::from),
+                    rounding: rate_rounding,
+                },
+                btc_inventory: AssetLimits {
+                    min: min_btc_inventory,
+                    max: max_btc_inventory,
+                },
+                usdt_inventory: AssetLimits {
+                    min: min_usdt_inventory.map(Amount::from),
+                    max: max_usdt_inventory.map(Amount::from),
+                },
+                rebalance_alerts: RebalanceAlerts {
+                    thresholds: RebalanceThresholds {
+                        low_btc: rebalance_low_btc,
+                        high_btc: rebalance_high_btc,
+                        low_usdt: rebalance_low_usdt,
+                        high_usdt: rebalance_high_usdt,
+                    },
+                    webhook: rebalance_webhook,
+                },
+                webhooks: Webhooks {
+                    urls: webhooks,
+                    secret: webhook_secret,
+                },
+                lending_params: LendingParameters {
+                    max_ltv_bps: loan_max_ltv_bps,
+                    liquidation_ltv_bps: loan_liquidation_ltv_bps,
+                    min_timelock: loan_min_timelock,
+                    max_timelock: loan_max_timelock,
+                    max_principal: loan_max_principal,
+                },
+                pairs,
+                api_key,
+                oracle_key,
+                cors: CorsConfig {
+                    allowed_origins: if cors_allow_any_origin {
+                        AllowedOrigins::Any
+                    } else {
+                        AllowedOrigins::Only(cors_origins)
+                    },
+                    allowed_methods: cors_methods,
+                    allowed_headers: cors_headers,
+                },
+                sweep_policy: cold_address.map(|cold_address| SweepPolicy {
+                    cold_address,
+                    max_hot_btc,
+                    max_hot_usdt,
+                }),
+                dry_run,
             },
             Command::LiquidateLoans {
                 elementsd_url,
+                wallet_name,
                 db_file,
+                postgres_url,
+                webhooks,
+                webhook_secret,
+                dry_run,
             } => Config::LiquidateLoans {
+                elementsd_url: wallet_url(elementsd_url, wallet_name)?,
+                database: resolve_database(db_file, postgres_url)?,
+                webhooks: Webhooks {
+                    urls: webhooks,
+                    secret: webhook_secret,
+                },
+                dry_run,
+            },
+            Command::Liquidate {
+                loan_id,
+                elementsd_url,
+                wallet_name,
+                db_file,
+                postgres_url,
+                webhooks,
+                webhook_secret,
+                dry_run,
+            } => Config::Liquidate {
+                loan_id,
+                elementsd_url: wallet_url(elementsd_url, wallet_name)?,
+                database: resolve_database(db_file, postgres_url)?,
+                webhooks: Webhooks {
+                    urls: webhooks,
+                    secret: webhook_secret,
+                },
+                dry_run,
+            },
+            Command::Balances {
+                elementsd_url,
+                wallet_name,
+                network,
+                usdt_asset_id,
+                native_asset_id,
+            } => Config::Balances {
+                elementsd_url: wallet_url(elementsd_url, wallet_name)?,
+                usdt_asset_id: resolve_usdt_asset_id(network, usdt_asset_id)?,
+                native_asset_id,
+            },
+            Command::ExportTrades {
+                db_file,
+                postgres_url,
+                output,
+            } => Config::ExportTrades {
+                database: resolve_database(db_file, postgres_url)?,
+                output,
+            },
+            Command::Report {
+                db_file,
+                postgres_url,
+            } => Config::Report {
+                database: resolve_database(db_file, postgres_url)?,
+            },
+            Command::Withdraw {
                 elementsd_url,
-                db_file: resolve_db_file(db_file)?,
+                wallet_name,
+                address,
+                asset_id,
+                amount,
+                dry_run,
+            } => Config::Withdraw {
+                elementsd_url: wallet_url(elementsd_url, wallet_name)?,
+                address,
+                asset_id,
+                amount,
+                dry_run,
             },
         };
 
@@ -70,6 +548,48 @@ impl Config {
     }
 }
 
+fn resolve_usdt_asset_id(network: Network, usdt_asset_id: Option<AssetId>) -> Result<AssetId> {
+    usdt_asset_id
+        .or_else(|| network.usdt_asset_id())
+        .with_context(|| format!("--usdt is required on {}", network))
+}
+
+fn parse_btc(s: &str) -> Result<Amount> {
+    let amount = Amount::from_str_in(s, Denomination::Bitcoin)?;
+
+    Ok(amount)
+}
+
+/// The URL of the RPC endpoint of elementsd scoped to the wallet with
+/// `wallet_name`, or the default wallet if there is none.
+fn wallet_url(mut elementsd_url: Url, wallet_name: Option<String>) -> Result<Url> {
+    if let Some(wallet_name) = wallet_name {
+        elementsd_url
+            .path_segments_mut()
+            .map_err(|_| anyhow!("elementsd URL cannot have a wallet path"))?
+            .pop_if_empty()
+            .push("wallet")
+            .push(&wallet_name);
+    }
+
+    Ok(elementsd_url)
+}
+
+fn resolve_database(
+    db_file: Option<PathBuf>,
+    postgres_url: Option<String>,
+) -> Result<DatabaseConfig> {
+    match postgres_url {
+        #[cfg(feature = "postgres")]
+        Some(url) => Ok(DatabaseConfig::Postgres(url)),
+        #[cfg(not(feature = "postgres"))]
+        Some(_) => Err(anyhow!(
+            "bobtimus was built without Postgres support, enable the `postgres` feature"
+        )),
+        None => Ok(DatabaseConfig::Sqlite(resolve_db_file(db_file)?)),
+    }
+}
+
 fn resolve_db_file(db_file: Option<PathBuf>) -> Result<PathBuf, anyhow::Error> {
     Ok(match db_file {
         None => {
@@ -0,0 +1,121 @@
+use crate::{elements_rpc::Client, shutdown::Shutdown, LiquidUsdt};
+use anyhow::{Context, Result};
+use elements::{bitcoin::Amount, Address, AssetId, Txid};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// How often we check whether our hot wallet holds too much.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Keep at most this much of each asset in the online elementsd
+/// wallet, moving the rest to cold storage.
+#[derive(Debug, Clone)]
+pub struct SweepPolicy {
+    pub cold_address: Address,
+    pub max_hot_btc: Option<Amount>,
+    pub max_hot_usdt: Option<LiquidUsdt>,
+}
+
+/// Periodically move everything above the limits of the `policy` to
+/// its cold address, until the `shutdown` is triggered.
+pub async fn run_sweeper(
+    elementsd: Client,
+    policy: SweepPolicy,
+    btc_asset_id: AssetId,
+    usdt_asset_id: AssetId,
+    shutdown: Shutdown,
+) {
+    loop {
+        let limits = [
+            (btc_asset_id, policy.max_hot_btc),
+            (usdt_asset_id, policy.max_hot_usdt.map(Amount::from)),
+        ];
+        for (asset_id, max_hot) in limits.iter() {
+            let max_hot = match max_hot {
+                Some(max_hot) => *max_hot,
+                None => continue,
+            };
+
+            if let Err(e) = sweep(&elementsd, &policy.cold_address, *asset_id, max_hot).await {
+                tracing::error!(
+                    "failed to sweep asset {} to cold storage: {:#}",
+                    asset_id,
+                    e
+                );
+            }
+        }
+
+        tokio::select! {
+            _ = sleep(SWEEP_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => return,
+        }
+    }
+}
+
+/// Send everything above `max_hot` of `asset_id` to the
+/// `cold_address`.
+#[tracing::instrument(skip(elementsd))]
+async fn sweep(
+    elementsd: &Client,
+    cold_address: &Address,
+    asset_id: AssetId,
+    max_hot: Amount,
+) -> Result<Option<Txid>> {
+    let balance = elementsd.get_balance(asset_id).await?;
+    let excess = match excess(balance, max_hot) {
+        Some(excess) => excess,
+        None => return Ok(None),
+    };
+
+    let txid = withdraw(elementsd, cold_address, asset_id, excess).await?;
+    tracing::info!(
+        "Swept {} of asset {} to cold storage in transaction {}",
+        excess,
+        asset_id,
+        txid
+    );
+
+    Ok(Some(txid))
+}
+
+/// Send `amount` of `asset_id` from the hot wallet to `address`.
+#[tracing::instrument(skip(elementsd))]
+pub async fn withdraw(
+    elementsd: &Client,
+    address: &Address,
+    asset_id: AssetId,
+    amount: Amount,
+) -> Result<Txid> {
+    elementsd
+        .send_asset_to_address(address, amount, Some(asset_id))
+        .await
+        .with_context(|| {
+            format!(
+                "failed to send {} of asset {} to {}",
+                amount, asset_id, address
+            )
+        })
+}
+
+fn excess(balance: Amount, max_hot: Amount) -> Option<Amount> {
+    balance
+        .checked_sub(max_hot)
+        .filter(|excess| *excess > Amount::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_sweeps_balance_above_limit() {
+        let max_hot = Amount::from_btc(1.0).unwrap();
+
+        assert_eq!(
+            excess(Amount::from_btc(1.5).unwrap(), max_hot),
+            Some(Amount::from_btc(0.5).unwrap())
+        );
+        assert_eq!(excess(Amount::from_btc(1.0).unwrap(), max_hot), None);
+        assert_eq!(excess(Amount::from_btc(0.5).unwrap(), max_hot), None);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::fee;
 use anyhow::{bail, Context, Result};
 use bitcoin_hashes::hex::FromHex;
 use elements::{
@@ -8,12 +9,24 @@ use elements::{
     Address, AssetId, OutPoint, Transaction, TxOut, TxOutWitness, Txid,
 };
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, str::FromStr};
+use std::{collections::HashMap, future::Future, str::FromStr, time::Duration};
+use tokio::time::sleep;
+
+/// How long we wait for elementsd to answer a single request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long we wait to establish a connection to elementsd.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often we try a request before giving up on an unreachable
+/// elementsd.
+const MAX_ATTEMPTS: u32 = 6;
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(500);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(10);
 
 #[jsonrpc_client::api(version = "1.0")]
 pub trait ElementsRpc {
     async fn getblockchaininfo(&self) -> BlockchainInfo;
     async fn getblockcount(&self) -> u32;
+    async fn getsidechaininfo(&self) -> SidechainInfo;
     async fn getnewaddress(&self, label: &str, address_type: Option<&str>) -> Address;
     #[allow(clippy::too_many_arguments)]
     async fn sendtoaddress(
@@ -63,6 +76,7 @@ pub trait ElementsRpc {
     async fn dumpmasterblindingkey(&self) -> String;
     async fn unblindrawtransaction(&self, tx_hex: String) -> UnblindRawTransactionResponse;
     async fn lockunspent(&self, unlock: bool, utxos: Vec<OutPoint>) -> bool;
+    async fn listlockunspent(&self) -> Vec<LockedUtxo>;
     async fn reissueasset(&self, asset: AssetId, amount: f64) -> ReissueAssetResponse;
     async fn getaddressinfo(&self, address: &Address) -> GetAddressInfoResponse;
     async fn listreceivedbyaddress(
@@ -88,6 +102,18 @@ pub trait ElementsRpc {
     async fn finalizepsbt(&self, psbt: String, extract: Option<bool>) -> FinalizePsbtResponse;
     async fn signmessage(&self, address: &Address, message: String) -> String;
     async fn dumpprivkey(&self, address: &Address) -> String;
+    async fn gettxout(
+        &self,
+        txid: Txid,
+        n: u32,
+        include_mempool: Option<bool>,
+    ) -> Option<GetTxOutResponse>;
+    async fn gettransaction(&self, txid: Txid) -> GetTransactionResponse;
+    async fn estimatesmartfee(
+        &self,
+        conf_target: u32,
+        estimate_mode: Option<&str>,
+    ) -> EstimateSmartFeeResponse;
 }
 
 #[jsonrpc_client::implement(ElementsRpc)]
@@ -95,6 +121,7 @@ pub trait ElementsRpc {
 pub struct Client {
     inner: reqwest::Client,
     base_url: reqwest::Url,
+    dry_run: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -148,15 +175,37 @@ pub struct FinalizePsbtResponse {
 }
 
 impl Client {
+    /// Connect to the elementsd RPC interface at `base_url`.
+    ///
+    /// Requests which only read from elementsd are retried while the
+    /// node is unreachable, e.g. because it is restarting. Requests
+    /// which move funds are never retried, because we cannot know
+    /// whether a request which timed out was executed.
     pub fn new(base_url: String) -> Result<Self> {
+        let inner = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .connect_timeout(CONNECT_TIMEOUT)
+            .build()
+            .context("failed to build HTTP client")?;
+
         Ok(Self {
-            inner: reqwest::Client::new(),
+            inner,
             base_url: base_url.parse()?,
+            dry_run: false,
         })
     }
 
+    /// In dry-run mode we still build and sign every transaction, but
+    /// never broadcast one or lock any of our UTXOs.
+    ///
+    /// Transactions we sign for others are handed out without our
+    /// signatures, so that nobody else can broadcast them either.
+    pub fn with_dry_run(self, dry_run: bool) -> Self {
+        Self { dry_run, ..self }
+    }
+
     async fn get_new_address(&self, address_type: Option<&str>) -> Result<Address> {
-        let address = self.getnewaddress("", address_type).await?;
+        let address = retry(|| self.getnewaddress("", address_type)).await?;
 
         Ok(address)
     }
@@ -165,14 +214,14 @@ impl Client {
         self.get_new_address(Some("blech32")).await
     }
 
+    /// The asset id of the chain's native asset, i.e. L-BTC on the
+    /// Liquid mainnet, in which all fees are paid.
     pub async fn get_bitcoin_asset_id(&self) -> Result<AssetId> {
-        let labels = self.dumpassetlabels().await?;
-        let bitcoin_asset_tag = "bitcoin";
-        let bitcoin_asset_id = labels
-            .get(bitcoin_asset_tag)
+        let info = retry(|| self.getsidechaininfo())
+            .await
             .context("failed to get asset id for bitcoin")?;
 
-        Ok(*bitcoin_asset_id)
+        Ok(info.pegged_asset)
     }
 
     pub async fn send_asset_to_address(
@@ -181,6 +230,27 @@ impl Client {
         amount: Amount,
         asset_id: Option<AssetId>,
     ) -> Result<Txid> {
+        if self.dry_run {
+            let asset_id = match asset_id {
+                Some(asset_id) => asset_id,
+                None => self.get_bitcoin_asset_id().await?,
+            };
+            let tx = Transaction {
+                output: vec![TxOut {
+                    asset: Asset::Explicit(asset_id),
+                    value: Value::Explicit(amount.as_sat()),
+                    nonce: Nonce::Null,
+                    script_pubkey: address.script_pubkey(),
+                    witness: TxOutWitness::default(),
+                }],
+                ..Default::default()
+            };
+            let tx = self.fund_raw_transaction(&tx).await?;
+            let tx = self.sign_raw_transaction(&tx).await?;
+
+            return self.send_raw_transaction(&tx).await;
+        }
+
         let txid = self
             .sendtoaddress(
                 address,
@@ -199,14 +269,28 @@ impl Client {
         Ok(txid)
     }
 
+    pub async fn get_balance(&self, asset_id: AssetId) -> Result<Amount> {
+        let balance = retry(|| self.getbalance(None, None, None, Some(asset_id))).await?;
+        let balance = Amount::from_btc(balance)
+            .with_context(|| format!("failed to parse balance {}", balance))?;
+
+        Ok(balance)
+    }
+
     pub async fn get_raw_transaction(&self, txid: Txid) -> Result<Transaction> {
-        let tx_hex = self.getrawtransaction(txid).await?;
+        let tx_hex = retry(|| self.getrawtransaction(txid)).await?;
         let tx = elements::encode::deserialize(&Vec::<u8>::from_hex(&tx_hex).unwrap())?;
 
         Ok(tx)
     }
 
+    #[tracing::instrument(level = "debug", skip(self, tx), fields(txid = %tx.txid()))]
     pub async fn send_raw_transaction(&self, tx: &Transaction) -> Result<Txid> {
+        if self.dry_run {
+            tracing::info!("Dry run, not broadcasting transaction");
+            return Ok(tx.txid());
+        }
+
         let tx_hex = serialize_hex(tx);
         let txid = self.sendrawtransaction(tx_hex).await?;
         Ok(txid)
@@ -214,7 +298,7 @@ impl Client {
 
     pub async fn unblind_raw_transaction(&self, tx: &Transaction) -> Result<Transaction> {
         let tx_hex = serialize_hex(tx);
-        let res = self.unblindrawtransaction(tx_hex).await?;
+        let res = retry(|| self.unblindrawtransaction(tx_hex.clone())).await?;
         let tx = elements::encode::deserialize(&Vec::<u8>::from_hex(&res.hex).unwrap())?;
 
         Ok(tx)
@@ -227,6 +311,7 @@ impl Client {
     /// If `should_lock` is set to true, all selected UTXOs will be
     /// exempt from being chosen again unless explicitly unlocked or
     /// after the elementsd node has been restarted.
+    #[tracing::instrument(level = "debug", skip(self))]
     pub async fn select_inputs_for(
         &self,
         asset: AssetId,
@@ -246,8 +331,7 @@ impl Client {
         };
 
         let tx_hex = serialize_hex(&tx);
-        let res = self
-            .fundrawtransaction(tx_hex)
+        let res = retry(|| self.fundrawtransaction(tx_hex.clone()))
             .await
             .context("cannot fund raw transaction")?;
 
@@ -294,23 +378,32 @@ impl Client {
         Ok(utxos)
     }
 
+    #[tracing::instrument(level = "debug", skip(self, tx))]
     pub async fn sign_raw_transaction(&self, tx: &Transaction) -> Result<Transaction> {
         let tx_hex = serialize_hex(tx);
-        let res = self.signrawtransactionwithwallet(tx_hex).await?;
-        let tx = elements::encode::deserialize(&Vec::<u8>::from_hex(&res.hex).unwrap())?;
+        let res = retry(|| self.signrawtransactionwithwallet(tx_hex.clone())).await?;
+        let signed_tx = elements::encode::deserialize(&Vec::<u8>::from_hex(&res.hex).unwrap())?;
 
-        Ok(tx)
+        if self.dry_run {
+            return Ok(tx.clone());
+        }
+
+        Ok(signed_tx)
     }
 
     pub async fn fund_raw_transaction(&self, tx: &Transaction) -> Result<Transaction> {
         let tx_hex = serialize_hex(tx);
-        let res = self.fundrawtransaction(tx_hex).await?;
+        let res = retry(|| self.fundrawtransaction(tx_hex.clone())).await?;
         let tx = elements::encode::deserialize(&Vec::<u8>::from_hex(&res.hex).unwrap())?;
 
         Ok(tx)
     }
 
     pub async fn lock_utxos(&self, utxos: Vec<OutPoint>) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
         let res = self.lockunspent(false, utxos).await?;
 
         if res {
@@ -320,13 +413,37 @@ impl Client {
         }
     }
 
+    pub async fn unlock_utxos(&self, utxos: Vec<OutPoint>) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let res = self.lockunspent(true, utxos).await?;
+
+        if res {
+            Ok(())
+        } else {
+            bail!("Could not unlock outputs")
+        }
+    }
+
+    /// The UTXOs which are currently locked, by us or anyone else
+    /// using the wallet.
+    pub async fn locked_utxos(&self) -> Result<Vec<OutPoint>> {
+        let locked = retry(|| self.listlockunspent()).await?;
+
+        Ok(locked
+            .into_iter()
+            .map(|utxo| OutPoint::new(utxo.txid, utxo.vout))
+            .collect())
+    }
+
     pub async fn list_received_by_address(
         &self,
         address: &Address,
     ) -> Result<Vec<ListReceivedByAddressResponse>> {
-        let res = self
-            .listreceivedbyaddress(Some(0), None, None, Some(address), None)
-            .await?;
+        let res =
+            retry(|| self.listreceivedbyaddress(Some(0), None, None, Some(address), None)).await?;
 
         Ok(res)
     }
@@ -346,10 +463,97 @@ impl Client {
     }
 
     pub async fn get_blockcount(&self) -> Result<u32> {
-        let blockcount = self.getblockcount().await?;
+        let blockcount = retry(|| self.getblockcount()).await?;
 
         Ok(blockcount)
     }
+
+    /// Whether `outpoint` is still unspent, taking the mempool into
+    /// account.
+    pub async fn is_unspent(&self, outpoint: OutPoint) -> Result<bool> {
+        let txout = retry(|| self.gettxout(outpoint.txid, outpoint.vout, Some(true))).await?;
+
+        Ok(txout.is_some())
+    }
+
+    /// Fee rate in sat/vbyte for a transaction to confirm within
+    /// `conf_target` blocks.
+    ///
+    /// Falls back to the minimum fee rate if elementsd has not seen
+    /// enough transactions to estimate one, e.g. on regtest.
+    pub async fn estimate_fee_rate(&self, conf_target: u32) -> Result<u64> {
+        let response = retry(|| self.estimatesmartfee(conf_target, None)).await?;
+        let fee_rate = match response.feerate {
+            // BTC/kvB to sat/vB
+            Some(btc_per_kvb) => (btc_per_kvb * 100_000.0).ceil() as u64,
+            None => fee::MIN_FEE_RATE,
+        };
+
+        Ok(fee_rate.max(fee::MIN_FEE_RATE))
+    }
+
+    /// Number of confirmations of a wallet transaction.
+    pub async fn get_confirmations(&self, txid: Txid) -> Result<i64> {
+        let transaction = retry(|| self.gettransaction(txid)).await?;
+
+        Ok(transaction.confirmations)
+    }
+}
+
+/// Call elementsd until it answers, backing off exponentially.
+///
+/// Only failures to reach elementsd are retried. An error returned
+/// by elementsd itself is returned immediately.
+async fn retry<T, E, F, Fut>(mut call: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+    E: Into<anyhow::Error>,
+{
+    let mut delay = INITIAL_RETRY_DELAY;
+    let mut attempt = 1;
+
+    loop {
+        match call().await.map_err(Into::into) {
+            Err(e) if attempt < MAX_ATTEMPTS && is_unreachable(&e) => {
+                tracing::warn!(
+                    "elementsd unreachable, retrying in {}ms: {:#}",
+                    delay.as_millis(),
+                    e
+                );
+                sleep(delay).await;
+
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Whether the request failed because we could not talk to
+/// elementsd, rather than because elementsd rejected it.
+fn is_unreachable(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| e.is_connect() || e.is_timeout())
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct GetTxOutResponse {
+    pub confirmations: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct EstimateSmartFeeResponse {
+    /// Estimated fee rate in BTC/kvB, if elementsd has enough data.
+    pub feerate: Option<f64>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct GetTransactionResponse {
+    /// Negative if the transaction conflicts with one in the chain.
+    pub confirmations: i64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -358,6 +562,11 @@ pub struct BlockchainInfo {
     mediantime: u32,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SidechainInfo {
+    pub pegged_asset: AssetId,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct IssueAssetResponse {
     pub txid: Txid,
@@ -383,6 +592,12 @@ pub struct ListUnspentOptions {
     pub asset: Option<AssetId>,
 }
 
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct LockedUtxo {
+    pub txid: Txid,
+    pub vout: u32,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct UtxoInfo {
     pub txid: Txid,
@@ -398,6 +613,37 @@ mod test {
     use elements_harness::Elementsd;
     use testcontainers::clients::Cli;
 
+    #[tokio::test]
+    async fn retries_until_elementsd_is_reachable() {
+        let unreachable = reqwest::get("http://127.0.0.1:1").await.unwrap_err();
+        let mut errors = vec![anyhow::Error::from(unreachable)];
+
+        let result = retry(|| {
+            let result = match errors.pop() {
+                Some(e) => Err(e),
+                None => Ok(42),
+            };
+            async move { result }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), 42);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_errors_returned_by_elementsd() {
+        let mut attempts = 0;
+
+        let result: Result<()> = retry(|| {
+            attempts += 1;
+            async { Err(anyhow::anyhow!("Invalid or non-wallet transaction id")) }
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+
     #[tokio::test]
     async fn get_network_info() {
         let tc_client = Cli::default();
@@ -415,6 +661,23 @@ mod test {
         assert_eq!(network, "elementsregtest")
     }
 
+    #[tokio::test]
+    async fn discovers_native_asset_id() {
+        let tc_client = Cli::default();
+        let (client, _container) = {
+            let blockchain = Elementsd::new(&tc_client, "0.18.1.9").unwrap();
+            (
+                Client::new(blockchain.node_url.clone().into()).unwrap(),
+                blockchain,
+            )
+        };
+
+        let native_asset_id = client.get_bitcoin_asset_id().await.unwrap();
+        let labels = client.dumpassetlabels().await.unwrap();
+
+        assert_eq!(Some(&native_asset_id), labels.get("bitcoin"))
+    }
+
     #[tokio::test]
     async fn send_to_generated_address() {
         let tc_client = Cli::default();
@@ -499,6 +762,32 @@ mod test {
             .unwrap();
     }
 
+    #[tokio::test]
+    async fn lists_locked_utxos() {
+        let tc_client = Cli::default();
+        let (client, _container) = {
+            let blockchain = Elementsd::new(&tc_client, "0.18.1.9").unwrap();
+
+            (
+                Client::new(blockchain.node_url.clone().into()).unwrap(),
+                blockchain,
+            )
+        };
+
+        let labels = client.dumpassetlabels().await.unwrap();
+        let inputs = client
+            .select_inputs_for(*labels.get("bitcoin").unwrap(), Amount::ONE_BTC, true)
+            .await
+            .unwrap();
+        let locked = inputs.iter().map(|(utxo, _)| *utxo).collect::<Vec<_>>();
+        let listed = client.locked_utxos().await.unwrap();
+        assert_eq!(listed.len(), locked.len());
+        assert!(locked.iter().all(|utxo| listed.contains(utxo)));
+
+        client.unlock_utxos(locked).await.unwrap();
+        assert!(client.locked_utxos().await.unwrap().is_empty());
+    }
+
     #[tokio::test]
     async fn get_blockcount() {
         let tc_client = Cli::default();
@@ -0,0 +1,206 @@
+use anyhow::{bail, Result};
+use estimate_transaction_size::estimate_virtual_size;
+use std::fmt;
+
+/// Number of blocks within which we want swap transactions to
+/// confirm.
+pub const CONFIRMATION_TARGET: u32 = 6;
+
+/// The lowest fee rate we ever use, in sat/vbyte.
+pub const MIN_FEE_RATE: u64 = 1;
+
+/// The highest fee rate a taker can ask for, in sat/vbyte.
+///
+/// We pay part of the fee of every swap, so we do not let takers
+/// choose an arbitrarily high one.
+pub const MAX_FEE_RATE: u64 = 100;
+
+/// The smallest amount of any asset we are willing to swap, in the
+/// smallest unit of the asset.
+///
+/// Outputs below this are non-standard on the Liquid network.
+pub const DUST_THRESHOLD: u64 = 546;
+
+/// The largest share of the L-BTC amount of a swap its transaction
+/// fee may take, in basis points.
+///
+/// Swaps below that size cost more to settle than they are worth.
+pub const MAX_FEE_SHARE_BPS: u64 = 500;
+
+/// Number of outputs of a swap transaction besides the fee output,
+/// i.e. the receive and change outputs of both parties.
+const SWAP_OUTPUTS: u64 = 4;
+
+/// Fee rates in sat/vbyte at which we sign the liquidation
+/// transactions of a loan, one per fee level.
+///
+/// We cannot sign a liquidation transaction once the loan is closed,
+/// so we sign one for every level upfront and pick the one matching
+/// the fee market once the loan is due.
+pub const LIQUIDATION_FEE_RATES: [u64; 5] = [1, 2, 5, 10, 25];
+
+/// The lowest fee level whose fee rate is at least the `estimate`, or
+/// the highest one if none is.
+pub fn liquidation_fee_level(estimate: u64) -> usize {
+    LIQUIDATION_FEE_RATES
+        .iter()
+        .position(|fee_rate| *fee_rate >= estimate)
+        .unwrap_or(LIQUIDATION_FEE_RATES.len() - 1)
+}
+
+/// Agree on the fee rate of a swap transaction, in sat/vbyte.
+///
+/// Takers propose the fee rate they funded their inputs for, which we
+/// accept as long as it is not lower than our own `estimate`. Takers
+/// which do not propose one get our estimate.
+pub fn agree(proposed: Option<u64>, estimate: u64) -> Result<u64, UnacceptableFeeRate> {
+    let estimate = estimate.max(MIN_FEE_RATE);
+    let proposed = match proposed {
+        Some(proposed) => proposed,
+        None => return Ok(estimate),
+    };
+
+    if proposed < estimate || proposed > MAX_FEE_RATE {
+        return Err(UnacceptableFeeRate {
+            proposed,
+            min: estimate,
+            max: MAX_FEE_RATE,
+        });
+    }
+
+    Ok(proposed)
+}
+
+/// The fee rate proposed by the taker is too low to confirm in time
+/// or higher than we are willing to pay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnacceptableFeeRate {
+    pub proposed: u64,
+    pub min: u64,
+    pub max: u64,
+}
+
+impl fmt::Display for UnacceptableFeeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "fee rate of {} sat/vbyte is not between {} and {} sat/vbyte",
+            self.proposed, self.min, self.max
+        )
+    }
+}
+
+impl std::error::Error for UnacceptableFeeRate {}
+
+/// Estimate the fee of a swap transaction spending `inputs` at the
+/// `fee_rate`, in satoshi.
+pub fn estimate_swap_fee(inputs: u64, fee_rate: u64) -> u64 {
+    estimate_virtual_size(inputs, SWAP_OUTPUTS) * fee_rate
+}
+
+/// Check that none of the `amounts` swapped is dust, and that the
+/// `fee` does not take more than [`MAX_FEE_SHARE_BPS`] of the
+/// `btc_amount` swapped, if L-BTC is part of the swap.
+pub fn check_swap_size(amounts: &[u64], btc_amount: Option<u64>, fee: u64) -> Result<()> {
+    if let Some(amount) = amounts.iter().find(|amount| **amount < DUST_THRESHOLD) {
+        bail!(DustAmount {
+            amount: *amount,
+            threshold: DUST_THRESHOLD,
+        })
+    }
+
+    if let Some(btc_amount) = btc_amount {
+        if fee as u128 * 10_000 > btc_amount as u128 * MAX_FEE_SHARE_BPS as u128 {
+            bail!(UneconomicSwap {
+                fee,
+                min_btc_amount: fee * 10_000 / MAX_FEE_SHARE_BPS,
+            })
+        }
+    }
+
+    Ok(())
+}
+
+/// One of the amounts of a swap is too small for a standard output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DustAmount {
+    pub amount: u64,
+    pub threshold: u64,
+}
+
+impl fmt::Display for DustAmount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "amount of {} is below the dust threshold of {}",
+            self.amount, self.threshold
+        )
+    }
+}
+
+impl std::error::Error for DustAmount {}
+
+/// The fee of a swap would take too large a share of the L-BTC
+/// swapped.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UneconomicSwap {
+    /// In satoshi.
+    pub fee: u64,
+    /// The smallest amount of L-BTC worth swapping at this fee, in
+    /// satoshi.
+    pub min_btc_amount: u64,
+}
+
+impl fmt::Display for UneconomicSwap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "fee of {} sat requires swapping at least {} sat",
+            self.fee, self.min_btc_amount
+        )
+    }
+}
+
+impl std::error::Error for UneconomicSwap {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_proposed_fee_rate_at_or_above_estimate() {
+        assert_eq!(agree(Some(2), 2), Ok(2));
+        assert_eq!(agree(Some(5), 2), Ok(5));
+        assert_eq!(agree(None, 3), Ok(3));
+    }
+
+    #[test]
+    fn picks_cheapest_liquidation_fee_level_above_estimate() {
+        assert_eq!(liquidation_fee_level(1), 0);
+        assert_eq!(liquidation_fee_level(3), 2);
+        assert_eq!(liquidation_fee_level(100), LIQUIDATION_FEE_RATES.len() - 1);
+    }
+
+    #[test]
+    fn rejects_dust_and_swaps_not_worth_their_fee() {
+        assert!(check_swap_size(&[100_000, 4_000_000], Some(100_000), 5_000).is_ok());
+        assert!(check_swap_size(&[500, 4_000_000], None, 5_000)
+            .unwrap_err()
+            .is::<DustAmount>());
+
+        let error = check_swap_size(&[50_000, 2_000_000], Some(50_000), 5_000).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<UneconomicSwap>(),
+            Some(&UneconomicSwap {
+                fee: 5_000,
+                min_btc_amount: 100_000,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_proposed_fee_rate_below_estimate_or_above_maximum() {
+        assert!(agree(Some(1), 2).is_err());
+        assert!(agree(Some(MAX_FEE_RATE + 1), 2).is_err());
+    }
+}
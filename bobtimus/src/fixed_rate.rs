@@ -1,4 +1,5 @@
-use crate::{LatestRate, LiquidUsdt, Rate, RateSubscription};
+use crate::{LatestRate, LiquidUsdt, Rate, RateSubscription, RateUpdate};
+use anyhow::Result;
 use std::{convert::TryFrom, time::Duration};
 use tokio::{
     sync::watch::{self, Receiver},
@@ -6,15 +7,20 @@ use tokio::{
 };
 
 #[derive(Clone)]
-pub struct Service(Receiver<Rate>);
+pub struct Service(Receiver<RateUpdate>);
 
 impl Service {
     pub fn new() -> Self {
-        let data = fixed_rate();
+        Self::with_rate(fixed_rate())
+    }
+
+    pub fn with_rate(rate: Rate) -> Self {
+        let mut data = RateUpdate::first(rate);
         let (tx, rx) = watch::channel(data);
 
         tokio::spawn(async move {
             loop {
+                data = data.next(rate);
                 let _ = tx.send(data);
 
                 sleep(Duration::from_secs(5)).await;
@@ -23,10 +29,6 @@ impl Service {
 
         Self(rx)
     }
-
-    pub fn subscribe(&self) -> RateSubscription {
-        RateSubscription::from(self.0.clone())
-    }
 }
 
 impl Default for Service {
@@ -36,8 +38,12 @@ impl Default for Service {
 }
 
 impl LatestRate for Service {
-    fn latest_rate(&mut self) -> Rate {
-        fixed_rate()
+    fn latest_rate(&self) -> Result<Rate> {
+        Ok(self.0.borrow().rate)
+    }
+
+    fn subscribe(&self) -> RateSubscription {
+        RateSubscription::from(self.0.clone())
     }
 }
 
@@ -1,30 +1,95 @@
-use crate::{problem, Bobtimus, CreateSwapPayload, LatestRate, RateSubscription};
+use crate::{
+    accounting,
+    database::{queries, Database, InventoryFilter, TradeDirection, TradeFilter},
+    limit_order::LimitOrderRequest,
+    loan_book, metrics,
+    oracle::AttestationFeed,
+    problem,
+    rate_history::{self, RateHistoryQuery},
+    shutdown::Shutdown,
+    unix_timestamp,
+    wallet::Wallet,
+    Bobtimus, CreateSwapPayload, LatestRate, RateSubscription,
+};
 use anyhow::Context;
 use elements::{
     encode::serialize_hex,
     secp256k1_zkp::rand::{thread_rng, CryptoRng, RngCore},
-    Transaction,
+    AssetId, Transaction,
 };
 use futures::{StreamExt, TryStreamExt};
+use http_api_problem::HttpApiProblem;
+use reqwest::Url;
 use rust_embed::RustEmbed;
-use std::{error::Error, fmt, sync::Arc};
-use tokio::sync::Mutex;
+use std::{error::Error, fmt, sync::Arc, time::Instant};
 use warp::{
+    cors::Builder,
     filters::BoxedFilter,
-    http::{header::HeaderValue, HeaderMap},
+    http::{
+        header::{HeaderName, HeaderValue},
+        HeaderMap, Method, StatusCode,
+    },
     path::Tail,
     reply::Response,
     Filter, Rejection, Reply,
 };
 
+/// Which browser contexts may call our API from a different origin,
+/// e.g. the extension.
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub allowed_origins: AllowedOrigins,
+    pub allowed_methods: Vec<Method>,
+    pub allowed_headers: Vec<HeaderName>,
+}
+
+#[derive(Debug, Clone)]
+pub enum AllowedOrigins {
+    Any,
+    Only(Vec<Url>),
+}
+
+impl CorsConfig {
+    pub fn filter(&self) -> Builder {
+        let cors = warp::cors()
+            .allow_methods(self.allowed_methods.iter().cloned())
+            .allow_headers(self.allowed_headers.iter().cloned())
+            .expose_headers(vec![REQUEST_ID_HEADER]);
+
+        match &self.allowed_origins {
+            AllowedOrigins::Any => cors.allow_any_origin(),
+            AllowedOrigins::Only(origins) => {
+                let origins = origins
+                    .iter()
+                    .map(|origin| origin.origin().ascii_serialization())
+                    .collect::<Vec<_>>();
+
+                cors.allow_origins(origins.iter().map(String::as_str))
+            }
+        }
+    }
+}
+
+/// Header identifying a request in our logs, which clients may set
+/// and which we always set on the response.
+const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The OpenAPI specification of our HTTP API.
+///
+/// Keep it in sync with the routes below.
+const SPEC: &str = include_str!("../openapi.json");
+
 #[derive(RustEmbed)]
 #[folder = "../waves/dist/"]
 struct Waves;
 
 pub fn routes<R, RS>(
-    bobtimus: Arc<Mutex<Bobtimus<R, RS>>>,
+    bobtimus: Arc<Bobtimus<R, RS>>,
     latest_rate_subscription: RateSubscription,
-) -> BoxedFilter<(impl Reply,)>
+    attestations: Option<AttestationFeed>,
+    api_key: Option<String>,
+    shutdown: Shutdown,
+) -> BoxedFilter<(Response,)>
 where
     R: RngCore + CryptoRng + Clone + Send + Sync + 'static,
     RS: LatestRate + Clone + Send + Sync + 'static,
@@ -41,59 +106,218 @@ where
 
     let latest_rate = warp::get()
         .and(warp::path!("api" / "rate" / "lbtc-lusdt"))
-        .map(move || latest_rate(latest_rate_subscription.clone()))
+        .map({
+            let latest_rate_subscription = latest_rate_subscription.clone();
+            let shutdown = shutdown.clone();
+            move || latest_rate(latest_rate_subscription.clone(), shutdown.clone())
+        })
+        .with(warp::reply::with::headers(sse_headers.clone()));
+
+    let rate_stream = warp::get()
+        .and(warp::path!("api" / "rate" / "stream"))
+        .map({
+            let shutdown = shutdown.clone();
+            move || rate_stream(latest_rate_subscription.clone(), shutdown.clone())
+        })
         .with(warp::reply::with::headers(sse_headers));
 
+    let rate_history = warp::get()
+        .and(warp::path!("api" / "rate" / "history"))
+        .and(warp::query::<RateHistoryQuery>())
+        .and_then({
+            let db = bobtimus.db.clone();
+            move |query| {
+                let db = db.clone();
+                async move { rate_history(&db, query).await }
+            }
+        });
+
+    let attestation = warp::get()
+        .and(warp::path!("api" / "oracle" / "attestation"))
+        .and_then(move || {
+            let attestations = attestations.clone();
+            async move {
+                match attestations {
+                    Some(attestations) => attestation(attestations).await,
+                    None => Err(warp::reject::not_found()),
+                }
+            }
+        });
+
+    let quote = warp::get()
+        .and(warp::path!("api" / "quote" / String / TradeDirection))
+        .and(warp::query::<QuoteRequest>())
+        .and_then({
+            let bobtimus = bobtimus.clone();
+            move |pair: String, direction, request| {
+                let bobtimus = bobtimus.clone();
+                async move { quote(&bobtimus, &pair, direction, request).await }
+            }
+        });
+
+    let get_quote = warp::get()
+        .and(warp::path!("api" / "quote" / String))
+        .and_then({
+            let bobtimus = bobtimus.clone();
+            move |id: String| {
+                let bobtimus = bobtimus.clone();
+                async move { get_quote(&bobtimus, &id).await }
+            }
+        });
+
     let create_buy_swap = warp::post()
-        .and(warp::path!("api" / "swap" / "lbtc-lusdt" / "buy"))
+        .and(warp::path!("api" / "swap" / String / "buy"))
+        .and(accepting_requests(shutdown.clone()))
+        .and(authenticate(api_key.clone()))
+        .and(warp::header::optional::<String>("idempotency-key"))
         .and(warp::body::json())
         .and_then({
             let bobtimus = bobtimus.clone();
-            move |payload| {
+            move |pair: String, idempotency_key, payload| {
                 let bobtimus = bobtimus.clone();
-                async move {
-                    let mut bobtimus = bobtimus.lock().await;
-                    create_buy_swap(&mut bobtimus, payload).await
-                }
+                async move { create_buy_swap(&bobtimus, &pair, idempotency_key, payload).await }
             }
         });
 
     let create_sell_swap = warp::post()
-        .and(warp::path!("api" / "swap" / "lbtc-lusdt" / "sell"))
+        .and(warp::path!("api" / "swap" / String / "sell"))
+        .and(accepting_requests(shutdown.clone()))
+        .and(authenticate(api_key.clone()))
+        .and(warp::header::optional::<String>("idempotency-key"))
         .and(warp::body::json())
         .and_then({
             let bobtimus = bobtimus.clone();
-            move |payload| {
+            move |pair: String, idempotency_key, payload| {
                 let bobtimus = bobtimus.clone();
-                async move {
-                    let mut bobtimus = bobtimus.lock().await;
-                    create_sell_swap(&mut bobtimus, payload).await
-                }
+                async move { create_sell_swap(&bobtimus, &pair, idempotency_key, payload).await }
+            }
+        });
+
+    let place_limit_order = warp::post()
+        .and(warp::path!("api" / "order" / String / TradeDirection))
+        .and(accepting_requests(shutdown.clone()))
+        .and(authenticate(api_key.clone()))
+        .and(warp::body::json())
+        .and_then({
+            let bobtimus = bobtimus.clone();
+            move |pair: String, direction, request| {
+                let bobtimus = bobtimus.clone();
+                async move { place_limit_order(&bobtimus, &pair, direction, request).await }
+            }
+        });
+
+    let get_limit_order = warp::get()
+        .and(warp::path!("api" / "order" / String))
+        .and_then({
+            let bobtimus = bobtimus.clone();
+            move |id: String| {
+                let bobtimus = bobtimus.clone();
+                async move { get_limit_order(&bobtimus, &id).await }
+            }
+        });
+
+    let cancel_limit_order = warp::delete()
+        .and(warp::path!("api" / "order" / String))
+        .and(authenticate(api_key.clone()))
+        .and_then({
+            let bobtimus = bobtimus.clone();
+            move |id: String| {
+                let bobtimus = bobtimus.clone();
+                async move { cancel_limit_order(&bobtimus, &id).await }
+            }
+        });
+
+    let spec = warp::get()
+        .and(warp::path!("api" / "spec"))
+        .map(|| warp::reply::with_header(SPEC, "content-type", "application/json"));
+
+    let metrics = warp::get().and(warp::path!("metrics")).and_then({
+        let wallet = bobtimus.wallet.clone();
+        let db = bobtimus.db.clone();
+        let btc_asset_id = bobtimus.btc_asset_id;
+        let usdt_asset_id = bobtimus.usdt_asset_id;
+        move || {
+            let wallet = wallet.clone();
+            let db = db.clone();
+            async move { metrics(&wallet, &db, btc_asset_id, usdt_asset_id).await }
+        }
+    });
+
+    let trades = warp::get()
+        .and(warp::path!("api" / "trades"))
+        .and(warp::query::<TradeFilter>())
+        .and_then({
+            let db = bobtimus.db.clone();
+            move |filter| {
+                let db = db.clone();
+                async move { trades(&db, filter).await }
+            }
+        });
+
+    let inventory = warp::get()
+        .and(warp::path!("api" / "inventory"))
+        .and(warp::query::<InventoryFilter>())
+        .and_then({
+            let db = bobtimus.db.clone();
+            move |filter| {
+                let db = db.clone();
+                async move { inventory(&db, filter).await }
+            }
+        });
+
+    let accounting = warp::get()
+        .and(warp::path!("api" / "accounting"))
+        .and(authenticate(api_key.clone()))
+        .and_then({
+            let db = bobtimus.db.clone();
+            move || {
+                let db = db.clone();
+                async move { accounting(&db).await }
+            }
+        });
+
+    let loans = warp::get()
+        .and(warp::path!("api" / "loans"))
+        .and(authenticate(api_key.clone()))
+        .and_then({
+            let db = bobtimus.db.clone();
+            move || {
+                let db = db.clone();
+                async move { loans(&db).await }
+            }
+        });
+
+    let loan_offer = warp::get()
+        .and(warp::path!("api" / "loan" / "offer"))
+        .and_then({
+            let bobtimus = bobtimus.clone();
+            move || {
+                let bobtimus = bobtimus.clone();
+                async move { loan_offer(&bobtimus).await }
             }
         });
 
     let create_loan = warp::post()
         .and(warp::path!("api" / "loan" / "lbtc-lusdt"))
+        .and(accepting_requests(shutdown))
+        .and(authenticate(api_key.clone()))
         .and(warp::body::json())
         .and_then({
             let bobtimus = bobtimus.clone();
             move |payload| {
                 let bobtimus = bobtimus.clone();
-                async move {
-                    let mut bobtimus = bobtimus.lock().await;
-                    create_loan(&mut bobtimus, payload).await
-                }
+                async move { create_loan(&bobtimus, payload).await }
             }
         });
 
     let finalize_loan = warp::post()
         .and(warp::path!("api" / "loan" / "lbtc-lusdt" / "finalize"))
+        .and(authenticate(api_key))
         .and(warp::body::json())
         .and_then(move |payload| {
             let bobtimus = bobtimus.clone();
             async move {
-                let mut bobtimus = bobtimus.lock().await;
-                finalize_loan(&mut bobtimus, payload)
+                finalize_loan(&bobtimus, payload)
                     .await
                     .map_err(anyhow::Error::from)
                     .map_err(problem::from_anyhow)
@@ -101,19 +325,208 @@ where
             }
         });
 
-    latest_rate
+    let routes = latest_rate
+        .or(rate_stream)
+        .or(rate_history)
+        .or(attestation)
+        .or(quote)
+        .or(get_quote)
         .or(create_sell_swap)
         .or(create_buy_swap)
+        .or(place_limit_order)
+        .or(get_limit_order)
+        .or(cancel_limit_order)
+        .or(trades)
+        .or(inventory)
+        .or(accounting)
+        .or(loans)
+        .or(loan_offer)
         .or(create_loan)
         .or(finalize_loan)
+        .or(spec)
+        .or(metrics)
         .or(waves_resources)
         .or(index_html)
-        .recover(problem::unpack_problem)
+        .recover(problem::unpack_problem);
+
+    traced(routes)
+}
+
+/// Tag every request to the `filter` with an id, taken from its
+/// `x-request-id` header or made up, and log its outcome.
+///
+/// The id is part of every log line of the request and is returned in
+/// the `x-request-id` header of the response, including error
+/// responses, so that failures reported by clients can be matched to
+/// our logs.
+pub fn traced<F, T>(filter: F) -> BoxedFilter<(Response,)>
+where
+    F: Filter<Extract = (T,), Error = Rejection> + Clone + Send + Sync + 'static,
+    T: Reply + Send,
+{
+    warp::header::optional::<String>(REQUEST_ID_HEADER)
+        .map(|request_id: Option<String>| {
+            let request_id = request_id
+                .filter(|request_id| is_valid_request_id(request_id))
+                .unwrap_or_else(|| format!("{:016x}", thread_rng().next_u64()));
+            tracing::Span::current().record("request_id", &request_id.as_str());
+
+            (request_id, Instant::now())
+        })
+        .and(filter)
+        .map(|(request_id, started_at): (String, Instant), reply: T| {
+            let mut response = reply.into_response();
+            tracing::info!(
+                status = response.status().as_u16(),
+                latency_ms = started_at.elapsed().as_millis() as u64,
+                "Finished request"
+            );
+
+            let request_id =
+                HeaderValue::from_str(&request_id).expect("request ids are valid header values");
+            response.headers_mut().insert(REQUEST_ID_HEADER, request_id);
+
+            response
+        })
+        .with(warp::trace(|info| {
+            tracing::info_span!(
+                "request",
+                request_id = tracing::field::Empty,
+                method = %info.method(),
+                path = %info.path(),
+            )
+        }))
+        .boxed()
+}
+
+/// Whether we adopt the request id a client sent, which ends up in
+/// our logs and response headers.
+fn is_valid_request_id(request_id: &str) -> bool {
+    !request_id.is_empty()
+        && request_id.len() <= 64
+        && request_id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Only let through requests which carry the given API key in their
+/// `Authorization` header, as in `Authorization: Bearer <api-key>`.
+///
+/// Without an API key, every request is let through.
+pub fn authenticate(api_key: Option<String>) -> BoxedFilter<()> {
+    warp::header::optional::<String>("authorization")
+        .and_then(move |authorization: Option<String>| {
+            let api_key = api_key.clone();
+            async move {
+                let api_key = match api_key {
+                    Some(api_key) => api_key,
+                    None => return Ok(()),
+                };
+
+                let bearer = authorization
+                    .as_deref()
+                    .and_then(|authorization| authorization.strip_prefix("Bearer "));
+                match bearer {
+                    Some(bearer) if constant_time_eq(bearer.as_bytes(), api_key.as_bytes()) => {
+                        Ok(())
+                    }
+                    _ => Err(warp::reject::custom(
+                        HttpApiProblem::new("Missing or invalid API key.")
+                            .set_status(StatusCode::UNAUTHORIZED),
+                    )),
+                }
+            }
+        })
+        .untuple_one()
+        .boxed()
+}
+
+/// Reject requests which would start a new swap or loan once we are
+/// shutting down.
+///
+/// Requests which complete a loan we already agreed to are still let
+/// through.
+fn accepting_requests(shutdown: Shutdown) -> BoxedFilter<()> {
+    warp::any()
+        .and_then(move || {
+            let shutdown = shutdown.clone();
+            async move {
+                if shutdown.is_triggered() {
+                    return Err(warp::reject::custom(
+                        HttpApiProblem::new("Shutting down.")
+                            .set_status(StatusCode::SERVICE_UNAVAILABLE),
+                    ));
+                }
+
+                Ok(())
+            }
+        })
+        .untuple_one()
         .boxed()
 }
 
+/// Compare two byte strings in time which only depends on their
+/// length, so that the API key cannot be guessed byte by byte.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+async fn rate_history(db: &Database, query: RateHistoryQuery) -> Result<impl Reply, Rejection> {
+    rate_history::history(db, query, unix_timestamp())
+        .await
+        .map(|rates| warp::reply::json(&rates))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn attestation(attestations: AttestationFeed) -> Result<impl Reply, Rejection> {
+    attestations
+        .latest()
+        .map(|attestation| warp::reply::json(&attestation))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+#[derive(serde::Deserialize)]
+struct QuoteRequest {
+    amount: u64,
+}
+
+async fn quote<R, RS>(
+    bobtimus: &Bobtimus<R, RS>,
+    pair: &str,
+    direction: TradeDirection,
+    request: QuoteRequest,
+) -> Result<impl Reply, Rejection>
+where
+    R: RngCore + CryptoRng,
+    RS: LatestRate,
+{
+    bobtimus
+        .handle_quote(pair, direction, request.amount)
+        .await
+        .map(|quote| warp::reply::json(&quote))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn get_quote<R, RS>(bobtimus: &Bobtimus<R, RS>, id: &str) -> Result<impl Reply, Rejection>
+where
+    R: RngCore + CryptoRng,
+    RS: LatestRate,
+{
+    bobtimus
+        .handle_get_quote(id)
+        .await
+        .map(|quote| warp::reply::json(&quote))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
 async fn create_buy_swap<R, RS>(
-    bobtimus: &mut Bobtimus<R, RS>,
+    bobtimus: &Bobtimus<R, RS>,
+    pair: &str,
+    idempotency_key: Option<String>,
     payload: serde_json::Value,
 ) -> Result<impl Reply, Rejection>
 where
@@ -127,7 +540,7 @@ where
         .map_err(warp::reject::custom)?;
 
     bobtimus
-        .handle_create_buy_swap(payload)
+        .handle_create_buy_swap(pair, payload, idempotency_key)
         .await
         .map(|transaction| serialize_hex(&transaction))
         .map_err(anyhow::Error::from)
@@ -136,7 +549,9 @@ where
 }
 
 async fn create_sell_swap<R, RS>(
-    bobtimus: &mut Bobtimus<R, RS>,
+    bobtimus: &Bobtimus<R, RS>,
+    pair: &str,
+    idempotency_key: Option<String>,
     payload: serde_json::Value,
 ) -> Result<impl Reply, Rejection>
 where
@@ -150,7 +565,7 @@ where
         .map_err(warp::reject::custom)?;
 
     bobtimus
-        .handle_create_sell_swap(payload)
+        .handle_create_sell_swap(pair, payload, idempotency_key)
         .await
         .map(|transaction| serialize_hex(&transaction))
         .map_err(anyhow::Error::from)
@@ -158,8 +573,121 @@ where
         .map_err(warp::reject::custom)
 }
 
+async fn place_limit_order<R, RS>(
+    bobtimus: &Bobtimus<R, RS>,
+    pair: &str,
+    direction: TradeDirection,
+    request: LimitOrderRequest,
+) -> Result<impl Reply, Rejection>
+where
+    R: RngCore + CryptoRng,
+    RS: LatestRate,
+{
+    bobtimus
+        .handle_place_limit_order(pair, direction, request)
+        .await
+        .map(|order| warp::reply::json(&order))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn get_limit_order<R, RS>(
+    bobtimus: &Bobtimus<R, RS>,
+    id: &str,
+) -> Result<impl Reply, Rejection>
+where
+    R: RngCore + CryptoRng,
+    RS: LatestRate,
+{
+    bobtimus
+        .handle_get_limit_order(id)
+        .await
+        .map(|order| warp::reply::json(&order))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn cancel_limit_order<R, RS>(
+    bobtimus: &Bobtimus<R, RS>,
+    id: &str,
+) -> Result<impl Reply, Rejection>
+where
+    R: RngCore + CryptoRng,
+    RS: LatestRate,
+{
+    bobtimus
+        .handle_cancel_limit_order(id)
+        .await
+        .map(|order| warp::reply::json(&order))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn metrics(
+    wallet: &Wallet,
+    db: &Database,
+    btc_asset_id: AssetId,
+    usdt_asset_id: AssetId,
+) -> Result<impl Reply, Rejection> {
+    let snapshot = wallet
+        .snapshot()
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)?;
+
+    metrics::export(&snapshot, db, btc_asset_id, usdt_asset_id)
+        .await
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn trades(db: &Database, filter: TradeFilter) -> Result<impl Reply, Rejection> {
+    db.do_in_transaction(|conn| queries::get_trades(conn, &filter))
+        .await
+        .map(|trades| warp::reply::json(&trades))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn inventory(db: &Database, filter: InventoryFilter) -> Result<impl Reply, Rejection> {
+    db.do_in_transaction(|conn| queries::get_inventory(conn, &filter))
+        .await
+        .map(|snapshots| warp::reply::json(&snapshots))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn accounting(db: &Database) -> Result<impl Reply, Rejection> {
+    accounting::report(db)
+        .await
+        .map(|report| warp::reply::json(&report))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn loans(db: &Database) -> Result<impl Reply, Rejection> {
+    loan_book::loans(db)
+        .await
+        .map(|loans| warp::reply::json(&loans))
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
+async fn loan_offer<R, RS>(bobtimus: &Bobtimus<R, RS>) -> Result<impl Reply, Rejection>
+where
+    R: RngCore + CryptoRng,
+    RS: LatestRate,
+{
+    bobtimus
+        .handle_loan_offer()
+        .await
+        .map(|loan_offer| warp::reply::json(&loan_offer))
+        .map_err(anyhow::Error::from)
+        .map_err(problem::from_anyhow)
+        .map_err(warp::reject::custom)
+}
+
 async fn create_loan<R, RS>(
-    bobtimus: &mut Bobtimus<R, RS>,
+    bobtimus: &Bobtimus<R, RS>,
     payload: serde_json::Value,
 ) -> Result<impl Reply, Rejection>
 where
@@ -188,7 +716,7 @@ struct FinalizeLoanPayload {
 }
 
 async fn finalize_loan<R, RS>(
-    bobtimus: &mut Bobtimus<R, RS>,
+    bobtimus: &Bobtimus<R, RS>,
     payload: serde_json::Value,
 ) -> anyhow::Result<impl Reply>
 where
@@ -202,7 +730,7 @@ where
         .map(|loan_response| warp::reply::json(&loan_response))
 }
 
-fn latest_rate(subscription: RateSubscription) -> impl Reply {
+fn latest_rate(subscription: RateSubscription, shutdown: Shutdown) -> impl Reply {
     let stream = subscription
         .into_stream()
         .map_ok(|data| {
@@ -219,7 +747,37 @@ fn latest_rate(subscription: RateSubscription) -> impl Reply {
             Ok(Err(e)) => Err(e),
             Err(e) => Err(e),
         })
-        .err_into::<RateStreamError>();
+        .err_into::<RateStreamError>()
+        .take_until(shutdown.triggered());
+
+    warp::sse::reply(warp::sse::keep_alive().stream(stream))
+}
+
+/// Stream rate updates, using their sequence number as the event id.
+///
+/// Clients can detect that they missed updates by looking for gaps
+/// in the sequence.
+///
+/// Like all rate streams, it ends once we are shutting down.
+fn rate_stream(subscription: RateSubscription, shutdown: Shutdown) -> impl Reply {
+    let stream = subscription
+        .into_sequenced_stream()
+        .map_ok(|rate_update| {
+            let event = warp::sse::Event::default()
+                .id(rate_update.sequence.to_string())
+                .event("rate")
+                .json_data(rate_update)
+                .context("failed to attach json data to sse event")?;
+
+            Ok(event)
+        })
+        .map(|result| match result {
+            Ok(Ok(ok)) => Ok(ok),
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(e),
+        })
+        .err_into::<RateStreamError>()
+        .take_until(shutdown.triggered());
 
     warp::sse::reply(warp::sse::keep_alive().stream(stream))
 }
@@ -264,3 +822,90 @@ fn serve_impl(path: &str) -> Result<impl Reply, Rejection> {
     );
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec_documents_every_api_route() {
+        let spec: serde_json::Value = serde_json::from_str(SPEC).unwrap();
+
+        for path in [
+            "/api/rate/lbtc-lusdt",
+            "/api/rate/stream",
+            "/api/rate/history",
+            "/api/oracle/attestation",
+            "/api/quote/{pair}/{direction}",
+            "/api/quote/{id}",
+            "/api/swap/{pair}/buy",
+            "/api/swap/{pair}/sell",
+            "/api/order/{pair}/{direction}",
+            "/api/order/{id}",
+            "/api/trades",
+            "/api/inventory",
+            "/api/accounting",
+            "/api/loans",
+            "/api/loan/offer",
+            "/api/loan/lbtc-lusdt",
+            "/api/loan/lbtc-lusdt/finalize",
+            "/api/spec",
+            "/metrics",
+        ]
+        .iter()
+        {
+            assert!(
+                spec["paths"].get(path).is_some(),
+                "{} is not documented",
+                path
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn responds_with_given_or_new_request_id() {
+        let filter = traced(warp::any().and_then(|| async { Ok::<_, Rejection>("ok") }));
+
+        let given = warp::test::request()
+            .header(REQUEST_ID_HEADER, "c0ffee-42")
+            .reply(&filter)
+            .await;
+        let invalid = warp::test::request()
+            .header(REQUEST_ID_HEADER, "not a valid id")
+            .reply(&filter)
+            .await;
+
+        assert_eq!(given.headers()[REQUEST_ID_HEADER], "c0ffee-42");
+        assert_eq!(invalid.headers()[REQUEST_ID_HEADER].len(), 16);
+    }
+
+    #[tokio::test]
+    async fn rejects_request_without_api_key() {
+        let filter = authenticate(Some("secret".to_owned()));
+
+        let result = warp::test::request().filter(&filter).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn accepts_request_with_api_key() {
+        let filter = authenticate(Some("secret".to_owned()));
+
+        let result = warp::test::request()
+            .header("authorization", "Bearer secret")
+            .filter(&filter)
+            .await;
+
+        assert!(result.is_ok());
+    }
+
+    #[tokio::test]
+    async fn accepts_any_request_if_no_api_key_is_configured() {
+        let filter = authenticate(None);
+
+        let result = warp::test::request().filter(&filter).await;
+
+        assert!(result.is_ok());
+    }
+}
@@ -0,0 +1,263 @@
+use crate::{database::TradeDirection, CreateSwapPayload};
+use anyhow::Result;
+use bitcoin_hashes::{sha256, Hash};
+use elements::Transaction;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// How long we answer a repeated swap request with the transaction
+/// we built for the first one.
+const IDEMPOTENCY_WINDOW: Duration = Duration::from_secs(10 * 60);
+
+/// Swap transactions we recently built, so that a retried request
+/// gets the same transaction instead of a second one spending the
+/// same inputs.
+#[derive(Debug, Default)]
+pub struct RecentSwaps {
+    swaps: HashMap<String, RecentSwap>,
+}
+
+#[derive(Debug)]
+struct RecentSwap {
+    created_at: Instant,
+    request: sha256::Hash,
+    transaction: Transaction,
+}
+
+impl RecentSwaps {
+    /// The transaction we built for the request identified by `key`,
+    /// unless it is older than the idempotency window.
+    ///
+    /// Fails if the `key` was used for a different `request`.
+    pub fn get(
+        &mut self,
+        key: &str,
+        request: sha256::Hash,
+        now: Instant,
+    ) -> Result<Option<Transaction>, IdempotencyKeyReused> {
+        self.swaps
+            .retain(|_, swap| now.saturating_duration_since(swap.created_at) < IDEMPOTENCY_WINDOW);
+
+        match self.swaps.get(key) {
+            Some(swap) if swap.request != request => Err(IdempotencyKeyReused),
+            Some(swap) => Ok(Some(swap.transaction.clone())),
+            None => Ok(None),
+        }
+    }
+
+    pub fn insert(
+        &mut self,
+        key: String,
+        request: sha256::Hash,
+        transaction: Transaction,
+        now: Instant,
+    ) {
+        self.swaps.insert(
+            key,
+            RecentSwap {
+                created_at: now,
+                request,
+                transaction,
+            },
+        );
+    }
+}
+
+/// The swap requests we recently answered and those we are answering
+/// right now.
+///
+/// We do not serialize swap requests, so a concurrent retry, or a
+/// second swap with the same quote, must not build another
+/// transaction before the first one is recorded. Looking up a recent
+/// swap and claiming a request therefore happen under one lock.
+#[derive(Debug, Default)]
+pub struct SwapRequests(Mutex<SwapRequestsState>);
+
+#[derive(Debug, Default)]
+struct SwapRequestsState {
+    recent: RecentSwaps,
+    in_progress: HashSet<String>,
+}
+
+/// What to do with a swap request.
+#[derive(Debug)]
+pub enum SwapRequest<'a> {
+    /// Answer with the transaction we built for the same request.
+    Recent(Transaction),
+    /// Build the swap, the request is ours until the claim is dropped.
+    Claimed(SwapClaim<'a>),
+}
+
+impl SwapRequests {
+    /// The transaction we built for the `request` identified by
+    /// `key`, or else a claim on the `key` and the `quote_id` of the
+    /// request.
+    ///
+    /// Fails if the `key` was used for a different `request`, or if
+    /// another swap holds the `key` or the quote.
+    pub fn begin(
+        &self,
+        key: &str,
+        request: sha256::Hash,
+        quote_id: Option<&str>,
+        now: Instant,
+    ) -> Result<SwapRequest<'_>> {
+        let mut state = self.0.lock().expect("lock is never poisoned");
+        if let Some(transaction) = state.recent.get(key, request, now)? {
+            return Ok(SwapRequest::Recent(transaction));
+        }
+
+        let mut claims = vec![format!("request:{}", key)];
+        if let Some(quote_id) = quote_id {
+            claims.push(format!("quote:{}", quote_id));
+        }
+        if claims.iter().any(|claim| state.in_progress.contains(claim)) {
+            return Err(SwapInProgress.into());
+        }
+        state.in_progress.extend(claims.iter().cloned());
+
+        Ok(SwapRequest::Claimed(SwapClaim {
+            requests: self,
+            key: key.to_owned(),
+            request,
+            claims,
+        }))
+    }
+}
+
+/// A swap request we are building the transaction for.
+#[derive(Debug)]
+pub struct SwapClaim<'a> {
+    requests: &'a SwapRequests,
+    key: String,
+    request: sha256::Hash,
+    claims: Vec<String>,
+}
+
+impl SwapClaim<'_> {
+    /// Remember the `transaction` we built, so that repeated requests
+    /// get it too, and release the claim.
+    pub fn complete(self, transaction: Transaction, now: Instant) {
+        let mut state = self.requests.0.lock().expect("lock is never poisoned");
+        state
+            .recent
+            .insert(self.key.clone(), self.request, transaction, now);
+    }
+}
+
+impl Drop for SwapClaim<'_> {
+    fn drop(&mut self) {
+        let mut state = self.requests.0.lock().expect("lock is never poisoned");
+        for claim in &self.claims {
+            state.in_progress.remove(claim);
+        }
+    }
+}
+
+/// Identifies a swap request, used as the idempotency key when the
+/// client does not provide one.
+pub fn request_digest(
+    pair: &str,
+    direction: TradeDirection,
+    payload: &CreateSwapPayload,
+) -> Result<sha256::Hash> {
+    let payload = serde_json::to_vec(&(pair, direction, payload))?;
+
+    Ok(sha256::Hash::hash(&payload))
+}
+
+/// The idempotency key was already used for a different swap request.
+#[derive(Debug, Clone, Copy)]
+pub struct IdempotencyKeyReused;
+
+impl fmt::Display for IdempotencyKeyReused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "idempotency key was used for a different request")
+    }
+}
+
+impl std::error::Error for IdempotencyKeyReused {}
+
+/// A swap with the same idempotency key or quote is being built.
+#[derive(Debug, Clone, Copy)]
+pub struct SwapInProgress;
+
+impl fmt::Display for SwapInProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a swap for the same request or quote is in progress")
+    }
+}
+
+impl std::error::Error for SwapInProgress {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn returns_transaction_for_repeated_request() {
+        let mut swaps = RecentSwaps::default();
+        let now = Instant::now();
+        let request = sha256::Hash::hash(b"request");
+        let transaction = Transaction::default();
+
+        swaps.insert("key".to_owned(), request, transaction.clone(), now);
+
+        assert_eq!(swaps.get("key", request, now).unwrap(), Some(transaction));
+        assert!(swaps
+            .get("key", sha256::Hash::hash(b"other request"), now)
+            .is_err());
+        assert_eq!(
+            swaps.get("key", request, now + IDEMPOTENCY_WINDOW).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn interleaved_requests_build_one_swap() {
+        let requests = SwapRequests::default();
+        let now = Instant::now();
+        let request = sha256::Hash::hash(b"request");
+        let transaction = Transaction::default();
+
+        let first = match requests.begin("key", request, None, now).unwrap() {
+            SwapRequest::Claimed(claim) => claim,
+            SwapRequest::Recent(_) => panic!("nothing was built yet"),
+        };
+
+        // the retry arrives while we are still building the swap
+        let retry = requests.begin("key", request, None, now).unwrap_err();
+        assert!(retry.is::<SwapInProgress>());
+
+        // and again right after we finished it
+        first.complete(transaction.clone(), now);
+        match requests.begin("key", request, None, now).unwrap() {
+            SwapRequest::Recent(recent) => assert_eq!(recent, transaction),
+            SwapRequest::Claimed(_) => panic!("swap was built twice"),
+        }
+    }
+
+    #[test]
+    fn rejects_concurrent_swap_with_same_quote() {
+        let requests = SwapRequests::default();
+        let now = Instant::now();
+
+        let claim = requests
+            .begin("key", sha256::Hash::hash(b"request"), Some("quote"), now)
+            .unwrap();
+        let other = sha256::Hash::hash(b"other request");
+        assert!(requests
+            .begin("other key", other, Some("quote"), now)
+            .unwrap_err()
+            .is::<SwapInProgress>());
+
+        drop(claim);
+        assert!(requests
+            .begin("other key", other, Some("quote"), now)
+            .is_ok());
+    }
+}
@@ -0,0 +1,570 @@
+use crate::{
+    database::{Database, InventoryForm, InventorySnapshot},
+    elements_rpc::Client,
+    metrics,
+    shutdown::Shutdown,
+    unix_timestamp, LiquidUsdt,
+};
+use anyhow::{bail, Context, Result};
+use elements::{bitcoin::Amount, AssetId};
+use reqwest::{header::CONTENT_TYPE, Url};
+use serde::Serialize;
+use std::{collections::HashMap, fmt, time::Duration};
+use tokio::time::sleep;
+
+/// How often we record our inventory and check whether it needs to
+/// be rebalanced.
+const INVENTORY_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// How much of an asset we are willing to hold, in its smallest
+/// unit.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AssetLimits {
+    pub min: Option<Amount>,
+    pub max: Option<Amount>,
+}
+
+/// How much of each asset of a market we are willing to hold.
+///
+/// Swaps that would take our balance of an asset below its minimum or
+/// above its maximum are rejected, and so are swaps of an asset we
+/// have no limits for.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InventoryLimits(HashMap<AssetId, AssetLimits>);
+
+/// A swap from our point of view, in the smallest unit of each asset.
+#[derive(Debug, Clone, Copy)]
+pub struct Trade {
+    /// The asset and amount we give away.
+    pub give: (AssetId, Amount),
+    /// The asset and amount we get in exchange.
+    pub receive: (AssetId, Amount),
+}
+
+impl InventoryLimits {
+    /// No limits for any of the `assets`, other than that we cannot
+    /// give away more than we have.
+    pub fn unlimited(assets: &[AssetId]) -> Self {
+        Self(
+            assets
+                .iter()
+                .map(|asset| (*asset, AssetLimits::default()))
+                .collect(),
+        )
+    }
+
+    /// Hold the `asset` within the `limits`.
+    pub fn with(mut self, asset: AssetId, limits: AssetLimits) -> Self {
+        self.0.insert(asset, limits);
+        self
+    }
+
+    fn get(&self, asset: AssetId) -> Result<AssetLimits> {
+        match self.0.get(&asset) {
+            Some(limits) => Ok(*limits),
+            None => bail!(NoInventoryLimits(asset)),
+        }
+    }
+
+    /// How much of the asset we `give` and the asset we `receive` a
+    /// trade may move without taking our balances beyond our limits,
+    /// given our balances of each.
+    ///
+    /// `None` means that there is no limit.
+    pub fn capacity(
+        &self,
+        (give_asset, give_balance): (AssetId, Amount),
+        (receive_asset, receive_balance): (AssetId, Amount),
+    ) -> Result<Capacity> {
+        let min_give = self.get(give_asset)?.min.map_or(0, Amount::as_sat);
+        let max_receive = self.get(receive_asset)?.max;
+
+        Ok(Capacity {
+            give: Some(give_balance.as_sat().saturating_sub(min_give)),
+            receive: max_receive.map(|max| max.as_sat().saturating_sub(receive_balance.as_sat())),
+        })
+    }
+
+    /// Ensure that our balances after the `trade` are still within
+    /// our limits, given our balances of the asset we give and the
+    /// one we receive.
+    pub fn check(&self, trade: Trade, give_balance: Amount, receive_balance: Amount) -> Result<()> {
+        let (give_asset, give_amount) = trade.give;
+        let (receive_asset, receive_amount) = trade.receive;
+
+        let give_balance = give_balance.as_sat().checked_sub(give_amount.as_sat());
+        if !is_within(give_balance, self.get(give_asset)?) {
+            bail!(InventoryLimitExceeded { asset: give_asset })
+        }
+
+        let receive_balance = receive_balance
+            .as_sat()
+            .checked_add(receive_amount.as_sat());
+        if !is_within(receive_balance, self.get(receive_asset)?) {
+            bail!(InventoryLimitExceeded {
+                asset: receive_asset
+            })
+        }
+
+        Ok(())
+    }
+}
+
+/// Balances outside of which the operator should rebalance our
+/// inventory.
+///
+/// Unlike the [`InventoryLimits`], crossing these does not stop us
+/// from trading.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RebalanceThresholds {
+    pub low_btc: Option<Amount>,
+    pub high_btc: Option<Amount>,
+    pub low_usdt: Option<LiquidUsdt>,
+    pub high_usdt: Option<LiquidUsdt>,
+}
+
+/// Where to send rebalance alerts to, in addition to our logs.
+#[derive(Debug, Clone, Default)]
+pub struct RebalanceAlerts {
+    pub thresholds: RebalanceThresholds,
+    /// URL we `POST` each [`RebalanceAlert`] to as JSON.
+    pub webhook: Option<Url>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Drift {
+    /// The balance dropped below the low threshold.
+    Low,
+    /// The balance rose above the high threshold.
+    High,
+}
+
+/// Our balance of an asset has drifted past one of its thresholds.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RebalanceAlert {
+    pub asset: &'static str,
+    pub drift: Drift,
+    /// Balance in the smallest unit of the asset.
+    pub balance: u64,
+    /// The threshold that was crossed, in the smallest unit of the
+    /// asset.
+    pub threshold: u64,
+}
+
+impl RebalanceThresholds {
+    /// The alerts for those of our balances which lie outside of the
+    /// thresholds.
+    pub fn check(&self, btc_balance: Amount, usdt_balance: LiquidUsdt) -> Vec<RebalanceAlert> {
+        let btc_thresholds = (
+            self.low_btc.map(Amount::as_sat),
+            self.high_btc.map(Amount::as_sat),
+        );
+        let usdt_thresholds = (
+            self.low_usdt.map(|low| low.as_satodollar()),
+            self.high_usdt.map(|high| high.as_satodollar()),
+        );
+
+        vec![
+            drift("L-BTC", btc_balance.as_sat(), btc_thresholds),
+            drift("L-USDt", usdt_balance.as_satodollar(), usdt_thresholds),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+fn drift(
+    asset: &'static str,
+    balance: u64,
+    (low, high): (Option<u64>, Option<u64>),
+) -> Option<RebalanceAlert> {
+    let (drift, threshold) = match (low, high) {
+        (Some(low), _) if balance < low => (Drift::Low, low),
+        (_, Some(high)) if balance > high => (Drift::High, high),
+        _ => return None,
+    };
+
+    Some(RebalanceAlert {
+        asset,
+        drift,
+        balance,
+        threshold,
+    })
+}
+
+/// Periodically record our L-BTC and L-USDt balances and alert the
+/// operator whenever one of them drifts past its thresholds, until
+/// the `shutdown` is triggered.
+pub async fn run_inventory_tracker(
+    elementsd: Client,
+    db: Database,
+    alerts: RebalanceAlerts,
+    btc_asset_id: AssetId,
+    usdt_asset_id: AssetId,
+    shutdown: Shutdown,
+) {
+    // the drift we last alerted about per asset, so that we only
+    // alert once per excursion
+    let mut drifts = HashMap::new();
+
+    loop {
+        if let Err(e) = track_inventory(
+            &elementsd,
+            &db,
+            &alerts,
+            btc_asset_id,
+            usdt_asset_id,
+            &mut drifts,
+        )
+        .await
+        {
+            tracing::error!("failed to track inventory: {:#}", e);
+        }
+
+        tokio::select! {
+            _ = sleep(INVENTORY_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => return,
+        }
+    }
+}
+
+async fn track_inventory(
+    elementsd: &Client,
+    db: &Database,
+    alerts: &RebalanceAlerts,
+    btc_asset_id: AssetId,
+    usdt_asset_id: AssetId,
+    drifts: &mut HashMap<&'static str, Drift>,
+) -> Result<()> {
+    let btc_balance = elementsd.get_balance(btc_asset_id).await?;
+    let usdt_balance = elementsd.get_balance(usdt_asset_id).await?;
+    let usdt_balance = LiquidUsdt::from_satodollar(usdt_balance.as_sat());
+
+    let recorded_at = unix_timestamp();
+    for (asset, balance) in [
+        ("L-BTC", btc_balance.as_sat()),
+        ("L-USDt", usdt_balance.as_satodollar()),
+    ]
+    .iter()
+    {
+        let snapshot = InventorySnapshot {
+            recorded_at,
+            asset: asset.to_string(),
+            balance: *balance,
+        };
+        db.do_in_transaction(|conn| InventoryForm::new(&snapshot)?.insert(conn))
+            .await
+            .context("failed to record inventory")?;
+    }
+
+    let current = alerts.thresholds.check(btc_balance, usdt_balance);
+    for asset in ["L-BTC", "L-USDt"].iter().copied() {
+        let alert = current.iter().find(|alert| alert.asset == asset);
+        metrics::INVENTORY_DRIFT.with_label_values(&[asset]).set(
+            match alert.map(|alert| alert.drift) {
+                Some(Drift::Low) => -1,
+                Some(Drift::High) => 1,
+                None => 0,
+            },
+        );
+
+        match alert {
+            Some(alert) if drifts.get(&asset) != Some(&alert.drift) => {
+                tracing::warn!(
+                    "{} balance of {} crossed the {:?} threshold of {}, please rebalance",
+                    alert.asset,
+                    alert.balance,
+                    alert.drift,
+                    alert.threshold
+                );
+                drifts.insert(asset, alert.drift);
+
+                if let Some(webhook) = &alerts.webhook {
+                    if let Err(e) = notify(webhook, alert).await {
+                        tracing::error!("failed to send rebalance alert: {:#}", e);
+                    }
+                }
+            }
+            Some(_) => {}
+            None => {
+                if drifts.remove(&asset).is_some() {
+                    tracing::info!("{} balance is back within its thresholds", asset);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn notify(webhook: &Url, alert: &RebalanceAlert) -> Result<()> {
+    reqwest::Client::new()
+        .post(webhook.clone())
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_string(alert)?)
+        .send()
+        .await?
+        .error_for_status()?;
+
+    Ok(())
+}
+
+/// The most a trade may move of each asset, in their smallest unit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capacity {
+    /// Of the asset we give away.
+    pub give: Option<u64>,
+    /// Of the asset we get in exchange.
+    pub receive: Option<u64>,
+}
+
+impl Capacity {
+    /// Scale down the `amount` of a trade in which we `give` and
+    /// `receive` the given amounts until it fits.
+    pub fn fit(&self, amount: u64, give: u64, receive: u64) -> u64 {
+        let scale = |amount: u64, moved: u64, max: Option<u64>| match max {
+            Some(max) if moved > max => (amount as u128 * max as u128 / moved as u128) as u64,
+            _ => amount,
+        };
+
+        scale(amount, give, self.give).min(scale(amount, receive, self.receive))
+    }
+}
+
+/// Whether the `balance` lies within the inclusive `limits`.
+///
+/// A `balance` of `None` has under- or overflowed and is never
+/// within bounds.
+fn is_within(balance: Option<u64>, limits: AssetLimits) -> bool {
+    let min = limits.min.map(Amount::as_sat);
+    let max = limits.max.map(Amount::as_sat);
+
+    match balance {
+        Some(balance) => {
+            min.map_or(true, |min| balance >= min) && max.map_or(true, |max| balance <= max)
+        }
+        None => false,
+    }
+}
+
+/// The requested swap would push our inventory of an asset beyond
+/// the configured limits.
+#[derive(Debug, Clone, Copy)]
+pub struct InventoryLimitExceeded {
+    pub asset: AssetId,
+}
+
+impl fmt::Display for InventoryLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "swap would exceed our inventory limits of {}",
+            self.asset
+        )
+    }
+}
+
+impl std::error::Error for InventoryLimitExceeded {}
+
+/// We have no inventory limits for an asset, so we do not trade it.
+#[derive(Debug, Clone, Copy)]
+pub struct NoInventoryLimits(pub AssetId);
+
+impl fmt::Display for NoInventoryLimits {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no inventory limits are configured for {}", self.0)
+    }
+}
+
+impl std::error::Error for NoInventoryLimits {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn btc() -> AssetId {
+        AssetId::from_slice(&[1; 32]).unwrap()
+    }
+
+    fn usdt() -> AssetId {
+        AssetId::from_slice(&[2; 32]).unwrap()
+    }
+
+    fn dollars(amount: &str) -> Amount {
+        LiquidUsdt::from_str_in_dollar(amount).unwrap().into()
+    }
+
+    #[test]
+    fn rejects_selling_btc_below_minimum_balance() {
+        let limits = InventoryLimits::unlimited(&[usdt()]).with(
+            btc(),
+            AssetLimits {
+                min: Some(Amount::from_btc(1.0).unwrap()),
+                max: None,
+            },
+        );
+        let trade = Trade {
+            give: (btc(), Amount::from_btc(0.5).unwrap()),
+            receive: (usdt(), dollars("10000")),
+        };
+
+        let error = limits
+            .check(trade, Amount::from_btc(1.2).unwrap(), Amount::ZERO)
+            .unwrap_err();
+
+        assert!(error.is::<InventoryLimitExceeded>());
+    }
+
+    #[test]
+    fn rejects_buying_btc_above_maximum_balance() {
+        let limits = InventoryLimits::unlimited(&[usdt()]).with(
+            btc(),
+            AssetLimits {
+                min: None,
+                max: Some(Amount::from_btc(2.0).unwrap()),
+            },
+        );
+        let trade = Trade {
+            give: (usdt(), dollars("10000")),
+            receive: (btc(), Amount::from_btc(0.5).unwrap()),
+        };
+
+        let result = limits.check(trade, dollars("20000"), Amount::from_btc(1.8).unwrap());
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rejects_trade_we_cannot_afford() {
+        let trade = Trade {
+            give: (usdt(), dollars("10000")),
+            receive: (btc(), Amount::from_btc(0.5).unwrap()),
+        };
+
+        let result = InventoryLimits::unlimited(&[btc(), usdt()]).check(
+            trade,
+            dollars("5000"),
+            Amount::ZERO,
+        );
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn rejects_asset_without_limits() {
+        let other = AssetId::from_slice(&[3; 32]).unwrap();
+        let trade = Trade {
+            give: (btc(), Amount::from_btc(0.5).unwrap()),
+            receive: (other, Amount::from_sat(1_000)),
+        };
+
+        let error = InventoryLimits::unlimited(&[btc(), usdt()])
+            .check(trade, Amount::from_btc(1.0).unwrap(), Amount::ZERO)
+            .unwrap_err();
+
+        assert!(error.is::<NoInventoryLimits>());
+    }
+
+    #[test]
+    fn trade_is_scaled_down_to_capacity() {
+        let limits = InventoryLimits::unlimited(&[usdt()]).with(
+            btc(),
+            AssetLimits {
+                min: Some(Amount::from_btc(1.0).unwrap()),
+                max: None,
+            },
+        );
+
+        let capacity = limits
+            .capacity(
+                (btc(), Amount::from_btc(1.5).unwrap()),
+                (usdt(), Amount::ZERO),
+            )
+            .unwrap();
+        let amount = capacity.fit(
+            dollars("40000").as_sat(),
+            Amount::ONE_BTC.as_sat(),
+            dollars("40000").as_sat(),
+        );
+
+        assert_eq!(
+            capacity,
+            Capacity {
+                give: Some(Amount::from_btc(0.5).unwrap().as_sat()),
+                receive: None,
+            }
+        );
+        assert_eq!(amount, dollars("20000").as_sat());
+    }
+
+    #[test]
+    fn alerts_on_balances_past_thresholds() {
+        let thresholds = RebalanceThresholds {
+            low_btc: Some(Amount::from_btc(1.0).unwrap()),
+            high_usdt: Some(LiquidUsdt::from_str_in_dollar("50000").unwrap()),
+            ..Default::default()
+        };
+
+        let alerts = thresholds.check(
+            Amount::from_btc(0.5).unwrap(),
+            LiquidUsdt::from_str_in_dollar("60000").unwrap(),
+        );
+
+        assert_eq!(
+            alerts,
+            vec![
+                RebalanceAlert {
+                    asset: "L-BTC",
+                    drift: Drift::Low,
+                    balance: Amount::from_btc(0.5).unwrap().as_sat(),
+                    threshold: Amount::from_btc(1.0).unwrap().as_sat(),
+                },
+                RebalanceAlert {
+                    asset: "L-USDt",
+                    drift: Drift::High,
+                    balance: LiquidUsdt::from_str_in_dollar("60000")
+                        .unwrap()
+                        .as_satodollar(),
+                    threshold: LiquidUsdt::from_str_in_dollar("50000")
+                        .unwrap()
+                        .as_satodollar(),
+                },
+            ]
+        );
+        assert!(thresholds
+            .check(
+                Amount::from_btc(1.0).unwrap(),
+                LiquidUsdt::from_str_in_dollar("50000").unwrap()
+            )
+            .is_empty());
+    }
+
+    #[test]
+    fn accepts_trade_within_limits() {
+        let limits = InventoryLimits::default()
+            .with(
+                btc(),
+                AssetLimits {
+                    min: Some(Amount::from_btc(1.0).unwrap()),
+                    max: None,
+                },
+            )
+            .with(
+                usdt(),
+                AssetLimits {
+                    min: None,
+                    max: Some(dollars("50000")),
+                },
+            );
+        let trade = Trade {
+            give: (btc(), Amount::from_btc(0.5).unwrap()),
+            receive: (usdt(), dollars("10000")),
+        };
+
+        let result = limits.check(trade, Amount::from_btc(2.0).unwrap(), dollars("20000"));
+
+        assert!(result.is_ok());
+    }
+}
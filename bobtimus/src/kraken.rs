@@ -1,79 +1,217 @@
-use crate::{LatestRate, LiquidUsdt, Rate, RateSubscription};
-use anyhow::{anyhow, bail, Result};
-use futures::{SinkExt, StreamExt};
+use crate::{
+    shutdown::Shutdown, supervisor::supervise, LatestRate, LiquidUsdt, Rate, RateSubscription,
+    RateUpdate, StaleRate,
+};
+use anyhow::{anyhow, bail, Context, Result};
+use futures::{stream::BoxStream, SinkExt, StreamExt};
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::convert::TryFrom;
-use tokio::sync::watch;
-use tokio_tungstenite::tungstenite::Message;
-use watch::Receiver;
+use std::{
+    convert::TryFrom,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::watch::{self, Receiver, Sender},
+    time::sleep,
+};
+use tokio_tungstenite::tungstenite::{self, Message};
 
 const KRAKEN_WS_URL: &str = "wss://ws.kraken.com";
-const SUBSCRIBE_XBT_USD_TICKER_PAYLOAD: &str = r#"
-{ "event": "subscribe",
-  "pair": [ "XBT/USD" ],
-  "subscription": {
-    "name": "ticker"
-  }
-}"#;
+const XBT_USD: &str = "XBT/USD";
+
+/// We refuse to quote if we have not heard from Kraken for longer
+/// than this.
+///
+/// Kraken only sends ticker updates when the rate changes, but it
+/// sends a heartbeat every second otherwise, so we can tell an idle
+/// market apart from a dead connection.
+const MAX_RATE_AGE: Duration = Duration::from_secs(30);
+
+const INITIAL_RECONNECT_DELAY: Duration = Duration::from_secs(1);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(60);
+
+type Ticker = BoxStream<'static, Result<Message, tungstenite::Error>>;
 
 #[derive(Clone)]
 pub struct RateService {
-    receiver: Receiver<Rate>,
+    receiver: Receiver<RateUpdate>,
+    last_update: Receiver<Option<Instant>>,
 }
 
 impl LatestRate for RateService {
-    fn latest_rate(&mut self) -> Rate {
-        *self.receiver.borrow()
+    fn latest_rate(&self) -> Result<Rate> {
+        let last_update = *self.last_update.borrow();
+        match last_update {
+            Some(last_update) if last_update.elapsed() <= MAX_RATE_AGE => {
+                Ok(self.receiver.borrow().rate)
+            }
+            _ => Err(StaleRate.into()),
+        }
+    }
+
+    fn subscribe(&self) -> RateSubscription {
+        RateSubscription::from(self.receiver.clone())
     }
 }
 
 impl RateService {
-    pub async fn new() -> Result<Self> {
-        let (tx, rx) = watch::channel(Rate::ZERO);
-
-        let (ws, _response) =
-            tokio_tungstenite::connect_async(Url::parse(KRAKEN_WS_URL).expect("valid url")).await?;
-
-        let (mut write, mut read) = ws.split();
-
-        // TODO: Handle the possibility of losing the connection
-        // to the Kraken WS. Currently the stream would produce no
-        // further items, and consumers would assume that the rate
-        // is up to date
-        tokio::spawn(async move {
-            while let Some(msg) = read.next().await {
-                let msg = match msg {
-                    Ok(Message::Text(msg)) => msg,
-                    _ => continue,
-                };
-
-                let ticker = match serde_json::from_str::<TickerUpdate>(&msg) {
-                    Ok(ticker) => ticker,
-                    _ => continue,
-                };
-
-                let rate = match Rate::try_from(ticker) {
-                    Ok(rate) => rate,
-                    Err(e) => {
-                        tracing::error!("could not get rate from ticker update: {}", e);
-                        continue;
-                    }
-                };
+    pub async fn new(shutdown: Shutdown) -> Result<Self> {
+        Self::for_pair(XBT_USD, shutdown).await
+    }
+
+    /// Follow the ticker of any Kraken pair, e.g. `XBT/EUR`, until the
+    /// `shutdown` is triggered.
+    pub async fn for_pair(pair: &str, shutdown: Shutdown) -> Result<Self> {
+        let (rate_sender, rate_receiver) = watch::channel(RateUpdate::first(Rate::ZERO));
+        let (update_sender, update_receiver) = watch::channel(None);
+        let rate_sender = Arc::new(rate_sender);
+        let update_sender = Arc::new(update_sender);
+
+        let mut ws = Some(connect(pair).await?);
 
-                let _ = tx.send(rate);
+        supervise("rate feed", shutdown.clone(), {
+            let pair = pair.to_owned();
+            let rate_receiver = rate_receiver.clone();
+            move || {
+                let ws = ws.take();
+                let pair = pair.clone();
+                let rate_sender = rate_sender.clone();
+                let update_sender = update_sender.clone();
+                // pick up the sequence where a failed run left off
+                let latest_update = *rate_receiver.borrow();
+                let shutdown = shutdown.clone();
+
+                async move {
+                    tokio::select! {
+                        _ = follow_ticker(pair, ws, &rate_sender, &update_sender, latest_update) => {}
+                        _ = shutdown.triggered() => {}
+                    }
+                }
             }
         });
 
-        write.send(SUBSCRIBE_XBT_USD_TICKER_PAYLOAD.into()).await?;
+        Ok(Self {
+            receiver: rate_receiver,
+            last_update: update_receiver,
+        })
+    }
+}
+
+/// Forward the rates of the `pair` from Kraken, reconnecting whenever
+/// the connection is lost.
+///
+/// Starts with the connection `ws`, if there is one.
+async fn follow_ticker(
+    pair: String,
+    mut ws: Option<Ticker>,
+    rate_sender: &Sender<RateUpdate>,
+    update_sender: &Sender<Option<Instant>>,
+    mut latest_update: RateUpdate,
+) {
+    let mut reconnect_delay = INITIAL_RECONNECT_DELAY;
+
+    loop {
+        let stream = match ws.take() {
+            Some(stream) => stream,
+            None => match connect(&pair).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    tracing::warn!("failed to reconnect to Kraken for {}: {:#}", pair, e);
 
-        Ok(Self { receiver: rx })
+                    sleep(reconnect_delay).await;
+                    reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
+                    continue;
+                }
+            },
+        };
+
+        let received_rate =
+            forward_rates(stream, rate_sender, update_sender, &mut latest_update).await;
+        if received_rate {
+            reconnect_delay = INITIAL_RECONNECT_DELAY;
+        }
+
+        tracing::warn!(
+            "lost connection to Kraken, reconnecting in {}s",
+            reconnect_delay.as_secs()
+        );
+        sleep(reconnect_delay).await;
+        reconnect_delay = (reconnect_delay * 2).min(MAX_RECONNECT_DELAY);
     }
+}
 
-    pub fn subscribe(&self) -> RateSubscription {
-        RateSubscription::from(self.receiver.clone())
+/// Connect to Kraken and subscribe to the ticker of the given `pair`.
+async fn connect(pair: &str) -> Result<Ticker> {
+    let (ws, _response) =
+        tokio_tungstenite::connect_async(Url::parse(KRAKEN_WS_URL).expect("valid url"))
+            .await
+            .context("failed to connect to Kraken")?;
+
+    let (mut write, read) = ws.split();
+    write
+        .send(Message::Text(subscribe_ticker_payload(pair)))
+        .await
+        .context("failed to subscribe to ticker")?;
+
+    Ok(read.boxed())
+}
+
+fn subscribe_ticker_payload(pair: &str) -> String {
+    serde_json::json!({
+        "event": "subscribe",
+        "pair": [pair],
+        "subscription": {
+            "name": "ticker"
+        }
+    })
+    .to_string()
+}
+
+/// Forward the rates received on the `stream` until the connection
+/// is lost.
+///
+/// Returns whether we received at least one rate.
+async fn forward_rates(
+    mut stream: Ticker,
+    rate_sender: &Sender<RateUpdate>,
+    update_sender: &Sender<Option<Instant>>,
+    latest_update: &mut RateUpdate,
+) -> bool {
+    let mut received_rate = false;
+
+    while let Some(msg) = stream.next().await {
+        let msg = match msg {
+            Ok(Message::Text(msg)) => msg,
+            Ok(Message::Close(_)) => break,
+            Ok(_) => continue,
+            Err(e) => {
+                tracing::warn!("error on Kraken connection: {}", e);
+                break;
+            }
+        };
+
+        match serde_json::from_str::<TickerUpdate>(&msg).map(Rate::try_from) {
+            Ok(Ok(rate)) => {
+                received_rate = true;
+                *latest_update = latest_update.next(rate);
+                let _ = rate_sender.send(*latest_update);
+            }
+            Ok(Err(e)) => {
+                tracing::error!("could not get rate from ticker update: {}", e);
+            }
+            Err(_) => {}
+        };
+
+        // after a reconnect, the rate we have is only current once
+        // Kraken has sent us a new one
+        if received_rate {
+            let _ = update_sender.send(Some(Instant::now()));
+        }
     }
+
+    received_rate
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -142,4 +280,24 @@ mod tests {
 
         let _ = serde_json::from_str::<TickerUpdate>(sample_response).unwrap();
     }
+
+    #[test]
+    fn refuses_to_quote_stale_rate() {
+        let (_rate_sender, receiver) = watch::channel(RateUpdate::first(Rate::ZERO));
+        let (update_sender, last_update) = watch::channel(None);
+        let service = RateService {
+            receiver,
+            last_update,
+        };
+
+        assert!(service.latest_rate().unwrap_err().is::<StaleRate>());
+
+        update_sender
+            .send(Some(Instant::now() - MAX_RATE_AGE - Duration::from_secs(1)))
+            .unwrap();
+        assert!(service.latest_rate().unwrap_err().is::<StaleRate>());
+
+        update_sender.send(Some(Instant::now())).unwrap();
+        assert!(service.latest_rate().is_ok());
+    }
 }
@@ -0,0 +1,357 @@
+use crate::{LiquidUsdt, Rate};
+use anyhow::{bail, Result};
+use elements::bitcoin::Amount;
+use serde::Serialize;
+use std::{convert::TryFrom, fmt};
+
+const BASIS_POINTS: u64 = 10_000;
+
+/// The terms under which we are willing to lend L-USDt against L-BTC
+/// collateral.
+#[derive(Debug, Clone)]
+pub struct LendingParameters {
+    /// Maximum ratio between principal and collateral value when we
+    /// lend, in basis points.
+    pub max_ltv_bps: u16,
+    /// Ratio between principal and collateral value at which a loan
+    /// is liquidated, in basis points.
+    pub liquidation_ltv_bps: u16,
+    /// Shortest loan duration we accept, in blocks.
+    pub min_timelock: u32,
+    /// Longest loan duration we accept, in blocks.
+    pub max_timelock: u32,
+    /// The biggest principal we are willing to lend in a single loan.
+    pub max_principal: LiquidUsdt,
+}
+
+impl Default for LendingParameters {
+    fn default() -> Self {
+        Self {
+            max_ltv_bps: 7_000,
+            liquidation_ltv_bps: 8_500,
+            // roughly one day to one month of Liquid blocks
+            min_timelock: 1_440,
+            max_timelock: 43_200,
+            max_principal: LiquidUsdt::from_satodollar(100_000 * 100_000_000),
+        }
+    }
+}
+
+/// Our current lending terms, as shown to borrowers before they
+/// make a loan request.
+///
+/// There is no interest rate in here because the repayment baru
+/// builds is only the principal.
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LoanOffer {
+    /// The rate at which we value the collateral.
+    pub rate: Rate,
+    pub max_ltv_bps: u16,
+    pub liquidation_ltv_bps: u16,
+    pub min_timelock: u32,
+    pub max_timelock: u32,
+    #[serde(serialize_with = "LiquidUsdt::serialize_to_nominal")]
+    pub max_principal: LiquidUsdt,
+}
+
+/// What we agreed on with the borrower of a loan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LoanTerms {
+    pub collateral: Amount,
+    pub principal: LiquidUsdt,
+    pub liquidation_ltv_bps: u16,
+}
+
+impl LoanTerms {
+    /// Ratio between what the borrower owes and the value of the
+    /// collateral at the bid of the `rate`, in basis points.
+    ///
+    /// Collateral which is worthless has an LTV of `u64::MAX`.
+    pub fn ltv_bps(&self, rate: Rate) -> u64 {
+        let debt = self.principal.as_satodollar() as u128;
+        let collateral_value = self.collateral.as_sat() as u128 * rate.bid.as_satodollar() as u128
+            / Amount::ONE_BTC.as_sat() as u128;
+        if collateral_value == 0 {
+            return u64::MAX;
+        }
+
+        let ltv = debt * BASIS_POINTS as u128 / collateral_value;
+
+        u64::try_from(ltv).unwrap_or(u64::MAX)
+    }
+
+    /// Whether the collateral is no longer worth enough at the
+    /// `rate` to secure the loan.
+    pub fn is_undercollateralized(&self, rate: Rate) -> bool {
+        self.ltv_bps(rate) >= u64::from(self.liquidation_ltv_bps)
+    }
+}
+
+impl LendingParameters {
+    /// Build an offer for the given collateral `rate`.
+    ///
+    /// We never offer to lend more than the `available` L-USDt.
+    pub fn offer(&self, rate: Rate, available: LiquidUsdt) -> LoanOffer {
+        let max_principal = if available.as_satodollar() < self.max_principal.as_satodollar() {
+            available
+        } else {
+            self.max_principal
+        };
+
+        LoanOffer {
+            rate,
+            max_ltv_bps: self.max_ltv_bps,
+            liquidation_ltv_bps: self.liquidation_ltv_bps,
+            min_timelock: self.min_timelock,
+            max_timelock: self.max_timelock,
+            max_principal,
+        }
+    }
+
+    /// The price of one L-BTC of collateral in L-USDt of principal,
+    /// i.e. the bid of the `rate` discounted by the maximum LTV.
+    pub fn lending_rate(&self, rate: Rate) -> LiquidUsdt {
+        let bid = rate.bid.as_satodollar() as u128;
+        let lending_rate = bid * self.max_ltv_bps as u128 / BASIS_POINTS as u128;
+
+        LiquidUsdt::from_satodollar(lending_rate as u64)
+    }
+
+    /// The most principal we lend for `duration` blocks against
+    /// `collateral`, valued at the `rate`.
+    pub fn max_principal(
+        &self,
+        rate: Rate,
+        collateral: Amount,
+        duration: u32,
+    ) -> Result<LiquidUsdt> {
+        if duration < self.min_timelock || duration > self.max_timelock {
+            bail!(UnacceptableTimelock {
+                requested: duration,
+                min: self.min_timelock,
+                max: self.max_timelock,
+            })
+        }
+
+        let lending_rate = Rate {
+            ask: LiquidUsdt::from_satodollar(0),
+            bid: self.lending_rate(rate),
+        };
+        let principal = lending_rate.buy_quote(collateral.into())?;
+        if principal.as_satodollar() > self.max_principal.as_satodollar() {
+            bail!(PrincipalTooLarge {
+                max_principal: self.max_principal,
+            })
+        }
+
+        Ok(principal)
+    }
+
+    /// The terms of a loan of `duration` blocks against `collateral`
+    /// in which baru built a loan transaction paying out `principal`
+    /// and a repayment of `repayment`.
+    ///
+    /// baru picks the principal itself, we only make sure that it
+    /// does not exceed what we would have lent at the `rate`.
+    pub fn terms(
+        &self,
+        rate: Rate,
+        collateral: Amount,
+        duration: u32,
+        principal: LiquidUsdt,
+        repayment: LiquidUsdt,
+    ) -> Result<LoanTerms> {
+        let max_principal = self.max_principal(rate, collateral, duration)?;
+        if principal.as_satodollar() > max_principal.as_satodollar() {
+            bail!(PrincipalTooLarge { max_principal })
+        }
+
+        if repayment.as_satodollar() < principal.as_satodollar() {
+            bail!("repayment is smaller than the principal")
+        }
+
+        Ok(LoanTerms {
+            collateral,
+            principal,
+            liquidation_ltv_bps: self.liquidation_ltv_bps,
+        })
+    }
+}
+
+/// The borrower asked for a loan duration outside of the range we
+/// accept.
+#[derive(Debug, Clone, Copy)]
+pub struct UnacceptableTimelock {
+    pub requested: u32,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl fmt::Display for UnacceptableTimelock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "loan duration of {} blocks is not between {} and {} blocks",
+            self.requested, self.min, self.max
+        )
+    }
+}
+
+impl std::error::Error for UnacceptableTimelock {}
+
+/// The collateral is worth more principal than we lend in a single
+/// loan.
+#[derive(Debug, Clone, Copy)]
+pub struct PrincipalTooLarge {
+    pub max_principal: LiquidUsdt,
+}
+
+impl fmt::Display for PrincipalTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "principal is bigger than the maximum of {:?}",
+            self.max_principal
+        )
+    }
+}
+
+impl std::error::Error for PrincipalTooLarge {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn max_principal_is_capped_by_available_liquidity() {
+        let params = LendingParameters {
+            max_principal: LiquidUsdt::from_str_in_dollar("10000").unwrap(),
+            ..Default::default()
+        };
+        let available = LiquidUsdt::from_str_in_dollar("2500").unwrap();
+
+        let offer = params.offer(Rate::ZERO, available);
+
+        assert_eq!(offer.max_principal, available);
+    }
+
+    #[test]
+    fn loan_offer_serializes_principal_in_dollars() {
+        let offer = LendingParameters {
+            max_ltv_bps: 5_000,
+            liquidation_ltv_bps: 8_000,
+            min_timelock: 1_440,
+            max_timelock: 10_080,
+            max_principal: LiquidUsdt::from_str_in_dollar("10000").unwrap(),
+        }
+        .offer(Rate::ZERO, LiquidUsdt::from_str_in_dollar("20000").unwrap());
+
+        let serialized = serde_json::to_string(&offer).unwrap();
+
+        assert_eq!(
+            serialized,
+            "{\"rate\":{\"ask\":0.0,\"bid\":0.0},\"max_ltv_bps\":5000,\"liquidation_ltv_bps\":8000,\"min_timelock\":1440,\"max_timelock\":10080,\"max_principal\":10000.0}"
+        )
+    }
+
+    #[test]
+    fn max_principal_is_collateral_value_at_max_ltv() {
+        let params = LendingParameters {
+            max_ltv_bps: 5_000,
+            ..Default::default()
+        };
+        let rate = Rate {
+            ask: LiquidUsdt::from_str_in_dollar("40000").unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar("40000").unwrap(),
+        };
+
+        let max_principal = params
+            .max_principal(rate, Amount::ONE_BTC, params.min_timelock)
+            .unwrap();
+
+        assert_eq!(
+            max_principal,
+            LiquidUsdt::from_str_in_dollar("20000").unwrap()
+        );
+    }
+
+    #[test]
+    fn terms_are_what_baru_built() {
+        let params = LendingParameters {
+            max_ltv_bps: 5_000,
+            ..Default::default()
+        };
+        let rate = Rate {
+            ask: LiquidUsdt::from_str_in_dollar("40000").unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar("40000").unwrap(),
+        };
+        let principal = LiquidUsdt::from_str_in_dollar("10000").unwrap();
+        let repayment = LiquidUsdt::from_str_in_dollar("10100").unwrap();
+
+        let terms = params
+            .terms(
+                rate,
+                Amount::ONE_BTC,
+                params.min_timelock,
+                principal,
+                repayment,
+            )
+            .unwrap();
+
+        assert_eq!(terms.principal, principal);
+    }
+
+    #[test]
+    fn rejects_principal_above_max_ltv() {
+        let params = LendingParameters {
+            max_ltv_bps: 5_000,
+            ..Default::default()
+        };
+        let rate = Rate {
+            ask: LiquidUsdt::from_str_in_dollar("40000").unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar("40000").unwrap(),
+        };
+        let principal = LiquidUsdt::from_str_in_dollar("20000.01").unwrap();
+
+        let error = params
+            .terms(
+                rate,
+                Amount::ONE_BTC,
+                params.min_timelock,
+                principal,
+                principal,
+            )
+            .unwrap_err();
+
+        assert!(error.is::<PrincipalTooLarge>());
+    }
+
+    #[test]
+    fn loan_is_undercollateralized_once_ltv_reaches_threshold() {
+        let terms = LoanTerms {
+            collateral: Amount::ONE_BTC,
+            principal: LiquidUsdt::from_str_in_dollar("20000").unwrap(),
+            liquidation_ltv_bps: 8_500,
+        };
+        let rate = |bid| Rate {
+            ask: LiquidUsdt::from_str_in_dollar(bid).unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar(bid).unwrap(),
+        };
+
+        assert_eq!(terms.ltv_bps(rate("40800")), 5_000);
+        assert!(!terms.is_undercollateralized(rate("24001")));
+        assert!(terms.is_undercollateralized(rate("24000")));
+        assert!(terms.is_undercollateralized(Rate::ZERO));
+    }
+
+    #[test]
+    fn rejects_timelock_outside_of_range() {
+        let params = LendingParameters::default();
+
+        let error = params
+            .max_principal(Rate::ZERO, Amount::ONE_BTC, params.max_timelock + 1)
+            .unwrap_err();
+
+        assert!(error.is::<UnacceptableTimelock>());
+    }
+}
@@ -3,59 +3,129 @@ extern crate diesel;
 #[macro_use]
 extern crate diesel_migrations;
 
-use std::{collections::HashMap, convert::TryInto};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::{TryFrom, TryInto},
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use crate::{
-    database::{queries, Sqlite},
+    database::{
+        queries, Database, LimitOrderForm, LimitOrderRecord, LimitOrderStatus, LiquidationStatus,
+        QuoteForm, QuoteRecord, QuoteStatus, TradeDirection, TradeForm, TradeRecord,
+    },
     elements_rpc::{Client, ElementsRpc},
+    idempotency::{SwapRequest, SwapRequests},
+    inventory::{InventoryLimitExceeded, InventoryLimits, Trade},
+    lending::{LendingParameters, LoanOffer, LoanTerms},
+    limit_order::{LimitOrderClosed, LimitOrderRequest, UnknownLimitOrder},
+    market::{Market, UnknownPair, DEFAULT_PAIR},
+    quote::{
+        InvalidQuote, Quote, QuoteAlreadyUsed, QuoteExpired, QuoteParameters, QuoteTerms,
+        UnknownQuote, QUOTE_TTL,
+    },
+    shutdown::Shutdown,
+    wallet::Wallet,
+    webhooks::{Event, Webhooks},
 };
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use baru::{
     input::Input,
     loan::{Lender0, Lender1, LoanRequest, LoanResponse},
     swap,
 };
-use database::LiquidationForm;
+use database::{FeeBumpForm, LiquidationForm, LoanForm};
 use elements::{
     bitcoin::{
         secp256k1::{All, Secp256k1},
         Amount,
     },
     secp256k1_zkp::{
-        rand::{CryptoRng, RngCore},
-        SecretKey, SECP256K1,
+        rand::{rngs::StdRng, CryptoRng, RngCore, SeedableRng},
+        PublicKey, SecretKey, SECP256K1,
     },
     Address, AssetId, OutPoint, Transaction, Txid,
 };
 use futures::{stream, stream::FuturesUnordered, Stream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use tokio::sync::watch::Receiver;
+use tokio::{sync::watch::Receiver, time::sleep};
 
 mod amounts;
 
+pub mod accounting;
+pub mod app;
 pub mod cli;
+pub mod cold_storage;
 pub mod database;
 pub mod elements_rpc;
+pub mod fee;
 pub mod fixed_rate;
 pub mod http;
+pub mod idempotency;
+pub mod inventory;
 pub mod kraken;
+pub mod lending;
+pub mod limit_order;
+pub mod loan_book;
+pub mod logging;
+pub mod market;
+pub mod metrics;
 pub mod models;
+pub mod oracle;
 pub mod problem;
+pub mod quote;
+pub mod rate_history;
+pub mod rate_limit;
 pub mod schema;
+pub mod shutdown;
+pub mod supervisor;
+pub mod utxo_lease;
+pub mod wallet;
+pub mod webhooks;
 
 pub use amounts::*;
 
-pub const USDT_ASSET_ID: &str = "ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2";
+pub const USDT_ASSET_ID: &str = network_config::LIQUID_USDT_ASSET_ID;
 
+/// Shared by the HTTP routes and the limit order matcher.
+///
+/// Requests are handled concurrently, so the little state they change
+/// sits behind locks of its own, none of which is held while we wait
+/// for elementsd.
 pub struct Bobtimus<R, RS> {
-    pub rng: R,
+    /// Seeds a generator per request, see [`Bobtimus::request_rng`].
+    pub rng: Mutex<R>,
     pub rate_service: RS,
+    pub quote_params: QuoteParameters,
+    pub inventory_limits: InventoryLimits,
+    pub lending_params: LendingParameters,
     pub secp: Secp256k1<All>,
     pub elementsd: Client,
     pub btc_asset_id: AssetId,
     pub usdt_asset_id: AssetId,
-    pub db: Sqlite,
-    pub lender_states: HashMap<Txid, Lender1>,
+    pub db: Database,
+    /// Loans we offered, by the txid of their loan transaction.
+    pub lender_states: Mutex<HashMap<Txid, PendingLoan>>,
+    /// Markets we make in addition to the [`DEFAULT_PAIR`].
+    pub markets: HashMap<String, Market>,
+    /// The key we sign quotes with.
+    pub quote_key: SecretKey,
+    pub swap_requests: SwapRequests,
+    pub wallet: Wallet,
+    pub webhooks: Webhooks,
+}
+
+/// A loan we offered, waiting for Alice to finalize it.
+pub struct PendingLoan {
+    pub lender: Lender1,
+    pub terms: LoanTerms,
+    /// The address we want to be repaid to.
+    pub repayment_address: Address,
+    /// Our inputs to the loan transaction, leased until it is
+    /// finalized or the lease expires.
+    pub inputs: Vec<OutPoint>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -63,6 +133,13 @@ pub struct CreateSwapPayload {
     pub alice_inputs: Vec<AliceInput>,
     pub address: Address,
     pub amount: u64,
+    /// The fee rate Alice funded her inputs for, in sat/vbyte.
+    #[serde(default)]
+    pub fee_rate: Option<u64>,
+    /// A quote we handed out earlier, to trade at its rate instead of
+    /// the latest one.
+    #[serde(default)]
+    pub quote: Option<Quote>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
@@ -77,104 +154,739 @@ where
     RS: LatestRate,
 {
     /// Handle Alice's request to create a swap transaction in which
-    /// she buys L-BTC from us and in return we get L-USDt from her.
+    /// she buys the base asset of the `pair` from us and in return we
+    /// get the quote asset from her.
+    ///
+    /// Repeating a request with the same `idempotency_key`, or the
+    /// same payload if there is none, returns the same transaction.
+    /// A repeat while we are still building it, or another swap with
+    /// the same quote, is rejected.
+    #[tracing::instrument(skip(self, payload))]
     pub async fn handle_create_buy_swap(
-        &mut self,
+        &self,
+        pair: &str,
         payload: CreateSwapPayload,
+        idempotency_key: Option<String>,
     ) -> Result<Transaction> {
-        let usdt_amount = LiquidUsdt::from_satodollar(payload.amount);
-        let latest_rate = self.rate_service.latest_rate();
-        let btc_amount = latest_rate.sell_base(usdt_amount)?;
+        let request = idempotency::request_digest(pair, TradeDirection::Buy, &payload)?;
+        let key = idempotency_key.unwrap_or_else(|| request.to_string());
+        let quote_id = payload.quote.as_ref().map(|quote| quote.terms.id.clone());
+        let swap_request =
+            self.swap_requests
+                .begin(&key, request, quote_id.as_deref(), Instant::now())?;
+        let claim = match swap_request {
+            SwapRequest::Recent(transaction) => return Ok(transaction),
+            SwapRequest::Claimed(claim) => claim,
+        };
+
+        let result = self.create_buy_swap(pair, payload).await;
+        if let Some(quote_id) = quote_id {
+            self.record_quote_outcome(&quote_id, &result).await;
+        }
+
+        let transaction = result?;
+        claim.complete(transaction.clone(), Instant::now());
+
+        Ok(transaction)
+    }
 
+    /// A generator for the randomness of a single request, which it
+    /// may use across awaits without holding on to our lock.
+    fn request_rng(&self) -> StdRng {
+        let mut rng = self.rng.lock().expect("lock is never poisoned");
+
+        StdRng::from_rng(&mut *rng).expect("seeding from a CSPRNG never fails")
+    }
+
+    async fn create_buy_swap(&self, pair: &str, payload: CreateSwapPayload) -> Result<Transaction> {
+        let _timer = metrics::QUOTE_DURATION.start_timer();
+
+        let (base_asset_id, quote_asset_id, _) = self.market(pair)?;
+
+        let terms = self.swap_terms(pair, TradeDirection::Buy, &payload).await?;
+        let base_amount = Amount::from_sat(terms.base_amount);
+        let quote_amount = Amount::from_sat(terms.quote_amount);
+        self.check_inventory(
+            pair,
+            Trade {
+                give: (base_asset_id, base_amount),
+                receive: (quote_asset_id, quote_amount),
+            },
+        )?;
+
+        let fee_rate = self.agree_on_fee_rate(payload.fee_rate).await?;
+        self.check_swap_size(
+            (base_asset_id, base_amount),
+            (quote_asset_id, quote_amount),
+            payload.alice_inputs.len(),
+            fee_rate,
+        )?;
         let transaction = self
             .swap_transaction(
-                (self.usdt_asset_id, usdt_amount.into()),
-                (self.btc_asset_id, btc_amount.into()),
+                (quote_asset_id, quote_amount),
+                (base_asset_id, base_amount),
                 payload.alice_inputs,
                 payload.address,
                 self.btc_asset_id,
+                fee_rate,
             )
             .await?;
 
+        self.record_trade(TradeRecord {
+            txid: transaction.txid(),
+            created_at: unix_timestamp(),
+            pair: pair.to_owned(),
+            direction: TradeDirection::Buy,
+            base_amount: base_amount.as_sat(),
+            quote_amount: quote_amount.as_sat(),
+            rate: LiquidUsdt::from_satodollar(terms.rate),
+            fee: accounting::transaction_fee(&transaction),
+        })
+        .await?;
+        self.webhooks.notify(Event::SwapCompleted {
+            txid: transaction.txid(),
+            pair: pair.to_owned(),
+            direction: TradeDirection::Buy,
+            base_amount: base_amount.as_sat(),
+            quote_amount: quote_amount.as_sat(),
+        });
+
+        metrics::TRADES.with_label_values(&[pair, "buy"]).inc();
+        metrics::TRADE_VOLUME
+            .with_label_values(&[self.asset_label(base_asset_id).as_str()])
+            .inc_by(base_amount.as_sat());
+
         Ok(transaction)
     }
 
     /// Handle Alice's request to create a swap transaction in which
-    /// she sells L-BTC and we give her L-USDt.
+    /// she sells the base asset of the `pair` and we give her the
+    /// quote asset.
+    ///
+    /// Repeating a request with the same `idempotency_key`, or the
+    /// same payload if there is none, returns the same transaction.
+    /// A repeat while we are still building it, or another swap with
+    /// the same quote, is rejected.
+    #[tracing::instrument(skip(self, payload))]
     pub async fn handle_create_sell_swap(
-        &mut self,
+        &self,
+        pair: &str,
+        payload: CreateSwapPayload,
+        idempotency_key: Option<String>,
+    ) -> Result<Transaction> {
+        let request = idempotency::request_digest(pair, TradeDirection::Sell, &payload)?;
+        let key = idempotency_key.unwrap_or_else(|| request.to_string());
+        let quote_id = payload.quote.as_ref().map(|quote| quote.terms.id.clone());
+        let swap_request =
+            self.swap_requests
+                .begin(&key, request, quote_id.as_deref(), Instant::now())?;
+        let claim = match swap_request {
+            SwapRequest::Recent(transaction) => return Ok(transaction),
+            SwapRequest::Claimed(claim) => claim,
+        };
+
+        let result = self.create_sell_swap(pair, payload).await;
+        if let Some(quote_id) = quote_id {
+            self.record_quote_outcome(&quote_id, &result).await;
+        }
+
+        let transaction = result?;
+        claim.complete(transaction.clone(), Instant::now());
+
+        Ok(transaction)
+    }
+
+    async fn create_sell_swap(
+        &self,
+        pair: &str,
         payload: CreateSwapPayload,
     ) -> Result<Transaction> {
-        let btc_amount = Amount::from_sat(payload.amount);
-        let latest_rate = self.rate_service.latest_rate();
-        let usdt_amount = latest_rate.buy_quote(btc_amount.into())?;
+        let _timer = metrics::QUOTE_DURATION.start_timer();
+
+        let (base_asset_id, quote_asset_id, _) = self.market(pair)?;
+
+        let terms = self
+            .swap_terms(pair, TradeDirection::Sell, &payload)
+            .await?;
+        let base_amount = Amount::from_sat(terms.base_amount);
+        let quote_amount = Amount::from_sat(terms.quote_amount);
+        self.check_inventory(
+            pair,
+            Trade {
+                give: (quote_asset_id, quote_amount),
+                receive: (base_asset_id, base_amount),
+            },
+        )?;
 
+        let fee_rate = self.agree_on_fee_rate(payload.fee_rate).await?;
+        self.check_swap_size(
+            (base_asset_id, base_amount),
+            (quote_asset_id, quote_amount),
+            payload.alice_inputs.len(),
+            fee_rate,
+        )?;
         let transaction = self
             .swap_transaction(
-                (self.btc_asset_id, btc_amount),
-                (self.usdt_asset_id, usdt_amount.into()),
+                (base_asset_id, base_amount),
+                (quote_asset_id, quote_amount),
                 payload.alice_inputs,
                 payload.address,
                 self.btc_asset_id,
+                fee_rate,
             )
             .await?;
 
+        self.record_trade(TradeRecord {
+            txid: transaction.txid(),
+            created_at: unix_timestamp(),
+            pair: pair.to_owned(),
+            direction: TradeDirection::Sell,
+            base_amount: base_amount.as_sat(),
+            quote_amount: quote_amount.as_sat(),
+            rate: LiquidUsdt::from_satodollar(terms.rate),
+            fee: accounting::transaction_fee(&transaction),
+        })
+        .await?;
+        self.webhooks.notify(Event::SwapCompleted {
+            txid: transaction.txid(),
+            pair: pair.to_owned(),
+            direction: TradeDirection::Sell,
+            base_amount: base_amount.as_sat(),
+            quote_amount: quote_amount.as_sat(),
+        });
+
+        metrics::TRADES.with_label_values(&[pair, "sell"]).inc();
+        metrics::TRADE_VOLUME
+            .with_label_values(&[self.asset_label(quote_asset_id).as_str()])
+            .inc_by(quote_amount.as_sat());
+
         Ok(transaction)
     }
 
-    async fn find_inputs(
-        elements_client: &Client,
-        asset_id: AssetId,
-        input_amount: Amount,
-    ) -> Result<Vec<Input>> {
-        let bob_inputs = elements_client
-            .select_inputs_for(asset_id, input_amount, false)
+    /// Sign a quote for a swap in the `direction` in which Alice gives
+    /// us `amount` of the asset she sells, valid for [`QUOTE_TTL`].
+    ///
+    /// If our inventory does not allow for a trade of that size, we
+    /// quote for the biggest part of it we can fill instead.
+    #[tracing::instrument(skip(self))]
+    pub async fn handle_quote(
+        &self,
+        pair: &str,
+        direction: TradeDirection,
+        amount: u64,
+    ) -> Result<Quote> {
+        let mut terms = self.price(pair, direction, amount)?;
+
+        let (give, achievable) = self.achievable_amount(&terms)?;
+        if achievable < amount {
+            if achievable == 0 {
+                bail!(InventoryLimitExceeded { asset: give })
+            }
+
+            terms = self.price(pair, direction, achievable)?;
+            terms.requested_amount = amount;
+        }
+
+        self.db
+            .do_in_transaction(|conn| QuoteForm::new(&terms, unix_timestamp())?.insert(conn))
             .await
-            .context("failed to select inputs for swap")?;
+            .context("failed to record quote")?;
 
-        let master_blinding_key = elements_client
-            .dumpmasterblindingkey()
+        Ok(terms.sign(&self.quote_key))
+    }
+
+    /// Look up a quote we handed out, including what became of it.
+    pub async fn handle_get_quote(&self, id: &str) -> Result<QuoteRecord> {
+        let mut quote = self
+            .db
+            .do_in_transaction(|conn| queries::get_quote(conn, id))
+            .await?
+            .ok_or_else(|| UnknownQuote(id.to_owned()))?;
+
+        // nobody tried to use the quote before it expired
+        if quote.status == QuoteStatus::Created && quote.expires_at <= unix_timestamp() {
+            quote.status = QuoteStatus::Expired;
+        }
+
+        Ok(quote)
+    }
+
+    /// Record what became of the quote with `quote_id` after Alice
+    /// tried to create a swap with it.
+    async fn record_quote_outcome(&self, quote_id: &str, result: &Result<Transaction>) {
+        let (status, txid, failure) = match result {
+            Ok(transaction) => (QuoteStatus::Executed, Some(transaction.txid()), None),
+            // the quote is not ours or was used before, leave its
+            // record as it is
+            Err(e) if e.is::<InvalidQuote>() || e.is::<QuoteAlreadyUsed>() => return,
+            Err(e) if e.is::<QuoteExpired>() => (QuoteStatus::Expired, None, None),
+            Err(e) => (QuoteStatus::Failed, None, Some(format!("{:#}", e))),
+        };
+
+        let result = self
+            .db
+            .do_in_transaction(|conn| {
+                queries::update_quote_status(conn, quote_id, status, txid, failure)
+            })
+            .await;
+        if let Err(e) = result {
+            tracing::error!("failed to record outcome of quote {}: {:#}", quote_id, e);
+        }
+    }
+
+    /// Rest Alice's limit order to swap in the `direction` once our rate
+    /// for the `pair` crosses her limit.
+    #[tracing::instrument(skip(self, request))]
+    pub async fn handle_place_limit_order(
+        &self,
+        pair: &str,
+        direction: TradeDirection,
+        request: LimitOrderRequest,
+    ) -> Result<LimitOrderRecord> {
+        self.market(pair)?;
+        let lifetime = request.lifetime()?;
+
+        let mut id = [0u8; 16];
+        self.rng
+            .lock()
+            .expect("lock is never poisoned")
+            .fill_bytes(&mut id);
+        let id = hex::encode(id);
+
+        let created_at = unix_timestamp();
+        let limit_rate = request.limit_rate;
+        let payload = request.into_swap_payload();
+        self.db
+            .do_in_transaction(|conn| {
+                LimitOrderForm::new(
+                    id.clone(),
+                    created_at,
+                    created_at + lifetime,
+                    pair,
+                    direction,
+                    limit_rate,
+                    &payload,
+                )?
+                .insert(conn)
+            })
             .await
-            .context("failed to dump master blinding key")?;
+            .context("failed to record limit order")?;
 
-        let master_blinding_key = hex::decode(master_blinding_key)?;
+        self.handle_get_limit_order(&id).await
+    }
+
+    /// Look up a limit order, including the swap transaction we
+    /// created once it was filled.
+    pub async fn handle_get_limit_order(&self, id: &str) -> Result<LimitOrderRecord> {
+        let mut order = self
+            .db
+            .do_in_transaction(|conn| queries::get_limit_order(conn, id))
+            .await?
+            .ok_or_else(|| UnknownLimitOrder(id.to_owned()))?;
+
+        // the order matcher did not get to it yet
+        if order.status == LimitOrderStatus::Open && order.expires_at <= unix_timestamp() {
+            order.status = LimitOrderStatus::Expired;
+        }
+
+        Ok(order)
+    }
 
-        let bob_inputs = bob_inputs
-            .into_iter()
-            .map(|(outpoint, txout)| {
-                use hmac::{Hmac, Mac, NewMac};
-                use sha2::Sha256;
+    /// Cancel a limit order which has not been filled yet.
+    pub async fn handle_cancel_limit_order(&self, id: &str) -> Result<LimitOrderRecord> {
+        let order = self.handle_get_limit_order(id).await?;
+        if order.status != LimitOrderStatus::Open {
+            bail!(LimitOrderClosed(id.to_owned()))
+        }
 
-                let mut mac = Hmac::<Sha256>::new_varkey(&master_blinding_key)
-                    .expect("HMAC can take key of any size");
-                mac.update(txout.script_pubkey.as_bytes());
+        self.db
+            .do_in_transaction(|conn| {
+                queries::close_limit_order(conn, id, LimitOrderStatus::Cancelled, None, None)
+            })
+            .await?;
 
-                let result = mac.finalize();
-                let input_blinding_sk = SecretKey::from_slice(&result.into_bytes())?;
+        self.handle_get_limit_order(id).await
+    }
 
-                Result::<_, anyhow::Error>::Ok(Input {
-                    txin: outpoint,
-                    original_txout: txout,
-                    blinding_key: input_blinding_sk,
-                })
+    /// Create the swap transactions of all open limit orders which our
+    /// current rates cross, and expire those whose time is up.
+    ///
+    /// Orders which cannot be filled because we lack an up-to-date
+    /// rate or the inventory stay open to be tried again.
+    pub async fn fill_limit_orders(&self) -> Result<()> {
+        let orders = self
+            .db
+            .do_in_transaction(queries::get_open_limit_orders)
+            .await
+            .context("failed to load open limit orders")?;
+
+        for order in orders {
+            if order.expires_at <= unix_timestamp() {
+                self.close_limit_order(&order.id, LimitOrderStatus::Expired, None, None)
+                    .await?;
+                continue;
+            }
+
+            let rate = match self.quoted_rate(&order.pair) {
+                Ok(rate) => rate,
+                Err(e) => {
+                    tracing::warn!("cannot match limit order {}: {:#}", order.id, e);
+                    continue;
+                }
+            };
+            if !limit_order::crosses(order.direction, order.limit_rate, rate) {
+                continue;
+            }
+
+            let result = match (order.direction, order.swap_payload()) {
+                (_, Err(e)) => Err(e),
+                (TradeDirection::Buy, Ok(payload)) => {
+                    self.create_buy_swap(&order.pair, payload).await
+                }
+                (TradeDirection::Sell, Ok(payload)) => {
+                    self.create_sell_swap(&order.pair, payload).await
+                }
+            };
+
+            match result {
+                Ok(transaction) => {
+                    tracing::info!(
+                        "Filled limit order {} with swap {}",
+                        order.id,
+                        transaction.txid()
+                    );
+                    self.close_limit_order(
+                        &order.id,
+                        LimitOrderStatus::Filled,
+                        Some(&transaction),
+                        None,
+                    )
+                    .await?;
+                }
+                Err(e) if e.is::<StaleRate>() || e.is::<InventoryLimitExceeded>() => {
+                    tracing::warn!("cannot fill limit order {} yet: {:#}", order.id, e);
+                }
+                Err(e) => {
+                    tracing::warn!("failed to fill limit order {}: {:#}", order.id, e);
+                    self.close_limit_order(
+                        &order.id,
+                        LimitOrderStatus::Failed,
+                        None,
+                        Some(format!("{:#}", e)),
+                    )
+                    .await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn close_limit_order(
+        &self,
+        id: &str,
+        status: LimitOrderStatus,
+        transaction: Option<&Transaction>,
+        failure: Option<String>,
+    ) -> Result<()> {
+        self.db
+            .do_in_transaction(|conn| {
+                queries::close_limit_order(conn, id, status, transaction, failure)
             })
-            .collect::<Result<Vec<_>, _>>()?;
+            .await
+            .with_context(|| format!("failed to close limit order {}", id))?;
 
-        Ok(bob_inputs)
+        Ok(())
     }
 
+    /// The asset we give in a swap with the `terms`, and the part of
+    /// the amount Alice asked to sell which our inventory allows us to
+    /// trade.
+    fn achievable_amount(&self, terms: &QuoteTerms) -> Result<(AssetId, u64)> {
+        let (base_asset_id, quote_asset_id, _) = self.market(&terms.pair)?;
+        let ((give, give_amount), (receive, receive_amount)) = match terms.direction {
+            TradeDirection::Buy => (
+                (base_asset_id, terms.base_amount),
+                (quote_asset_id, terms.quote_amount),
+            ),
+            TradeDirection::Sell => (
+                (quote_asset_id, terms.quote_amount),
+                (base_asset_id, terms.base_amount),
+            ),
+        };
+
+        let snapshot = self.wallet.snapshot()?;
+        let capacity = self.inventory_limits(&terms.pair)?.capacity(
+            (give, snapshot.balance(give)?),
+            (receive, snapshot.balance(receive)?),
+        )?;
+
+        Ok((
+            give,
+            capacity.fit(terms.requested_amount, give_amount, receive_amount),
+        ))
+    }
+
+    /// The terms at which we trade if Alice gives us `amount` of the
+    /// asset she sells, based on the latest rate.
+    fn price(&self, pair: &str, direction: TradeDirection, amount: u64) -> Result<QuoteTerms> {
+        let (_, _, quote_params) = self.market(pair)?;
+        let latest_rate = self.quoted_rate(pair)?;
+
+        let (rate, base_amount, quote_amount) = match direction {
+            TradeDirection::Buy => {
+                let quote_amount = Amount::from_sat(amount);
+                quote_params.check_quote_size(quote_amount)?;
+                let base_amount = latest_rate.sell_base(LiquidUsdt::from_satodollar(amount))?;
+
+                (latest_rate.ask, Amount::from(base_amount), quote_amount)
+            }
+            TradeDirection::Sell => {
+                let base_amount = Amount::from_sat(amount);
+                let quote_amount = Amount::from(latest_rate.buy_quote(base_amount.into())?);
+                quote_params.check_quote_size(quote_amount)?;
+
+                (latest_rate.bid, base_amount, quote_amount)
+            }
+        };
+
+        let mut id = [0u8; 16];
+        self.rng
+            .lock()
+            .expect("lock is never poisoned")
+            .fill_bytes(&mut id);
+
+        Ok(QuoteTerms {
+            id: hex::encode(id),
+            pair: pair.to_owned(),
+            direction,
+            rate: rate.as_satodollar(),
+            base_amount: base_amount.as_sat(),
+            quote_amount: quote_amount.as_sat(),
+            requested_amount: amount,
+            expires_at: unix_timestamp() + QUOTE_TTL.as_secs(),
+        })
+    }
+
+    /// The terms of the swap Alice asked for: those of her quote if
+    /// she handed one in, otherwise based on the latest rate.
+    async fn swap_terms(
+        &self,
+        pair: &str,
+        direction: TradeDirection,
+        payload: &CreateSwapPayload,
+    ) -> Result<QuoteTerms> {
+        let quote = match &payload.quote {
+            Some(quote) => quote,
+            None => return self.price(pair, direction, payload.amount),
+        };
+
+        let public_key = PublicKey::from_secret_key(SECP256K1, &self.quote_key);
+        let terms = quote.verify(&public_key, unix_timestamp())?;
+
+        let amount = match direction {
+            TradeDirection::Buy => terms.quote_amount,
+            TradeDirection::Sell => terms.base_amount,
+        };
+        if terms.pair != pair || terms.direction != direction || amount != payload.amount {
+            bail!(InvalidQuote)
+        }
+
+        let id = terms.id.clone();
+        let record = self
+            .db
+            .do_in_transaction(|conn| queries::get_quote(conn, &id))
+            .await?;
+        if let Some(QuoteRecord {
+            status: QuoteStatus::Executed,
+            ..
+        }) = record
+        {
+            bail!(QuoteAlreadyUsed)
+        }
+
+        Ok(terms.clone())
+    }
+
+    /// The fee rate of a swap transaction, given the one proposed by
+    /// Alice.
+    async fn agree_on_fee_rate(&self, proposed: Option<u64>) -> Result<Amount> {
+        let estimate = self
+            .elementsd
+            .estimate_fee_rate(fee::CONFIRMATION_TARGET)
+            .await
+            .context("failed to estimate fee rate")?;
+        let fee_rate = fee::agree(proposed, estimate)?;
+
+        Ok(Amount::from_sat(fee_rate))
+    }
+
+    /// Reject swaps of dust, or too small to be worth the fee of the
+    /// swap transaction at the `fee_rate`.
+    ///
+    /// We do not know how many of our inputs the swap transaction will
+    /// spend yet, so we assume one.
+    fn check_swap_size(
+        &self,
+        (base_asset_id, base_amount): (AssetId, Amount),
+        (quote_asset_id, quote_amount): (AssetId, Amount),
+        alice_inputs: usize,
+        fee_rate: Amount,
+    ) -> Result<()> {
+        let btc_amount = if base_asset_id == self.btc_asset_id {
+            Some(base_amount.as_sat())
+        } else if quote_asset_id == self.btc_asset_id {
+            Some(quote_amount.as_sat())
+        } else {
+            None
+        };
+        let fee = fee::estimate_swap_fee(alice_inputs as u64 + 1, fee_rate.as_sat());
+
+        fee::check_swap_size(
+            &[base_amount.as_sat(), quote_amount.as_sat()],
+            btc_amount,
+            fee,
+        )
+    }
+
+    async fn record_trade(&self, trade: TradeRecord) -> Result<()> {
+        self.db
+            .do_in_transaction(|conn| TradeForm::new(&trade)?.insert(conn))
+            .await
+            .context("failed to record trade")
+    }
+
+    /// The base asset, quote asset and quote parameters of the
+    /// market we make in `pair`.
+    fn market(&self, pair: &str) -> Result<(AssetId, AssetId, QuoteParameters)> {
+        if pair == DEFAULT_PAIR {
+            return Ok((self.btc_asset_id, self.usdt_asset_id, self.quote_params));
+        }
+
+        let market = self
+            .markets
+            .get(pair)
+            .ok_or_else(|| UnknownPair(pair.to_owned()))?;
+
+        Ok((
+            market.base_asset_id,
+            market.quote_asset_id,
+            market.quote_params,
+        ))
+    }
+
+    fn asset_label(&self, asset_id: AssetId) -> String {
+        if asset_id == self.btc_asset_id {
+            "L-BTC".to_owned()
+        } else if asset_id == self.usdt_asset_id {
+            "L-USDt".to_owned()
+        } else {
+            asset_id.to_string()
+        }
+    }
+
+    /// The inventory limits of the market we make in `pair`.
+    fn inventory_limits(&self, pair: &str) -> Result<&InventoryLimits> {
+        if pair == DEFAULT_PAIR {
+            return Ok(&self.inventory_limits);
+        }
+
+        let market = self
+            .markets
+            .get(pair)
+            .ok_or_else(|| UnknownPair(pair.to_owned()))?;
+
+        Ok(&market.inventory_limits)
+    }
+
+    /// Ensure that the `trade` in `pair` keeps our balances within the
+    /// inventory limits of its market.
+    ///
+    /// The balances are those of the latest wallet snapshot, which may
+    /// lag behind by a few seconds.
+    fn check_inventory(&self, pair: &str, trade: Trade) -> Result<()> {
+        let snapshot = self.wallet.snapshot()?;
+
+        self.inventory_limits(pair)?.check(
+            trade,
+            snapshot.balance(trade.give.0)?,
+            snapshot.balance(trade.receive.0)?,
+        )
+    }
+
+    /// The latest market rate of the `pair`, adjusted by our quote
+    /// parameters.
+    fn quoted_rate(&self, pair: &str) -> Result<Rate> {
+        if pair == DEFAULT_PAIR {
+            let market_rate = self.rate_service.latest_rate()?;
+
+            return Ok(self.quote_params.apply(market_rate));
+        }
+
+        let market = self
+            .markets
+            .get(pair)
+            .ok_or_else(|| UnknownPair(pair.to_owned()))?;
+        let market_rate = market.rate_service.latest_rate()?;
+
+        Ok(market.quote_params.apply(market_rate))
+    }
+
+    /// Build and sign our side of a swap transaction.
+    ///
+    /// Our inputs stay leased to the transaction until Alice had
+    /// enough time to broadcast it, so that concurrent swaps do not
+    /// spend the same UTXOs.
     async fn swap_transaction(
-        &mut self,
+        &self,
         (alice_input_asset_id, alice_input_amount): (AssetId, Amount),
         (bob_input_asset_id, bob_input_amount): (AssetId, Amount),
         alice_inputs: Vec<AliceInput>,
         alice_address: Address,
         btc_asset_id: AssetId,
+        fee_rate: Amount,
     ) -> Result<Transaction> {
-        let bob_inputs = Self::find_inputs(&self.elementsd, bob_input_asset_id, bob_input_amount)
+        let bob_inputs = self
+            .wallet
+            .select_inputs(bob_input_asset_id, bob_input_amount, true)
             .await
             .context("could not find transaction inputs for Bob")?;
+        let leased = bob_inputs
+            .iter()
+            .map(|input| input.txin)
+            .collect::<Vec<_>>();
+
+        let result = self
+            .build_swap_transaction(
+                (alice_input_asset_id, alice_input_amount),
+                (bob_input_asset_id, bob_input_amount),
+                bob_inputs,
+                alice_inputs,
+                alice_address,
+                btc_asset_id,
+                fee_rate,
+            )
+            .await;
+        if result.is_err() {
+            if let Err(e) = self.wallet.release(leased).await {
+                tracing::error!("failed to release inputs of swap: {:#}", e);
+            }
+        }
+
+        result
+    }
 
+    #[allow(clippy::too_many_arguments)]
+    async fn build_swap_transaction(
+        &self,
+        (alice_input_asset_id, alice_input_amount): (AssetId, Amount),
+        (bob_input_asset_id, bob_input_amount): (AssetId, Amount),
+        bob_inputs: Vec<Input>,
+        alice_inputs: Vec<AliceInput>,
+        alice_address: Address,
+        btc_asset_id: AssetId,
+        fee_rate: Amount,
+    ) -> Result<Transaction> {
         let bob_address = self
             .elementsd
             .get_new_segwit_confidential_address()
@@ -238,12 +950,12 @@ where
         )?;
 
         let transaction = swap::bob_create_transaction(
-            &mut self.rng,
+            &mut self.request_rng(),
             &self.secp,
             alice,
             bob,
             btc_asset_id,
-            Amount::from_sat(1), // TODO: Make this dynamic once there is something going on on Liquid
+            fee_rate,
             {
                 let elementsd = self.elementsd.clone();
                 move |transaction| async move {
@@ -258,44 +970,130 @@ where
         Ok(transaction)
     }
 
+    /// Tell Alice under which terms we are currently willing to lend
+    /// her L-USDt.
+    #[tracing::instrument(skip(self))]
+    pub async fn handle_loan_offer(&self) -> Result<LoanOffer> {
+        let latest_rate = self.quoted_rate(DEFAULT_PAIR)?;
+        let available = self.wallet.snapshot()?.balance(self.usdt_asset_id)?;
+
+        Ok(self
+            .lending_params
+            .offer(latest_rate, LiquidUsdt::from_satodollar(available.as_sat())))
+    }
+
     /// Handle Alice's loan request in which she puts up L-BTC as
     /// collateral and we give lend her L-USDt which she will have to
     /// repay in the future.
-    pub async fn handle_loan_request(&mut self, payload: LoanRequest) -> Result<LoanResponse> {
+    #[tracing::instrument(skip(self, payload))]
+    pub async fn handle_loan_request(&self, payload: LoanRequest) -> Result<LoanResponse> {
+        let latest_rate = self.quoted_rate(DEFAULT_PAIR)?;
+
+        let blockcount = self.elementsd.get_blockcount().await?;
+        let duration = payload.timelock.saturating_sub(u64::from(blockcount));
+        let duration = u32::try_from(duration).unwrap_or(u32::MAX);
+        let collateral = payload.collateral_amount;
+        // fail early, before baru builds a transaction we would refuse
+        self.lending_params
+            .max_principal(latest_rate, collateral, duration)?;
+
         let lender_address = self
             .elementsd
             .get_new_segwit_confidential_address()
             .await
             .context("failed to get lender address")?;
 
+        let mut rng = self.request_rng();
         let lender0 = Lender0::new(
-            &mut self.rng,
+            &mut rng,
             self.btc_asset_id,
             self.usdt_asset_id,
-            lender_address,
+            lender_address.clone(),
         )
         .unwrap();
 
-        let lender1 = lender0
-            .interpret(
-                &mut self.rng,
-                &SECP256K1,
-                {
-                    let elementsd_client = self.elementsd.clone();
-                    |amount, asset| async move {
-                        Self::find_inputs(&elementsd_client, asset, amount).await
-                    }
-                },
-                payload,
-                self.rate_service.latest_rate().bid.as_satodollar(),
-            )
-            .await
-            .unwrap();
+        // baru decides on the principal itself, so we record the
+        // L-USDt it asks us to fund instead of assuming it lends at
+        // our rate
+        let funded = Arc::new(Mutex::new(Amount::from_sat(0)));
+        // leased like the inputs of a swap, so that no swap spends
+        // them while Alice signs the loan transaction
+        let leased = Arc::new(Mutex::new(Vec::new()));
+        let built = async {
+            let lender1 = lender0
+                .interpret(
+                    &mut rng,
+                    &SECP256K1,
+                    {
+                        let wallet = self.wallet.clone();
+                        let funded = funded.clone();
+                        let leased = leased.clone();
+                        let usdt_asset_id = self.usdt_asset_id;
+                        move |amount, asset| {
+                            if asset == usdt_asset_id {
+                                *funded.lock().expect("lock is never poisoned") += amount;
+                            }
+
+                            let wallet = wallet.clone();
+                            let leased = leased.clone();
+                            async move {
+                                let inputs = wallet.select_inputs(asset, amount, true).await?;
+                                leased
+                                    .lock()
+                                    .expect("lock is never poisoned")
+                                    .extend(inputs.iter().map(|input| input.txin));
+
+                                Result::<_, anyhow::Error>::Ok(inputs)
+                            }
+                        }
+                    },
+                    payload,
+                    self.lending_params
+                        .lending_rate(latest_rate)
+                        .as_satodollar(),
+                )
+                .await
+                .context("failed to build loan transaction")?;
+            let principal = funded.lock().expect("lock is never poisoned").as_sat();
+            let principal = LiquidUsdt::from_satodollar(principal);
+
+            // the repayment baru builds is the principal alone
+            let terms = self.lending_params.terms(
+                latest_rate,
+                collateral,
+                duration,
+                principal,
+                principal,
+            )?;
+
+            Result::<_, anyhow::Error>::Ok((lender1, terms))
+        }
+        .await;
+        let inputs = std::mem::take(&mut *leased.lock().expect("lock is never poisoned"));
+        let (lender, terms) = match built {
+            Ok(built) => built,
+            Err(e) => {
+                if let Err(e) = self.wallet.release(inputs).await {
+                    tracing::error!("failed to release inputs of loan: {:#}", e);
+                }
+                return Err(e);
+            }
+        };
 
-        let loan_response = lender1.loan_response();
+        let loan_response = lender.loan_response();
 
         self.lender_states
-            .insert(loan_response.transaction.txid(), lender1);
+            .lock()
+            .expect("lock is never poisoned")
+            .insert(
+                loan_response.transaction.txid(),
+                PendingLoan {
+                    lender,
+                    terms,
+                    repayment_address: lender_address,
+                    inputs,
+                },
+            );
 
         Ok(loan_response)
     }
@@ -307,17 +1105,52 @@ where
     ///
     /// Additionally, we save the signed liquidation transaction so
     /// that we can broadcast it when the locktime is reached.
-    pub async fn finalize_loan(&mut self, transaction: Transaction) -> Result<Txid> {
+    #[tracing::instrument(skip(self, transaction), fields(txid = %transaction.txid()))]
+    pub async fn finalize_loan(&self, transaction: Transaction) -> Result<Txid> {
         // TODO: We should only take into account loan transactions which
         // are relatively recent e.g. within 1 minute. We expect the
         // borrower to quickly perform the protocol and let us broadcast
         // the loan transaction
 
-        let lender = self
+        // taken out so that a concurrent request cannot finalize the
+        // same loan, and put back if we fail to
+        let loan_txid = transaction.txid();
+        let loan = self
             .lender_states
-            .get(&transaction.txid())
+            .lock()
+            .expect("lock is never poisoned")
+            .remove(&loan_txid)
             .context("unknown loan transaction")?;
 
+        let result = self.originate_loan(transaction, &loan).await;
+        match &result {
+            // the loan transaction spent our inputs
+            Ok(_) => {
+                if let Err(e) = self.wallet.release(loan.inputs).await {
+                    tracing::warn!("failed to release inputs of loan: {:#}", e);
+                }
+            }
+            Err(_) => {
+                self.lender_states
+                    .lock()
+                    .expect("lock is never poisoned")
+                    .insert(loan_txid, loan);
+            }
+        }
+
+        result
+    }
+
+    /// Sign and broadcast the loan `transaction`, and record the loan
+    /// together with its liquidation transactions.
+    async fn originate_loan(&self, transaction: Transaction, loan: &PendingLoan) -> Result<Txid> {
+        let PendingLoan {
+            lender,
+            terms,
+            repayment_address,
+            ..
+        } = loan;
+
         let transaction = lender
             .finalise_loan(transaction, {
                 let elementsd = self.elementsd.clone();
@@ -326,9 +1159,22 @@ where
             .await?;
 
         let txid = self.elementsd.send_raw_transaction(&transaction).await?;
-
-        let liquidation_tx =
-            lender.liquidation_transaction(&mut self.rng, &self.secp, Amount::ONE_SAT)?;
+        if let Err(e) = self.wallet.refresh().await {
+            tracing::warn!("failed to refresh wallet balances: {:#}", e);
+        }
+
+        // one liquidation transaction per fee level, as we cannot
+        // sign new ones once the loan is due
+        let mut rng = self.request_rng();
+        let mut liquidation_txs = Vec::new();
+        for fee_rate in fee::LIQUIDATION_FEE_RATES.iter() {
+            let liquidation_tx = lender.liquidation_transaction(
+                &mut rng,
+                &self.secp,
+                Amount::from_sat(*fee_rate),
+            )?;
+            liquidation_txs.push(liquidation_tx);
+        }
         let locktime = lender
             .timelock
             .try_into()
@@ -336,47 +1182,224 @@ where
 
         self.db
             .do_in_transaction(|conn| {
-                LiquidationForm::new(txid, &liquidation_tx, locktime).insert(conn)?;
+                LiquidationForm::new(txid, &liquidation_txs[0], locktime).insert(conn)?;
+                for (fee_level, liquidation_tx) in liquidation_txs.iter().enumerate().skip(1) {
+                    FeeBumpForm::new(txid, fee_level, liquidation_tx)?.insert(conn)?;
+                }
+                LoanForm::new(txid, terms, unix_timestamp(), Some(repayment_address))?
+                    .insert(conn)?;
 
                 Ok(())
             })
             .await?;
+        self.webhooks.notify(Event::LoanOriginated {
+            loan_txid: txid,
+            collateral: terms.collateral.as_sat(),
+            principal: terms.principal.as_satodollar(),
+        });
 
         Ok(txid)
     }
 }
 
 pub trait LatestRate {
-    fn latest_rate(&mut self) -> Rate;
+    /// Return the latest rate, or [`StaleRate`] if the rate source
+    /// cannot vouch for it being up to date.
+    fn latest_rate(&self) -> Result<Rate>;
+
+    /// Follow every rate from now on.
+    fn subscribe(&self) -> RateSubscription;
+}
+
+/// The latest rate we know of is too old to quote with.
+#[derive(Debug, Clone, Copy)]
+pub struct StaleRate;
+
+impl fmt::Display for StaleRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "latest rate is stale")
+    }
+}
+
+impl std::error::Error for StaleRate {}
+
+/// A rate, numbered in the order in which the rate source produced
+/// it.
+///
+/// Subscribers only ever see the latest rate, so a jump in the
+/// sequence number tells them that they missed some updates.
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct RateUpdate {
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub rate: Rate,
+}
+
+impl RateUpdate {
+    pub fn first(rate: Rate) -> Self {
+        Self { sequence: 0, rate }
+    }
+
+    pub fn next(&self, rate: Rate) -> Self {
+        Self {
+            sequence: self.sequence + 1,
+            rate,
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct RateSubscription {
-    receiver: Receiver<Rate>,
+    receiver: Receiver<RateUpdate>,
+    quote_params: QuoteParameters,
 }
 
-impl From<Receiver<Rate>> for RateSubscription {
-    fn from(receiver: Receiver<Rate>) -> Self {
-        Self { receiver }
+impl From<Receiver<RateUpdate>> for RateSubscription {
+    fn from(receiver: Receiver<RateUpdate>) -> Self {
+        Self {
+            receiver,
+            quote_params: QuoteParameters::default(),
+        }
     }
 }
 
 impl RateSubscription {
+    /// Adjust the streamed rates by the given quote parameters, so
+    /// that subscribers see the rates we actually trade at.
+    pub fn quoted_with(self, quote_params: QuoteParameters) -> Self {
+        Self {
+            quote_params,
+            ..self
+        }
+    }
+
     pub fn into_stream(self) -> impl Stream<Item = Result<Rate>> {
-        stream::try_unfold(self.receiver, |mut receiver| async move {
+        self.into_sequenced_stream()
+            .map_ok(|rate_update| rate_update.rate)
+    }
+
+    pub fn into_sequenced_stream(self) -> impl Stream<Item = Result<RateUpdate>> {
+        let quote_params = self.quote_params;
+
+        stream::try_unfold(self.receiver, move |mut receiver| async move {
             receiver
                 .changed()
                 .await
                 .context("failed to receive latest rate update")?;
 
-            let latest_rate = *receiver.borrow();
+            let latest_update = *receiver.borrow();
+            let latest_update = RateUpdate {
+                rate: quote_params.apply(latest_update.rate),
+                ..latest_update
+            };
 
-            Ok(Some((latest_rate, receiver)))
+            Ok(Some((latest_update, receiver)))
         })
     }
 }
 
-pub async fn liquidate_loans(elementsd: &Client, db: Sqlite) -> Result<()> {
+/// How often the liquidation engine checks for loans to liquidate.
+const LIQUIDATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Keep liquidating loans in the background for as long as bobtimus
+/// is running.
+///
+/// The liquidation transactions are only valid once the loan's
+/// timelock has expired, so that is what triggers a liquidation.
+///
+/// We also watch the collateralization of every open loan at the
+/// latest rate of the `rate_service`. The covenant does not let us
+/// liquidate before the timelock has expired yet, so for now we can
+/// only warn about loans which crossed their liquidation threshold.
+///
+/// Stops between two rounds once the `shutdown` is triggered.
+pub async fn run_liquidation_engine<RS>(
+    elementsd: Client,
+    db: Database,
+    rate_service: RS,
+    webhooks: Webhooks,
+    shutdown: Shutdown,
+) where
+    RS: LatestRate,
+{
+    let mut undercollateralized = HashSet::new();
+
+    loop {
+        if let Err(e) = liquidate_loans(&elementsd, db.clone(), &webhooks).await {
+            tracing::error!("failed to liquidate loans: {:#}", e);
+        }
+
+        match rate_service.latest_rate() {
+            Ok(rate) => {
+                if let Err(e) = check_collateralization(&db, rate, &mut undercollateralized).await {
+                    tracing::error!("failed to check collateralization of loans: {:#}", e);
+                }
+            }
+            Err(e) => tracing::warn!("cannot check collateralization of loans: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = sleep(LIQUIDATION_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => return,
+        }
+    }
+}
+
+/// Warn about every open loan whose collateral is no longer worth
+/// enough at the `rate`, once per loan.
+///
+/// `undercollateralized` holds the loans we already warned about.
+async fn check_collateralization(
+    db: &Database,
+    rate: Rate,
+    undercollateralized: &mut HashSet<Txid>,
+) -> Result<()> {
+    let open_loans = db.do_in_transaction(queries::get_open_loans).await?;
+
+    let mut count = 0;
+    for (loan_txid, terms) in open_loans.iter() {
+        if !terms.is_undercollateralized(rate) {
+            undercollateralized.remove(loan_txid);
+            continue;
+        }
+
+        count += 1;
+        if undercollateralized.insert(*loan_txid) {
+            tracing::warn!(
+                "Loan {} crossed its liquidation threshold of {} bps at an LTV of {} bps",
+                loan_txid,
+                terms.liquidation_ltv_bps,
+                terms.ltv_bps(rate)
+            );
+        }
+    }
+    metrics::UNDERCOLLATERALIZED_LOANS.set(count);
+
+    Ok(())
+}
+
+/// Number of confirmations after which we consider a loan liquidated.
+const LIQUIDATION_CONFIRMATIONS: i64 = 6;
+
+/// Number of blocks we wait for a liquidation transaction to confirm
+/// before we replace it with one paying a higher fee.
+const FEE_BUMP_INTERVAL: u32 = 3;
+
+/// Broadcast the liquidation transactions of all loans whose timelock
+/// has expired and whose collateral has not been reclaimed by the
+/// borrower, and track the confirmation of the ones we already
+/// broadcast.
+///
+/// Transactions rejected by elementsd are retried in the next round
+/// at the next fee level, and transactions which do not confirm in
+/// time are replaced by ones paying a higher fee. A loan only counts
+/// as liquidated once its liquidation transaction has
+/// [`LIQUIDATION_CONFIRMATIONS`], so that it survives reorgs.
+///
+/// The `webhooks` are notified of every loan we find repaid or
+/// liquidated.
+#[tracing::instrument(skip(elementsd, db, webhooks))]
+pub async fn liquidate_loans(elementsd: &Client, db: Database, webhooks: &Webhooks) -> Result<()> {
     let blockcount = elementsd.get_blockcount().await?;
     let liquidation_txs = db
         .do_in_transaction(|conn| {
@@ -385,16 +1408,348 @@ pub async fn liquidate_loans(elementsd: &Client, db: Sqlite) -> Result<()> {
         })
         .await?;
 
-    for tx in liquidation_txs.iter() {
-        match elementsd.send_raw_transaction(&tx).await {
-            Ok(txid) => log::info!("Broadcast liquidation transaction {}", txid),
-            Err(e) => log::error!("Failed to broadcast liquidation transaction: {}", e),
-        };
+    for (loan_txid, tx) in liquidation_txs {
+        if let Err(e) =
+            broadcast_liquidation(elementsd, &db, webhooks, loan_txid, tx, blockcount).await
+        {
+            tracing::error!("Failed to liquidate loan {}: {:#}", loan_txid, e);
+        }
+    }
+
+    let unconfirmed = db
+        .do_in_transaction(queries::get_unconfirmed_liquidation_txids)
+        .await?;
+
+    for (loan_txid, liquidation_txid) in unconfirmed {
+        if let Err(e) = track_liquidation(
+            elementsd,
+            &db,
+            webhooks,
+            loan_txid,
+            liquidation_txid,
+            blockcount,
+        )
+        .await
+        {
+            tracing::error!(
+                "Failed to track liquidation transaction {} of loan {}: {:#}",
+                liquidation_txid,
+                loan_txid,
+                e
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Check whether the liquidation transaction with `liquidation_txid`
+/// we broadcast for the loan with `loan_txid` confirmed, and replace
+/// it if it is stuck or was superseded.
+async fn track_liquidation(
+    elementsd: &Client,
+    db: &Database,
+    webhooks: &Webhooks,
+    loan_txid: Txid,
+    liquidation_txid: Txid,
+    blockcount: u32,
+) -> Result<()> {
+    let confirmations = elementsd.get_confirmations(liquidation_txid).await?;
+
+    if confirmations >= LIQUIDATION_CONFIRMATIONS {
+        tracing::info!("Liquidation transaction {} confirmed", liquidation_txid);
+        db.do_in_transaction(|conn| {
+            queries::update_liquidation_status(
+                conn,
+                loan_txid,
+                LiquidationStatus::Confirmed,
+                Some(liquidation_txid),
+            )
+        })
+        .await?;
+        webhooks.notify(Event::LoanLiquidated {
+            loan_txid,
+            liquidation_txid,
+        });
+    } else if confirmations < 0 {
+        track_conflicted_liquidation(
+            elementsd,
+            db,
+            webhooks,
+            loan_txid,
+            liquidation_txid,
+            blockcount,
+        )
+        .await?;
+    } else if confirmations == 0 {
+        bump_liquidation_fee(elementsd, db, loan_txid, blockcount).await?;
     }
 
     Ok(())
 }
 
+/// Liquidate the loan with `loan_txid` right away, regardless of the
+/// fee level we would otherwise wait for.
+///
+/// Fails if the loan is not pending liquidation, or if elementsd
+/// rejects the liquidation transaction, e.g. because the timelock of
+/// the loan has not expired yet.
+pub async fn liquidate_loan(
+    elementsd: &Client,
+    db: Database,
+    webhooks: &Webhooks,
+    loan_txid: Txid,
+) -> Result<()> {
+    let (tx, attempt) = db
+        .do_in_transaction(|conn| {
+            let tx = queries::get_liquidation_tx(conn, loan_txid)?;
+            let attempt = queries::get_liquidation_attempt(conn, loan_txid)?;
+
+            Ok((tx, attempt))
+        })
+        .await
+        .with_context(|| format!("unknown loan {}", loan_txid))?;
+    if attempt.status != LiquidationStatus::Pending {
+        bail!(
+            "loan {} is not pending liquidation: {:?}",
+            loan_txid,
+            attempt.status
+        )
+    }
+
+    let blockcount = elementsd.get_blockcount().await?;
+    broadcast_liquidation(elementsd, &db, webhooks, loan_txid, tx, blockcount).await
+}
+
+/// Broadcast the liquidation transaction `tx` of the loan with
+/// `loan_txid` at the fee level matching the current estimate, unless
+/// the borrower already reclaimed the collateral.
+///
+/// A failed broadcast is recorded so that the next attempt pays a
+/// higher fee.
+async fn broadcast_liquidation(
+    elementsd: &Client,
+    db: &Database,
+    webhooks: &Webhooks,
+    loan_txid: Txid,
+    tx: Transaction,
+    blockcount: u32,
+) -> Result<()> {
+    let collateral = tx
+        .input
+        .first()
+        .context("liquidation transaction without inputs")?
+        .previous_output;
+
+    if !elementsd.is_unspent(collateral).await? {
+        tracing::info!(
+            "Collateral of loan {} was reclaimed by the borrower",
+            loan_txid
+        );
+        db.do_in_transaction(|conn| {
+            queries::update_liquidation_status(conn, loan_txid, LiquidationStatus::Repaid, None)
+        })
+        .await?;
+        webhooks.notify(Event::LoanRepaid { loan_txid });
+        return Ok(());
+    }
+
+    let estimate = elementsd
+        .estimate_fee_rate(fee::CONFIRMATION_TARGET)
+        .await
+        .context("failed to estimate fee rate")?;
+    let attempt = db
+        .do_in_transaction(|conn| queries::get_liquidation_attempt(conn, loan_txid))
+        .await?;
+    let txs = fee_levels(db, loan_txid, tx).await?;
+    let fee_level = attempt
+        .fee_level
+        .max(fee::liquidation_fee_level(estimate))
+        .min(txs.len() - 1);
+
+    match elementsd.send_raw_transaction(&txs[fee_level]).await {
+        Ok(txid) => {
+            tracing::info!("Broadcast liquidation transaction {}", txid);
+            db.do_in_transaction(|conn| {
+                queries::record_liquidation_broadcast(conn, loan_txid, fee_level, txid, blockcount)
+            })
+            .await?;
+
+            Ok(())
+        }
+        Err(e) => {
+            let next_fee_level = (fee_level + 1).min(txs.len() - 1);
+            db.do_in_transaction(|conn| {
+                queries::record_liquidation_failure(
+                    conn,
+                    loan_txid,
+                    next_fee_level,
+                    blockcount,
+                    format!("{:#}", e),
+                )
+            })
+            .await?;
+
+            Err(e.context("failed to broadcast liquidation transaction"))
+        }
+    }
+}
+
+/// All liquidation transactions of the loan with `loan_txid`, by fee
+/// level, starting with the one stored with the loan.
+pub(crate) async fn fee_levels(
+    db: &Database,
+    loan_txid: Txid,
+    first: Transaction,
+) -> Result<Vec<Transaction>> {
+    let fee_bumps = db
+        .do_in_transaction(|conn| queries::get_fee_bumps(conn, loan_txid))
+        .await?;
+
+    Ok(std::iter::once(first).chain(fee_bumps).collect())
+}
+
+/// Replace the unconfirmed liquidation transaction of the loan with
+/// `loan_txid` by the one at the next fee level, if it did not confirm
+/// within [`FEE_BUMP_INTERVAL`] blocks.
+///
+/// Otherwise broadcast it again, in case it was evicted from the
+/// mempool or reorged out.
+async fn bump_liquidation_fee(
+    elementsd: &Client,
+    db: &Database,
+    loan_txid: Txid,
+    blockcount: u32,
+) -> Result<()> {
+    let (first, attempt) = db
+        .do_in_transaction(|conn| {
+            let first = queries::get_liquidation_tx(conn, loan_txid)?;
+            let attempt = queries::get_liquidation_attempt(conn, loan_txid)?;
+
+            Ok((first, attempt))
+        })
+        .await?;
+    let txs = fee_levels(db, loan_txid, first).await?;
+    let current = txs
+        .get(attempt.fee_level)
+        .context("unknown liquidation fee level")?;
+
+    let waited = blockcount.saturating_sub(attempt.last_attempt_height.unwrap_or(0));
+    let next_fee_level = attempt.fee_level + 1;
+    if waited < FEE_BUMP_INTERVAL || next_fee_level >= txs.len() {
+        if let Err(e) = elementsd.send_raw_transaction(current).await {
+            tracing::debug!(
+                "Failed to rebroadcast liquidation transaction {}: {:#}",
+                current.txid(),
+                e
+            );
+        }
+
+        return Ok(());
+    }
+
+    match elementsd.send_raw_transaction(&txs[next_fee_level]).await {
+        Ok(txid) => {
+            tracing::info!(
+                "Replaced liquidation transaction {} with {} paying a higher fee",
+                current.txid(),
+                txid
+            );
+            db.do_in_transaction(|conn| {
+                queries::record_liquidation_broadcast(
+                    conn,
+                    loan_txid,
+                    next_fee_level,
+                    txid,
+                    blockcount,
+                )
+            })
+            .await?;
+        }
+        Err(e) => {
+            // the replacement is rejected if the liquidation
+            // transaction does not signal replaceability, in which
+            // case we have to keep waiting for the current one
+            tracing::warn!(
+                "Failed to replace liquidation transaction {}: {:#}",
+                current.txid(),
+                e
+            );
+            db.do_in_transaction(|conn| {
+                queries::record_liquidation_failure(
+                    conn,
+                    loan_txid,
+                    attempt.fee_level,
+                    blockcount,
+                    format!("{:#}", e),
+                )
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The liquidation transaction we last broadcast for the loan with
+/// `loan_txid` conflicts with a confirmed transaction.
+///
+/// That is either one of our other liquidation transactions, which we
+/// then keep tracking instead, or the borrower's repayment.
+async fn track_conflicted_liquidation(
+    elementsd: &Client,
+    db: &Database,
+    webhooks: &Webhooks,
+    loan_txid: Txid,
+    liquidation_txid: Txid,
+    blockcount: u32,
+) -> Result<()> {
+    let first = db
+        .do_in_transaction(|conn| queries::get_liquidation_tx(conn, loan_txid))
+        .await?;
+    let txs = fee_levels(db, loan_txid, first).await?;
+
+    for (fee_level, tx) in txs.iter().enumerate() {
+        let txid = tx.txid();
+        // transactions we never broadcast are unknown to the wallet
+        if !matches!(elementsd.get_confirmations(txid).await, Ok(confirmations) if confirmations > 0)
+        {
+            continue;
+        }
+
+        tracing::info!(
+            "Liquidation transaction {} was superseded by {}",
+            liquidation_txid,
+            txid
+        );
+        db.do_in_transaction(|conn| {
+            queries::record_liquidation_broadcast(conn, loan_txid, fee_level, txid, blockcount)
+        })
+        .await?;
+
+        return Ok(());
+    }
+
+    tracing::info!(
+        "Collateral of loan {} was reclaimed by the borrower",
+        loan_txid
+    );
+    db.do_in_transaction(|conn| {
+        queries::update_liquidation_status(conn, loan_txid, LiquidationStatus::Repaid, None)
+    })
+    .await?;
+    webhooks.notify(Event::LoanRepaid { loan_txid });
+
+    Ok(())
+}
+
+pub(crate) fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system time to be after the Unix epoch")
+        .as_secs()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -403,7 +1758,7 @@ mod tests {
         fixed_rate,
     };
     use anyhow::{Context, Result};
-    use baru::swap::sign_with_key;
+    use baru::{loan::Borrower0, swap::sign_with_key};
     use elements::{
         bitcoin::{secp256k1::Secp256k1, Amount, Network, PrivateKey, PublicKey},
         secp256k1_zkp::{rand::thread_rng, SecretKey, SECP256K1},
@@ -415,7 +1770,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_btc_sell_swap_request() {
-        let db = Sqlite::new_ephemeral_db().expect("A ephemeral db");
+        let db = Database::new_ephemeral_db().expect("A ephemeral db");
 
         let tc_client = Cli::default();
         let (client, _container) = {
@@ -478,26 +1833,44 @@ mod tests {
             .unwrap();
         client.generatetoaddress(1, &mining_address).await.unwrap();
 
-        let mut bob = Bobtimus {
-            rng: &mut thread_rng(),
+        let (wallet, wallet_task) =
+            Wallet::new(client.clone(), vec![have_asset_id_alice, have_asset_id_bob]);
+        tokio::spawn(wallet_task.run());
+
+        let bob = Bobtimus {
+            rng: Mutex::new(thread_rng()),
             rate_service,
+            quote_params: QuoteParameters::default(),
+            inventory_limits: InventoryLimits::unlimited(&[have_asset_id_alice, have_asset_id_bob]),
+            lending_params: LendingParameters::default(),
             secp: Secp256k1::new(),
             elementsd: client.clone(),
             btc_asset_id: have_asset_id_alice,
             usdt_asset_id: have_asset_id_bob,
             db,
-            lender_states: HashMap::new(),
+            lender_states: Mutex::new(HashMap::new()),
+            markets: HashMap::new(),
+            quote_key: SecretKey::new(&mut thread_rng()),
+            swap_requests: SwapRequests::default(),
+            wallet,
+            webhooks: Webhooks::default(),
         };
 
         let transaction = bob
-            .handle_create_sell_swap(CreateSwapPayload {
-                alice_inputs: vec![AliceInput {
-                    outpoint: input_alice.0,
-                    blinding_key: fund_blinding_sk_alice,
-                }],
-                address: final_address_alice,
-                amount: redeem_amount_bob.as_sat(),
-            })
+            .handle_create_sell_swap(
+                DEFAULT_PAIR,
+                CreateSwapPayload {
+                    alice_inputs: vec![AliceInput {
+                        outpoint: input_alice.0,
+                        blinding_key: fund_blinding_sk_alice,
+                    }],
+                    address: final_address_alice,
+                    amount: redeem_amount_bob.as_sat(),
+                    fee_rate: None,
+                    quote: None,
+                },
+                None,
+            )
             .await
             .unwrap();
 
@@ -547,7 +1920,7 @@ mod tests {
 
     #[tokio::test]
     async fn test_handle_btc_buy_swap_request() {
-        let db = Sqlite::new_ephemeral_db().expect("A ephemeral db");
+        let db = Database::new_ephemeral_db().expect("A ephemeral db");
 
         let tc_client = Cli::default();
         let (client, _container) = {
@@ -598,26 +1971,44 @@ mod tests {
             _final_blinding_pk_alice,
         ) = make_confidential_address();
 
-        let mut bob = Bobtimus {
-            rng: &mut thread_rng(),
+        let (wallet, wallet_task) =
+            Wallet::new(client.clone(), vec![have_asset_id_bob, have_asset_id_alice]);
+        tokio::spawn(wallet_task.run());
+
+        let bob = Bobtimus {
+            rng: Mutex::new(thread_rng()),
             rate_service,
+            quote_params: QuoteParameters::default(),
+            inventory_limits: InventoryLimits::unlimited(&[have_asset_id_bob, have_asset_id_alice]),
+            lending_params: LendingParameters::default(),
             secp: Secp256k1::new(),
             elementsd: client.clone(),
             btc_asset_id: have_asset_id_bob,
             usdt_asset_id: have_asset_id_alice,
             db,
-            lender_states: HashMap::new(),
+            lender_states: Mutex::new(HashMap::new()),
+            markets: HashMap::new(),
+            quote_key: SecretKey::new(&mut thread_rng()),
+            swap_requests: SwapRequests::default(),
+            wallet,
+            webhooks: Webhooks::default(),
         };
 
         let transaction = bob
-            .handle_create_buy_swap(CreateSwapPayload {
-                alice_inputs: vec![AliceInput {
-                    outpoint: input_alice.0,
-                    blinding_key: fund_blinding_sk_alice,
-                }],
-                address: final_address_alice,
-                amount: redeem_amount_bob.as_satodollar(),
-            })
+            .handle_create_buy_swap(
+                DEFAULT_PAIR,
+                CreateSwapPayload {
+                    alice_inputs: vec![AliceInput {
+                        outpoint: input_alice.0,
+                        blinding_key: fund_blinding_sk_alice,
+                    }],
+                    address: final_address_alice,
+                    amount: redeem_amount_bob.as_satodollar(),
+                    fee_rate: None,
+                    quote: None,
+                },
+                None,
+            )
             .await
             .unwrap();
 
@@ -663,6 +2054,104 @@ mod tests {
         ));
     }
 
+    #[tokio::test]
+    async fn loan_terms_have_principal_of_loan_transaction() {
+        let db = Database::new_ephemeral_db().expect("A ephemeral db");
+
+        let tc_client = Cli::default();
+        let (client, _container) = {
+            let blockchain = Elementsd::new(&tc_client, "0.18.1.9").unwrap();
+
+            (
+                Client::new(blockchain.node_url.clone().into()).unwrap(),
+                blockchain,
+            )
+        };
+        let mining_address = client.get_new_segwit_confidential_address().await.unwrap();
+
+        let btc_asset_id = client.get_bitcoin_asset_id().await.unwrap();
+        let usdt_asset_id = client.issueasset(100_000.0, 0.0, true).await.unwrap().asset;
+
+        let (
+            collateral_address,
+            _collateral_sk,
+            _collateral_pk,
+            collateral_blinding_sk,
+            _collateral_blinding_pk,
+        ) = make_confidential_address();
+        let fund_borrower_txid = client
+            .send_asset_to_address(&collateral_address, Amount::ONE_BTC, Some(btc_asset_id))
+            .await
+            .unwrap();
+        client.generatetoaddress(1, &mining_address).await.unwrap();
+
+        let collateral_input = extract_input(
+            &client
+                .get_raw_transaction(fund_borrower_txid)
+                .await
+                .unwrap(),
+            collateral_address.clone(),
+        )
+        .unwrap();
+
+        let (wallet, wallet_task) = Wallet::new(client.clone(), vec![btc_asset_id, usdt_asset_id]);
+        tokio::spawn(wallet_task.run());
+
+        let bob = Bobtimus {
+            rng: Mutex::new(thread_rng()),
+            rate_service: fixed_rate::Service::new(),
+            quote_params: QuoteParameters::default(),
+            inventory_limits: InventoryLimits::unlimited(&[btc_asset_id, usdt_asset_id]),
+            lending_params: LendingParameters::default(),
+            secp: Secp256k1::new(),
+            elementsd: client.clone(),
+            btc_asset_id,
+            usdt_asset_id,
+            db,
+            lender_states: Mutex::new(HashMap::new()),
+            markets: HashMap::new(),
+            quote_key: SecretKey::new(&mut thread_rng()),
+            swap_requests: SwapRequests::default(),
+            wallet,
+            webhooks: Webhooks::default(),
+        };
+
+        let blockcount = client.get_blockcount().await.unwrap();
+        let borrower = Borrower0::new(
+            &mut thread_rng(),
+            move |_amount, _asset| async move {
+                Ok(vec![Input {
+                    txin: collateral_input.0,
+                    original_txout: collateral_input.1,
+                    blinding_key: collateral_blinding_sk,
+                }])
+            },
+            collateral_address,
+            collateral_blinding_sk,
+            Amount::from_btc(0.5).unwrap(),
+            Amount::from_sat(1),
+            u64::from(blockcount) + 2_000,
+            btc_asset_id,
+            usdt_asset_id,
+        )
+        .await
+        .unwrap();
+
+        let loan_response = bob
+            .handle_loan_request(borrower.loan_request())
+            .await
+            .unwrap();
+        let loan_txid = loan_response.transaction.txid();
+        let borrower = borrower.interpret(SECP256K1, loan_response).unwrap();
+
+        let lender_states = bob.lender_states.lock().unwrap();
+        let terms = &lender_states[&loan_txid].terms;
+        assert_eq!(
+            terms.principal.as_satodollar(),
+            borrower.principal_tx_out_amount.as_sat()
+        );
+    }
+
     fn extract_input(tx: &Transaction, address: Address) -> Result<(OutPoint, TxOut)> {
         let vout = tx
             .output
@@ -704,4 +2193,87 @@ mod tests {
             blinding_pk,
         )
     }
+
+    #[tokio::test]
+    async fn liquidation_which_cannot_be_looked_up_does_not_block_others() {
+        let db = Database::new_ephemeral_db().expect("A ephemeral db");
+
+        let tc_client = Cli::default();
+        let (client, _container) = {
+            let blockchain = Elementsd::new(&tc_client, "0.18.1.9").unwrap();
+
+            (
+                Client::new(blockchain.node_url.clone().into()).unwrap(),
+                blockchain,
+            )
+        };
+        let mining_address = client.get_new_segwit_confidential_address().await.unwrap();
+        let btc_asset_id = client.get_bitcoin_asset_id().await.unwrap();
+
+        // stands in for a liquidation transaction, all that matters is
+        // that the wallet knows it
+        let (address, _, _, _, _) = make_confidential_address();
+        let confirmed_txid = client
+            .send_asset_to_address(&address, Amount::ONE_BTC, Some(btc_asset_id))
+            .await
+            .unwrap();
+        client.generatetoaddress(6, &mining_address).await.unwrap();
+        let confirmed_tx = client.get_raw_transaction(confirmed_txid).await.unwrap();
+        let blockcount = client.get_blockcount().await.unwrap();
+
+        // loans are tracked by txid, so the broken one comes first
+        let broken_loan = "00".repeat(32).parse::<Txid>().unwrap();
+        let healthy_loan = "ff".repeat(32).parse::<Txid>().unwrap();
+        let unknown_txid = "11".repeat(32).parse::<Txid>().unwrap();
+        db.do_in_transaction(|conn| {
+            for (loan_txid, liquidation_txid) in
+                [(broken_loan, unknown_txid), (healthy_loan, confirmed_txid)].iter()
+            {
+                LiquidationForm::new(*loan_txid, &confirmed_tx, 0).insert(conn)?;
+                queries::record_liquidation_broadcast(
+                    conn,
+                    *loan_txid,
+                    0,
+                    *liquidation_txid,
+                    blockcount,
+                )?;
+            }
+
+            Ok(())
+        })
+        .await
+        .unwrap();
+
+        liquidate_loans(&client, db.clone(), &Webhooks::default())
+            .await
+            .unwrap();
+
+        let (broken, healthy) = db
+            .do_in_transaction(|conn| {
+                Ok((
+                    queries::get_liquidation_attempt(conn, broken_loan)?,
+                    queries::get_liquidation_attempt(conn, healthy_loan)?,
+                ))
+            })
+            .await
+            .unwrap();
+        assert_eq!(broken.status, LiquidationStatus::Broadcast);
+        assert_eq!(healthy.status, LiquidationStatus::Confirmed);
+    }
+
+    #[test]
+    fn rate_update_serializes_sequence_alongside_rate() {
+        let rate = Rate {
+            ask: LiquidUsdt::from_str_in_dollar("19313.52").unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar("19213.53").unwrap(),
+        };
+        let rate_update = RateUpdate::first(rate).next(rate);
+
+        let serialized = serde_json::to_string(&rate_update).unwrap();
+
+        assert_eq!(
+            serialized,
+            "{\"sequence\":1,\"ask\":19313.52,\"bid\":19213.53}"
+        )
+    }
 }
@@ -0,0 +1,154 @@
+use crate::{
+    database::TradeDirection, shutdown::Shutdown, AliceInput, Bobtimus, CreateSwapPayload,
+    LatestRate, Rate,
+};
+use anyhow::{bail, Result};
+use elements::{
+    secp256k1_zkp::rand::{CryptoRng, RngCore},
+    Address,
+};
+use serde::Deserialize;
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::time::sleep;
+
+/// How often we check whether our rates crossed the limit of any open
+/// order.
+const MATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long an order rests if Alice does not say otherwise.
+const DEFAULT_ORDER_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// How long an order may rest at most.
+const MAX_ORDER_TTL: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+/// Alice's request to swap once our rate reaches her limit.
+///
+/// Apart from the limit, it is the same request she would make to
+/// create a swap right away.
+#[derive(Debug, Deserialize)]
+pub struct LimitOrderRequest {
+    pub alice_inputs: Vec<AliceInput>,
+    pub address: Address,
+    pub amount: u64,
+    /// The fee rate Alice funded her inputs for, in sat/vbyte.
+    #[serde(default)]
+    pub fee_rate: Option<u64>,
+    /// Worst price of one whole unit of the base asset Alice accepts,
+    /// in the smallest unit of the quote asset.
+    pub limit_rate: u64,
+    /// Seconds until the order expires, defaults to a day.
+    #[serde(default)]
+    pub expires_in: Option<u64>,
+}
+
+impl LimitOrderRequest {
+    /// Seconds for which the order rests.
+    pub fn lifetime(&self) -> Result<u64> {
+        if self.limit_rate == 0 {
+            bail!(InvalidLimitOrder("`limit_rate` must be positive"))
+        }
+
+        match self.expires_in {
+            None => Ok(DEFAULT_ORDER_TTL.as_secs()),
+            Some(0) => bail!(InvalidLimitOrder("`expires_in` must be positive")),
+            Some(expires_in) if expires_in > MAX_ORDER_TTL.as_secs() => {
+                bail!(InvalidLimitOrder("`expires_in` must be at most a week"))
+            }
+            Some(expires_in) => Ok(expires_in),
+        }
+    }
+
+    /// The swap request we execute once the order is filled.
+    pub fn into_swap_payload(self) -> CreateSwapPayload {
+        CreateSwapPayload {
+            alice_inputs: self.alice_inputs,
+            address: self.address,
+            amount: self.amount,
+            fee_rate: self.fee_rate,
+            quote: None,
+        }
+    }
+}
+
+/// Whether our `rate` is at least as good for Alice as the
+/// `limit_rate` of her order in the `direction`.
+pub fn crosses(direction: TradeDirection, limit_rate: u64, rate: Rate) -> bool {
+    match direction {
+        TradeDirection::Buy => rate.ask.as_satodollar() <= limit_rate,
+        TradeDirection::Sell => rate.bid.as_satodollar() >= limit_rate,
+    }
+}
+
+/// Periodically fill the limit orders which our rates crossed, until
+/// the `shutdown` is triggered.
+pub async fn run_order_matcher<R, RS>(bobtimus: Arc<Bobtimus<R, RS>>, shutdown: Shutdown)
+where
+    R: RngCore + CryptoRng,
+    RS: LatestRate,
+{
+    loop {
+        let result = bobtimus.fill_limit_orders().await;
+        if let Err(e) = result {
+            tracing::error!("failed to match limit orders: {:#}", e);
+        }
+
+        tokio::select! {
+            _ = sleep(MATCH_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => return,
+        }
+    }
+}
+
+/// The limit order is malformed.
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidLimitOrder(&'static str);
+
+impl fmt::Display for InvalidLimitOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid limit order: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidLimitOrder {}
+
+#[derive(Debug, Clone)]
+pub struct UnknownLimitOrder(pub String);
+
+impl fmt::Display for UnknownLimitOrder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown limit order {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownLimitOrder {}
+
+/// The limit order was already filled, cancelled or expired.
+#[derive(Debug, Clone)]
+pub struct LimitOrderClosed(pub String);
+
+impl fmt::Display for LimitOrderClosed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "limit order {} is no longer open", self.0)
+    }
+}
+
+impl std::error::Error for LimitOrderClosed {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::LiquidUsdt;
+
+    #[test]
+    fn limit_is_crossed_once_our_rate_is_as_good() {
+        let rate = Rate {
+            ask: LiquidUsdt::from_satodollar(40_100),
+            bid: LiquidUsdt::from_satodollar(39_900),
+        };
+
+        assert!(crosses(TradeDirection::Buy, 40_100, rate));
+        assert!(!crosses(TradeDirection::Buy, 40_000, rate));
+        assert!(crosses(TradeDirection::Sell, 39_900, rate));
+        assert!(!crosses(TradeDirection::Sell, 40_000, rate));
+    }
+}
@@ -0,0 +1,178 @@
+use crate::{
+    database::{queries, Database, LiquidationStatus, LoanBookEntry},
+    elements_rpc::Client,
+    fee_levels,
+    shutdown::Shutdown,
+    webhooks::{Event, Webhooks},
+};
+use anyhow::{Context, Result};
+use elements::{bitcoin::Amount, AssetId, Txid};
+use std::time::Duration;
+use tokio::time::sleep;
+
+/// How often we check whether the collateral of open loans was spent.
+const WATCH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What spent the collateral of a loan.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Spend {
+    /// One of our liquidation transactions, at the given fee level.
+    Liquidation { fee_level: usize, txid: Txid },
+    /// The borrower's repayment.
+    Repayment,
+    /// A transaction we cannot account for.
+    Unknown,
+}
+
+/// All loans we made, newest first.
+pub async fn loans(db: &Database) -> Result<Vec<LoanBookEntry>> {
+    db.do_in_transaction(queries::get_loan_book)
+        .await
+        .context("failed to load loan book")
+}
+
+/// Periodically check whether the collateral of any open loan was
+/// spent and update the loan book accordingly, until the `shutdown` is
+/// triggered.
+///
+/// Unlike the liquidation engine, this also notices loans which are
+/// repaid before their timelock expires.
+pub async fn run_loan_watcher(
+    elementsd: Client,
+    db: Database,
+    usdt_asset_id: AssetId,
+    webhooks: Webhooks,
+    shutdown: Shutdown,
+) {
+    loop {
+        if let Err(e) = watch_loans(&elementsd, &db, usdt_asset_id, &webhooks).await {
+            tracing::error!("failed to watch loans: {:#}", e);
+        }
+
+        tokio::select! {
+            _ = sleep(WATCH_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => return,
+        }
+    }
+}
+
+async fn watch_loans(
+    elementsd: &Client,
+    db: &Database,
+    usdt_asset_id: AssetId,
+    webhooks: &Webhooks,
+) -> Result<()> {
+    let open_loans = loans(db)
+        .await?
+        .into_iter()
+        .filter(|loan| loan.status == LiquidationStatus::Pending);
+
+    for loan in open_loans {
+        let loan_txid = loan.loan_txid;
+        let liquidation_tx = db
+            .do_in_transaction(|conn| queries::get_liquidation_tx(conn, loan_txid))
+            .await?;
+        let collateral = liquidation_tx
+            .input
+            .first()
+            .context("liquidation transaction without inputs")?
+            .previous_output;
+
+        if elementsd.is_unspent(collateral).await? {
+            continue;
+        }
+
+        match classify_spend(elementsd, db, &loan, usdt_asset_id).await? {
+            Spend::Liquidation { fee_level, txid } => {
+                tracing::info!(
+                    "Collateral of loan {} was spent by our liquidation transaction {}",
+                    loan_txid,
+                    txid
+                );
+                let blockcount = elementsd.get_blockcount().await?;
+                db.do_in_transaction(|conn| {
+                    queries::record_liquidation_broadcast(
+                        conn, loan_txid, fee_level, txid, blockcount,
+                    )
+                })
+                .await?;
+            }
+            Spend::Repayment => {
+                tracing::info!("Loan {} was repaid", loan_txid);
+                db.do_in_transaction(|conn| {
+                    queries::update_liquidation_status(
+                        conn,
+                        loan_txid,
+                        LiquidationStatus::Repaid,
+                        None,
+                    )
+                })
+                .await?;
+                webhooks.notify(Event::LoanRepaid { loan_txid });
+            }
+            Spend::Unknown => {
+                tracing::warn!(
+                    "Collateral of loan {} was spent by an unknown transaction",
+                    loan_txid
+                );
+                db.do_in_transaction(|conn| {
+                    queries::update_liquidation_status(
+                        conn,
+                        loan_txid,
+                        LiquidationStatus::Unknown,
+                        None,
+                    )
+                })
+                .await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Find out what spent the collateral of the `loan`.
+///
+/// Our liquidation transactions pay to our wallet, so elementsd knows
+/// about them once they are in the mempool. A repayment is recognised
+/// by the principal arriving at the repayment address.
+/// Loans made before we recorded their repayment address are assumed
+/// to be repaid.
+async fn classify_spend(
+    elementsd: &Client,
+    db: &Database,
+    loan: &LoanBookEntry,
+    usdt_asset_id: AssetId,
+) -> Result<Spend> {
+    let loan_txid = loan.loan_txid;
+    let first = db
+        .do_in_transaction(|conn| queries::get_liquidation_tx(conn, loan_txid))
+        .await?;
+    let txs = fee_levels(db, loan_txid, first).await?;
+
+    for (fee_level, tx) in txs.iter().enumerate() {
+        let txid = tx.txid();
+        if matches!(elementsd.get_confirmations(txid).await, Ok(confirmations) if confirmations >= 0)
+        {
+            return Ok(Spend::Liquidation { fee_level, txid });
+        }
+    }
+
+    let repayment_address = match &loan.repayment_address {
+        Some(repayment_address) => repayment_address,
+        None => return Ok(Spend::Repayment),
+    };
+    let received = elementsd
+        .list_received_by_address(repayment_address)
+        .await?
+        .iter()
+        .filter_map(|response| response.amount.get(&usdt_asset_id.to_string()))
+        .sum::<f64>();
+    let received = Amount::from_btc(received)?;
+
+    if received.as_sat() >= loan.principal {
+        Ok(Spend::Repayment)
+    } else {
+        Ok(Spend::Unknown)
+    }
+}
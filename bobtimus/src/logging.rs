@@ -0,0 +1,26 @@
+use anyhow::{anyhow, bail, Result};
+use tracing_subscriber::EnvFilter;
+
+/// Environment variable which selects the log format, either `text`
+/// or `json`.
+const LOG_FORMAT_VAR: &str = "BOBTIMUS_LOG_FORMAT";
+
+/// Log to stdout.
+///
+/// What is logged can be filtered through `RUST_LOG`, e.g.
+/// `RUST_LOG=bobtimus=debug,warp=info`, and defaults to everything at
+/// info level and above. With `BOBTIMUS_LOG_FORMAT=json`, every event
+/// is logged as a JSON object which includes the spans it was
+/// recorded in, e.g. the HTTP request it belongs to.
+pub fn init() -> Result<()> {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+
+    let result = match std::env::var(LOG_FORMAT_VAR).as_deref() {
+        Err(_) | Ok("text") => builder.try_init(),
+        Ok("json") => builder.json().try_init(),
+        Ok(other) => bail!("unknown log format {}, expected text or json", other),
+    };
+
+    result.map_err(|e| anyhow!("failed to initialize logging: {}", e))
+}
@@ -0,0 +1,248 @@
+use crate::{
+    fixed_rate,
+    inventory::{AssetLimits, InventoryLimits},
+    kraken,
+    quote::QuoteParameters,
+    shutdown::Shutdown,
+    LatestRate, LiquidUsdt, Rate,
+};
+use anyhow::{bail, Context, Result};
+use elements::{
+    bitcoin::{Amount, Denomination},
+    AssetId,
+};
+use std::{fmt, str::FromStr};
+
+/// The pair we have always been making a market in.
+///
+/// Its assets, rate source and quote parameters are configured
+/// directly on [`crate::Bobtimus`].
+pub const DEFAULT_PAIR: &str = "lbtc-lusdt";
+
+/// An additional pair of Liquid assets we make a market in.
+///
+/// Rates are expressed as the amount of `quote` asset we want for
+/// one unit of `base` asset.
+pub struct Market {
+    pub base_asset_id: AssetId,
+    pub quote_asset_id: AssetId,
+    pub rate_service: Box<dyn LatestRate + Send + Sync>,
+    pub quote_params: QuoteParameters,
+    pub inventory_limits: InventoryLimits,
+}
+
+/// Where the rate of a pair comes from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RateSource {
+    /// Follow the ticker of the given Kraken pair, e.g. `XBT/EUR`.
+    Kraken(String),
+    /// Always quote the same rate.
+    Fixed(Rate),
+}
+
+/// Configuration of an additional market, as passed on the command
+/// line.
+///
+/// The format is
+/// `<name>,<base-asset-id>,<quote-asset-id>,<rate-source>,<spread-bps>,<base-limits>,<quote-limits>[,<quote-size>]`,
+/// where the rate source is either `kraken:<PAIR>` or
+/// `fixed:<ask>:<bid>`. The inventory limits of each asset and the
+/// bounds of the swap size are `<min>:<max>` in whole units of the
+/// asset, either of which may be left empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PairConfig {
+    pub name: String,
+    pub base_asset_id: AssetId,
+    pub quote_asset_id: AssetId,
+    pub rate_source: RateSource,
+    pub spread_bps: u16,
+    pub base_limits: AssetLimits,
+    pub quote_limits: AssetLimits,
+    /// Bounds of the swap size, in the quote asset.
+    pub quote_size: AssetLimits,
+}
+
+impl PairConfig {
+    /// Start following the rate source of the pair, until the
+    /// `shutdown` is triggered.
+    pub async fn into_market(self, shutdown: Shutdown) -> Result<(String, Market)> {
+        let rate_service: Box<dyn LatestRate + Send + Sync> = match self.rate_source {
+            RateSource::Kraken(pair) => {
+                Box::new(kraken::RateService::for_pair(&pair, shutdown).await?)
+            }
+            RateSource::Fixed(rate) => Box::new(fixed_rate::Service::with_rate(rate)),
+        };
+
+        let market = Market {
+            base_asset_id: self.base_asset_id,
+            quote_asset_id: self.quote_asset_id,
+            rate_service,
+            quote_params: QuoteParameters {
+                spread_bps: self.spread_bps,
+                min_quote: self.quote_size.min.unwrap_or(Amount::ZERO),
+                max_quote: self.quote_size.max,
+                ..QuoteParameters::default()
+            },
+            inventory_limits: InventoryLimits::default()
+                .with(self.base_asset_id, self.base_limits)
+                .with(self.quote_asset_id, self.quote_limits),
+        };
+
+        Ok((self.name, market))
+    }
+}
+
+impl FromStr for PairConfig {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let fields = s.split(',').collect::<Vec<_>>();
+        if fields.len() != 7 && fields.len() != 8 {
+            bail!("expected <name>,<base>,<quote>,<rate-source>,<spread-bps>,<base-limits>,<quote-limits>[,<quote-size>]")
+        }
+        let (name, base, quote, rate_source, spread_bps) =
+            (fields[0], fields[1], fields[2], fields[3], fields[4]);
+        let (base_limits, quote_limits) = (fields[5], fields[6]);
+        let quote_size = fields.get(7).copied().unwrap_or(":");
+
+        if name == DEFAULT_PAIR {
+            bail!("{} is configured separately", DEFAULT_PAIR)
+        }
+
+        let rate_source = match rate_source.split(':').collect::<Vec<_>>().as_slice() {
+            ["kraken", pair] => RateSource::Kraken((*pair).to_owned()),
+            ["fixed", ask, bid] => RateSource::Fixed(Rate {
+                ask: LiquidUsdt::from_str_in_dollar(ask)?,
+                bid: LiquidUsdt::from_str_in_dollar(bid)?,
+            }),
+            _ => bail!("unknown rate source {}", rate_source),
+        };
+
+        Ok(Self {
+            name: name.to_owned(),
+            base_asset_id: AssetId::from_str(base).context("invalid base asset id")?,
+            quote_asset_id: AssetId::from_str(quote).context("invalid quote asset id")?,
+            rate_source,
+            spread_bps: spread_bps.parse().context("invalid spread")?,
+            base_limits: parse_limits(base_limits).context("invalid base asset limits")?,
+            quote_limits: parse_limits(quote_limits).context("invalid quote asset limits")?,
+            quote_size: parse_limits(quote_size).context("invalid swap size")?,
+        })
+    }
+}
+
+/// Parse `<min>:<max>` in whole units of an asset, either of which
+/// may be empty.
+fn parse_limits(s: &str) -> Result<AssetLimits> {
+    let parse = |amount: &str| match amount {
+        "" => Ok(None),
+        amount => Amount::from_str_in(amount, Denomination::Bitcoin).map(Some),
+    };
+
+    match s.split(':').collect::<Vec<_>>().as_slice() {
+        [min, max] => Ok(AssetLimits {
+            min: parse(min)?,
+            max: parse(max)?,
+        }),
+        _ => bail!("expected <min>:<max>"),
+    }
+}
+
+/// We do not make a market in the requested pair.
+#[derive(Debug, Clone)]
+pub struct UnknownPair(pub String);
+
+impl fmt::Display for UnknownPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown pair {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownPair {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASSET_ID: &str = "ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2";
+
+    #[test]
+    fn parse_pair_with_kraken_rate() {
+        let config = format!("lbtc-leur,{},{},kraken:XBT/EUR,25,:,:", ASSET_ID, ASSET_ID)
+            .parse::<PairConfig>()
+            .unwrap();
+
+        assert_eq!(config.name, "lbtc-leur");
+        assert_eq!(config.rate_source, RateSource::Kraken("XBT/EUR".to_owned()));
+        assert_eq!(config.spread_bps, 25);
+    }
+
+    #[test]
+    fn parse_pair_with_fixed_rate() {
+        let config = format!(
+            "lbtc-leur,{},{},fixed:30000:29000,0,:,:",
+            ASSET_ID, ASSET_ID
+        )
+        .parse::<PairConfig>()
+        .unwrap();
+
+        assert_eq!(
+            config.rate_source,
+            RateSource::Fixed(Rate {
+                ask: LiquidUsdt::from_str_in_dollar("30000").unwrap(),
+                bid: LiquidUsdt::from_str_in_dollar("29000").unwrap(),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_pair_with_limits() {
+        let config = format!(
+            "lbtc-leur,{},{},kraken:XBT/EUR,25,1:10,:500000,100:",
+            ASSET_ID, ASSET_ID
+        )
+        .parse::<PairConfig>()
+        .unwrap();
+
+        assert_eq!(
+            config.base_limits,
+            AssetLimits {
+                min: Some(Amount::ONE_BTC),
+                max: Some(Amount::from_btc(10.0).unwrap()),
+            }
+        );
+        assert_eq!(
+            config.quote_limits,
+            AssetLimits {
+                min: None,
+                max: Some(Amount::from_btc(500_000.0).unwrap()),
+            }
+        );
+        assert_eq!(
+            config.quote_size,
+            AssetLimits {
+                min: Some(Amount::from_btc(100.0).unwrap()),
+                max: None,
+            }
+        );
+    }
+
+    #[test]
+    fn pair_without_limits_is_rejected() {
+        let result =
+            format!("lbtc-leur,{},{},kraken:XBT/EUR,25", ASSET_ID, ASSET_ID).parse::<PairConfig>();
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn default_pair_cannot_be_reconfigured() {
+        let result = format!(
+            "{},{},{},kraken:XBT/USD,0,:,:",
+            DEFAULT_PAIR, ASSET_ID, ASSET_ID
+        )
+        .parse::<PairConfig>();
+
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use crate::{
+    database::{queries, Database},
+    wallet::WalletSnapshot,
+};
+use anyhow::{Context, Result};
+use conquer_once::Lazy;
+use elements::AssetId;
+use prometheus::{
+    core::Collector, Encoder, GaugeVec, Histogram, HistogramOpts, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+static REGISTRY: Lazy<Registry> = Lazy::new(Registry::new);
+
+pub static TRADES: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new("bobtimus_trades_total", "Number of swaps we created."),
+            &["pair", "direction"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+pub static TRADE_VOLUME: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "bobtimus_trade_volume_total",
+                "Amount we gave away in swaps, in the smallest unit of the asset.",
+            ),
+            &["asset"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+pub static QUOTE_DURATION: Lazy<Histogram> = Lazy::new(|| {
+    register(
+        Histogram::with_opts(HistogramOpts::new(
+            "bobtimus_quote_duration_seconds",
+            "Time it takes us to answer a swap request.",
+        ))
+        .expect("valid metric"),
+    )
+});
+
+pub static INVENTORY_DRIFT: Lazy<IntGaugeVec> = Lazy::new(|| {
+    register(
+        IntGaugeVec::new(
+            Opts::new(
+                "bobtimus_inventory_drift",
+                "Balance per asset below (-1), within (0) or above (1) the rebalance thresholds.",
+            ),
+            &["asset"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+pub static UNDERCOLLATERALIZED_LOANS: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "bobtimus_undercollateralized_loans",
+            "Number of open loans which crossed their liquidation threshold.",
+        )
+        .expect("valid metric"),
+    )
+});
+
+pub static TASK_RESTARTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register(
+        IntCounterVec::new(
+            Opts::new(
+                "bobtimus_task_restarts_total",
+                "Number of times a background task had to be restarted.",
+            ),
+            &["task"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static WALLET_BALANCE: Lazy<GaugeVec> = Lazy::new(|| {
+    register(
+        GaugeVec::new(
+            Opts::new(
+                "bobtimus_wallet_balance",
+                "Balance of our wallet per asset.",
+            ),
+            &["asset"],
+        )
+        .expect("valid metric"),
+    )
+});
+
+static OPEN_LOANS: Lazy<IntGauge> = Lazy::new(|| {
+    register(
+        IntGauge::new(
+            "bobtimus_open_loans",
+            "Number of loans which have been neither repaid nor liquidated.",
+        )
+        .expect("valid metric"),
+    )
+});
+
+fn register<M>(metric: M) -> M
+where
+    M: Collector + Clone + 'static,
+{
+    REGISTRY
+        .register(Box::new(metric.clone()))
+        .expect("metric to be registered only once");
+
+    metric
+}
+
+/// Render all metrics in the Prometheus text format.
+///
+/// Wallet balances are taken from the `wallet` snapshot and open
+/// loans are looked up at the time of the call, all other metrics are
+/// recorded as things happen.
+pub async fn export(
+    wallet: &WalletSnapshot,
+    db: &Database,
+    btc_asset_id: AssetId,
+    usdt_asset_id: AssetId,
+) -> Result<String> {
+    for (label, asset_id) in [("L-BTC", btc_asset_id), ("L-USDt", usdt_asset_id)].iter() {
+        let balance = wallet
+            .balance(*asset_id)
+            .with_context(|| format!("failed to get {} balance", label))?;
+        WALLET_BALANCE
+            .with_label_values(&[label])
+            .set(balance.as_btc());
+    }
+
+    let open_loans = db.do_in_transaction(queries::count_open_loans).await?;
+    OPEN_LOANS.set(open_loans);
+
+    // make sure that all metrics show up, even before they are first
+    // recorded
+    let _ = (
+        &*TRADES,
+        &*TRADE_VOLUME,
+        &*QUOTE_DURATION,
+        &*INVENTORY_DRIFT,
+        &*UNDERCOLLATERALIZED_LOANS,
+        &*TASK_RESTARTS,
+    );
+
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&REGISTRY.gather(), &mut buffer)
+        .context("failed to encode metrics")?;
+
+    Ok(String::from_utf8(buffer)?)
+}
@@ -0,0 +1,154 @@
+use crate::{shutdown::Shutdown, unix_timestamp, LatestRate, LiquidUsdt, Rate, StaleRate};
+use anyhow::{bail, Result};
+use bitcoin_hashes::{sha256, Hash};
+use elements::secp256k1_zkp::{Message, PublicKey, SecretKey, SECP256K1};
+use serde::Serialize;
+use std::{sync::Arc, time::Duration};
+use tokio::{sync::watch, time::sleep};
+
+/// How often we attest to the L-BTC price.
+const ATTESTATION_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Attestations older than this are not handed out, because the rate
+/// feed they are based on evidently stopped.
+const MAX_ATTESTATION_AGE: Duration = Duration::from_secs(3 * 60);
+
+/// A signed statement of the L-BTC price at some point in time.
+///
+/// The signed `message` is the price followed by the timestamp, both
+/// as 8-byte little-endian integers, which is how a loan covenant
+/// reads them off the stack. The `signature` is the DER-encoded ECDSA
+/// signature of the SHA256 of the `message`, as verified by
+/// `OP_CHECKSIGFROMSTACK`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Attestation {
+    /// Mid-market price of one L-BTC, in satodollar.
+    pub price: u64,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Hex-encoded.
+    pub message: String,
+    /// Hex-encoded.
+    pub signature: String,
+    /// Hex-encoded.
+    pub public_key: String,
+}
+
+/// Signs attestations of the L-BTC price with a key which covenants
+/// of oracle-enabled loans commit to.
+#[derive(Clone)]
+pub struct Oracle {
+    key: SecretKey,
+    sender: Arc<watch::Sender<Option<Attestation>>>,
+}
+
+impl Oracle {
+    pub fn new(key: SecretKey) -> (Self, AttestationFeed) {
+        let (sender, receiver) = watch::channel(None);
+
+        let oracle = Self {
+            key,
+            sender: Arc::new(sender),
+        };
+
+        (oracle, AttestationFeed(receiver))
+    }
+
+    pub fn public_key(&self) -> PublicKey {
+        PublicKey::from_secret_key(SECP256K1, &self.key)
+    }
+
+    pub fn attest(&self, price: LiquidUsdt, timestamp: u64) -> Attestation {
+        let price = price.as_satodollar();
+
+        let mut message = Vec::with_capacity(16);
+        message.extend_from_slice(&price.to_le_bytes());
+        message.extend_from_slice(&timestamp.to_le_bytes());
+
+        let signature = SECP256K1.sign(&digest(&message), &self.key);
+
+        Attestation {
+            price,
+            timestamp,
+            message: hex::encode(message),
+            signature: hex::encode(signature.serialize_der()),
+            public_key: self.public_key().to_string(),
+        }
+    }
+}
+
+/// The latest attestation of the [`Oracle`].
+#[derive(Clone)]
+pub struct AttestationFeed(watch::Receiver<Option<Attestation>>);
+
+impl AttestationFeed {
+    /// The latest attestation, or [`StaleRate`] if there is none which
+    /// is recent enough.
+    pub fn latest(&self) -> Result<Attestation> {
+        match &*self.0.borrow() {
+            Some(attestation)
+                if unix_timestamp().saturating_sub(attestation.timestamp)
+                    <= MAX_ATTESTATION_AGE.as_secs() =>
+            {
+                Ok(attestation.clone())
+            }
+            _ => bail!(StaleRate),
+        }
+    }
+}
+
+/// Periodically attest to the mid-market price of the `rate_service`
+/// until the `shutdown` is triggered.
+pub async fn run_oracle<RS>(oracle: Oracle, rate_service: RS, shutdown: Shutdown)
+where
+    RS: LatestRate,
+{
+    tracing::info!("Attesting to prices with key {}", oracle.public_key());
+
+    loop {
+        match rate_service.latest_rate() {
+            Ok(rate) => {
+                let attestation = oracle.attest(mid_price(rate), unix_timestamp());
+                let _ = oracle.sender.send(Some(attestation));
+            }
+            Err(e) => tracing::warn!("cannot attest to price: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = sleep(ATTESTATION_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => return,
+        }
+    }
+}
+
+fn mid_price(rate: Rate) -> LiquidUsdt {
+    let mid = (rate.ask.as_satodollar() as u128 + rate.bid.as_satodollar() as u128) / 2;
+
+    LiquidUsdt::from_satodollar(mid as u64)
+}
+
+fn digest(message: &[u8]) -> Message {
+    let hash = sha256::Hash::hash(message);
+    Message::from_slice(&hash.into_inner()).expect("SHA256 hash is a valid message")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::secp256k1_zkp::{rand::thread_rng, Signature};
+
+    #[test]
+    fn attestation_signs_price_and_timestamp() {
+        let (oracle, _) = Oracle::new(SecretKey::new(&mut thread_rng()));
+
+        let attestation = oracle.attest(LiquidUsdt::from_satodollar(0x0102), 0x0a0b);
+
+        assert_eq!(attestation.message, "02010000000000000b0a000000000000");
+
+        let message = hex::decode(&attestation.message).unwrap();
+        let signature = Signature::from_der(&hex::decode(&attestation.signature).unwrap()).unwrap();
+        assert!(SECP256K1
+            .verify(&digest(&message), &signature, &oracle.public_key())
+            .is_ok());
+    }
+}
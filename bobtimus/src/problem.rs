@@ -1,3 +1,17 @@
+use crate::{
+    fee::{DustAmount, UnacceptableFeeRate, UneconomicSwap},
+    idempotency::{IdempotencyKeyReused, SwapInProgress},
+    inventory::{InventoryLimitExceeded, NoInventoryLimits},
+    lending::{PrincipalTooLarge, UnacceptableTimelock},
+    limit_order::{InvalidLimitOrder, LimitOrderClosed, UnknownLimitOrder},
+    market::UnknownPair,
+    quote::{
+        InvalidQuote, QuoteAlreadyUsed, QuoteExpired, QuoteTooLarge, QuoteTooSmall, UnknownQuote,
+    },
+    rate_history::InvalidRateHistoryQuery,
+    rate_limit::TooManyRequests,
+    StaleRate,
+};
 use baru::swap::{ChangeAmountTooSmall, InputAmountTooSmall, InvalidAssetTypes};
 use http_api_problem::HttpApiProblem;
 use std::error::Error;
@@ -24,6 +38,77 @@ pub fn from_anyhow(e: anyhow::Error) -> HttpApiProblem {
             HttpApiProblem::new("Change amount too small to cover fee.")
                 .set_status(StatusCode::BAD_REQUEST)
         }
+        e if e.is::<QuoteTooSmall>() => HttpApiProblem::new("Swap amount too small.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<DustAmount>() => HttpApiProblem::new("Swap amount is dust.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<UneconomicSwap>() => HttpApiProblem::new("Swap amount too small for fee.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<QuoteTooLarge>() => HttpApiProblem::new("Swap amount too large.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<InvalidQuote>() => {
+            HttpApiProblem::new("Invalid quote.").set_status(StatusCode::BAD_REQUEST)
+        }
+        e if e.is::<QuoteExpired>() => HttpApiProblem::new("Quote expired.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<QuoteAlreadyUsed>() => {
+            HttpApiProblem::new("Quote was already used.").set_status(StatusCode::CONFLICT)
+        }
+        e if e.is::<UnknownQuote>() => HttpApiProblem::new("Unknown quote.")
+            .set_status(StatusCode::NOT_FOUND)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<InvalidLimitOrder>() => HttpApiProblem::new("Invalid limit order.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<UnknownLimitOrder>() => HttpApiProblem::new("Unknown limit order.")
+            .set_status(StatusCode::NOT_FOUND)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<LimitOrderClosed>() => {
+            HttpApiProblem::new("Limit order is no longer open.").set_status(StatusCode::CONFLICT)
+        }
+        e if e.is::<InventoryLimitExceeded>() => {
+            HttpApiProblem::new("Swap exceeds our inventory limits.")
+                .set_status(StatusCode::BAD_REQUEST)
+                .set_detail(format!("{:#}", e))
+        }
+        e if e.is::<NoInventoryLimits>() => HttpApiProblem::new("Asset is not traded.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<UnacceptableTimelock>() => HttpApiProblem::new("Unacceptable loan duration.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<PrincipalTooLarge>() => HttpApiProblem::new("Loan amount too large.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<UnknownPair>() => HttpApiProblem::new("Unknown trading pair.")
+            .set_status(StatusCode::NOT_FOUND)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<UnacceptableFeeRate>() => HttpApiProblem::new("Unacceptable fee rate.")
+            .set_status(StatusCode::BAD_REQUEST)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<InvalidRateHistoryQuery>() => {
+            HttpApiProblem::new("Invalid rate history query.")
+                .set_status(StatusCode::BAD_REQUEST)
+                .set_detail(format!("{:#}", e))
+        }
+        e if e.is::<TooManyRequests>() => HttpApiProblem::new("Too many requests.")
+            .set_status(StatusCode::TOO_MANY_REQUESTS)
+            .set_detail(format!("{:#}", e)),
+        e if e.is::<IdempotencyKeyReused>() => {
+            HttpApiProblem::new("Idempotency key was used for a different request.")
+                .set_status(StatusCode::UNPROCESSABLE_ENTITY)
+        }
+        e if e.is::<SwapInProgress>() => {
+            HttpApiProblem::new("Swap for the same request is in progress.")
+                .set_status(StatusCode::CONFLICT)
+        }
+        e if e.is::<StaleRate>() => HttpApiProblem::new("No up-to-date rate available.")
+            .set_status(StatusCode::SERVICE_UNAVAILABLE),
         e => {
             tracing::error!("unhandled error: {:#}", e);
 
@@ -0,0 +1,403 @@
+use crate::{database::TradeDirection, LiquidUsdt, Rate};
+use anyhow::{bail, Result};
+use bitcoin_hashes::{sha256, Hash};
+use elements::{
+    bitcoin::Amount,
+    secp256k1_zkp::{Message, PublicKey, SecretKey, Signature, SECP256K1},
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, time::Duration};
+
+const BASIS_POINTS: u128 = 10_000;
+
+/// How long a quote can be used to create a swap after we handed it
+/// out.
+pub const QUOTE_TTL: Duration = Duration::from_secs(30);
+
+/// How we turn the market rate into the rate we are willing to trade
+/// at.
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteParameters {
+    /// Spread applied to each side of the market rate, in basis
+    /// points.
+    ///
+    /// The ask is increased and the bid is decreased by this amount.
+    pub spread_bps: u16,
+    /// The smallest swap we are willing to quote, in the smallest
+    /// unit of the quote asset.
+    pub min_quote: Amount,
+    /// The biggest swap we are willing to quote, in the smallest unit
+    /// of the quote asset.
+    pub max_quote: Option<Amount>,
+    /// Quoted prices are rounded to a multiple of this amount, always
+    /// in our favour.
+    pub rounding: LiquidUsdt,
+}
+
+impl Default for QuoteParameters {
+    fn default() -> Self {
+        Self {
+            spread_bps: 0,
+            min_quote: Amount::ZERO,
+            max_quote: None,
+            rounding: LiquidUsdt::from_satodollar(1),
+        }
+    }
+}
+
+impl QuoteParameters {
+    /// Apply the spread and rounding to the market `rate`.
+    pub fn apply(&self, rate: Rate) -> Rate {
+        let spread = self.spread_bps as u128;
+        let rounding = self.rounding.as_satodollar().max(1) as u128;
+
+        let ask = rate.ask.as_satodollar() as u128 * (BASIS_POINTS + spread);
+        let ask = div_ceil(div_ceil(ask, BASIS_POINTS), rounding) * rounding;
+
+        let bid = rate.bid.as_satodollar() as u128 * BASIS_POINTS.saturating_sub(spread);
+        let bid = bid / BASIS_POINTS / rounding * rounding;
+
+        Rate {
+            ask: LiquidUsdt::from_satodollar(ask as u64),
+            bid: LiquidUsdt::from_satodollar(bid as u64),
+        }
+    }
+
+    /// Ensure that a swap worth `quote` of the quote asset is neither
+    /// too small nor too big for us to trade.
+    pub fn check_quote_size(&self, quote: Amount) -> Result<()> {
+        if quote < self.min_quote {
+            bail!(QuoteTooSmall {
+                min_quote: self.min_quote,
+            });
+        }
+
+        match self.max_quote {
+            Some(max_quote) if quote > max_quote => {
+                bail!(QuoteTooLarge { max_quote })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The terms of a swap we are willing to trade at, until
+/// `expires_at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuoteTerms {
+    /// Identifies the quote, e.g. when looking up what became of it.
+    pub id: String,
+    pub pair: String,
+    pub direction: TradeDirection,
+    /// Price of one whole unit of the base asset, in the smallest
+    /// unit of the quote asset.
+    pub rate: u64,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+    /// The amount Alice asked to sell.
+    ///
+    /// If we cannot trade that much, the quote is only for the part
+    /// we can fill, which is smaller.
+    pub requested_amount: u64,
+    /// Unix timestamp in seconds after which we no longer honour the
+    /// quote.
+    pub expires_at: u64,
+}
+
+impl QuoteTerms {
+    pub fn sign(self, key: &SecretKey) -> Quote {
+        let signature = SECP256K1.sign(&self.digest(), key);
+
+        Quote {
+            terms: self,
+            signature,
+        }
+    }
+
+    fn digest(&self) -> Message {
+        let direction: u8 = match self.direction {
+            TradeDirection::Buy => 0,
+            TradeDirection::Sell => 1,
+        };
+
+        let mut bytes = Vec::new();
+        for field in [&self.id, &self.pair].iter() {
+            bytes.extend_from_slice(&(field.len() as u64).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes.push(direction);
+        for value in [
+            self.rate,
+            self.base_amount,
+            self.quote_amount,
+            self.requested_amount,
+            self.expires_at,
+        ]
+        .iter()
+        {
+            bytes.extend_from_slice(&value.to_be_bytes());
+        }
+
+        let hash = sha256::Hash::hash(&bytes);
+        Message::from_slice(&hash.into_inner()).expect("SHA256 hash is a valid message")
+    }
+}
+
+/// Terms we signed, so that they can be handed back to us when
+/// creating the swap.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    #[serde(flatten)]
+    pub terms: QuoteTerms,
+    pub signature: Signature,
+}
+
+impl Quote {
+    /// Ensure that we signed the terms of this quote with the key
+    /// belonging to `public_key` and that they have not expired at
+    /// `now`.
+    pub fn verify(&self, public_key: &PublicKey, now: u64) -> Result<&QuoteTerms> {
+        if SECP256K1
+            .verify(&self.terms.digest(), &self.signature, public_key)
+            .is_err()
+        {
+            bail!(InvalidQuote)
+        }
+
+        if now > self.terms.expires_at {
+            bail!(QuoteExpired {
+                expired_at: self.terms.expires_at,
+            })
+        }
+
+        Ok(&self.terms)
+    }
+}
+
+fn div_ceil(dividend: u128, divisor: u128) -> u128 {
+    (dividend + divisor - 1) / divisor
+}
+
+/// The requested swap is smaller than the minimum we quote for.
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteTooSmall {
+    pub min_quote: Amount,
+}
+
+impl fmt::Display for QuoteTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "swap is smaller than the minimum of {} in the smallest unit of the quote asset",
+            self.min_quote.as_sat()
+        )
+    }
+}
+
+impl std::error::Error for QuoteTooSmall {}
+
+/// The requested swap is bigger than the maximum we quote for.
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteTooLarge {
+    pub max_quote: Amount,
+}
+
+impl fmt::Display for QuoteTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "swap is bigger than the maximum of {} in the smallest unit of the quote asset",
+            self.max_quote.as_sat()
+        )
+    }
+}
+
+impl std::error::Error for QuoteTooLarge {}
+
+/// The quote was not signed by us, was altered or does not match the
+/// swap it was handed in with.
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidQuote;
+
+impl fmt::Display for InvalidQuote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "quote is invalid")
+    }
+}
+
+impl std::error::Error for InvalidQuote {}
+
+/// The quote is no longer valid.
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteExpired {
+    pub expired_at: u64,
+}
+
+impl fmt::Display for QuoteExpired {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "quote expired at {}", self.expired_at)
+    }
+}
+
+impl std::error::Error for QuoteExpired {}
+
+/// A swap was already created against the quote.
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteAlreadyUsed;
+
+impl fmt::Display for QuoteAlreadyUsed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "quote was already used")
+    }
+}
+
+impl std::error::Error for QuoteAlreadyUsed {}
+
+/// We never handed out a quote with this ID.
+#[derive(Debug, Clone)]
+pub struct UnknownQuote(pub String);
+
+impl fmt::Display for UnknownQuote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown quote {}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownQuote {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spread_widens_rate_in_our_favour() {
+        let params = QuoteParameters {
+            spread_bps: 50,
+            ..Default::default()
+        };
+        let rate = Rate {
+            ask: LiquidUsdt::from_str_in_dollar("20000").unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar("20000").unwrap(),
+        };
+
+        let quoted = params.apply(rate);
+
+        assert_eq!(quoted.ask, LiquidUsdt::from_str_in_dollar("20100").unwrap());
+        assert_eq!(quoted.bid, LiquidUsdt::from_str_in_dollar("19900").unwrap());
+    }
+
+    #[test]
+    fn rounding_is_in_our_favour() {
+        let params = QuoteParameters {
+            rounding: LiquidUsdt::from_str_in_dollar("0.01").unwrap(),
+            ..Default::default()
+        };
+        let rate = Rate {
+            ask: LiquidUsdt::from_str_in_dollar("19313.524").unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar("19213.526").unwrap(),
+        };
+
+        let quoted = params.apply(rate);
+
+        assert_eq!(
+            quoted.ask,
+            LiquidUsdt::from_str_in_dollar("19313.53").unwrap()
+        );
+        assert_eq!(
+            quoted.bid,
+            LiquidUsdt::from_str_in_dollar("19213.52").unwrap()
+        );
+    }
+
+    #[test]
+    fn default_parameters_quote_market_rate() {
+        let rate = Rate {
+            ask: LiquidUsdt::from_str_in_dollar("19313.52345678").unwrap(),
+            bid: LiquidUsdt::from_str_in_dollar("19213.52345678").unwrap(),
+        };
+
+        let quoted = QuoteParameters::default().apply(rate);
+
+        assert_eq!(quoted, rate);
+    }
+
+    #[test]
+    fn rejects_quote_above_maximum() {
+        let params = QuoteParameters {
+            max_quote: Some(LiquidUsdt::from_str_in_dollar("1000").unwrap().into()),
+            ..Default::default()
+        };
+
+        let error = params
+            .check_quote_size(LiquidUsdt::from_str_in_dollar("1000.01").unwrap().into())
+            .unwrap_err();
+
+        assert!(error.is::<QuoteTooLarge>());
+        assert!(params
+            .check_quote_size(LiquidUsdt::from_str_in_dollar("1000").unwrap().into())
+            .is_ok());
+    }
+
+    #[test]
+    fn verifies_signed_quote_until_expiry() {
+        let key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(SECP256K1, &key);
+        let terms = QuoteTerms {
+            id: "a3c1f0e2".to_owned(),
+            pair: "lbtc-lusdt".to_owned(),
+            direction: TradeDirection::Buy,
+            rate: 20_000 * 100_000_000,
+            base_amount: 5_000_000,
+            quote_amount: 1_000 * 100_000_000,
+            requested_amount: 1_000 * 100_000_000,
+            expires_at: 1_000,
+        };
+
+        let quote = terms.clone().sign(&key);
+
+        assert_eq!(quote.verify(&public_key, 1_000).unwrap(), &terms);
+        assert!(quote
+            .verify(&public_key, 1_001)
+            .unwrap_err()
+            .is::<QuoteExpired>());
+    }
+
+    #[test]
+    fn rejects_altered_quote() {
+        let key = SecretKey::from_slice(&[1; 32]).unwrap();
+        let public_key = PublicKey::from_secret_key(SECP256K1, &key);
+        let mut quote = QuoteTerms {
+            id: "a3c1f0e2".to_owned(),
+            pair: "lbtc-lusdt".to_owned(),
+            direction: TradeDirection::Buy,
+            rate: 20_000 * 100_000_000,
+            base_amount: 5_000_000,
+            quote_amount: 1_000 * 100_000_000,
+            requested_amount: 1_000 * 100_000_000,
+            expires_at: 1_000,
+        }
+        .sign(&key);
+
+        quote.terms.base_amount += 1;
+
+        assert!(quote
+            .verify(&public_key, 0)
+            .unwrap_err()
+            .is::<InvalidQuote>());
+    }
+
+    #[test]
+    fn rejects_quote_below_minimum() {
+        let params = QuoteParameters {
+            min_quote: LiquidUsdt::from_str_in_dollar("10").unwrap().into(),
+            ..Default::default()
+        };
+
+        assert!(params
+            .check_quote_size(LiquidUsdt::from_str_in_dollar("9.99").unwrap().into())
+            .is_err());
+        assert!(params
+            .check_quote_size(LiquidUsdt::from_str_in_dollar("10").unwrap().into())
+            .is_ok());
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    database::{queries, Database, RateForm, RateSample},
+    market::DEFAULT_PAIR,
+    quote::QuoteParameters,
+    shutdown::Shutdown,
+    unix_timestamp, LatestRate,
+};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use std::{fmt, time::Duration};
+use tokio::time::sleep;
+
+/// How often we record the rate we offer.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time range covered by a history query which does not specify one.
+const DEFAULT_RANGE: u64 = 24 * 60 * 60;
+
+/// Maximum number of rates returned by a single history query.
+const MAX_POINTS: u64 = 1000;
+
+/// Periodically record the rate we offer for the default pair, i.e.
+/// the latest rate of the `rate_service` with the `quote_params`
+/// applied, until the `shutdown` is triggered.
+pub async fn run_rate_recorder<RS>(
+    db: Database,
+    rate_service: RS,
+    quote_params: QuoteParameters,
+    shutdown: Shutdown,
+) where
+    RS: LatestRate,
+{
+    loop {
+        match rate_service.latest_rate() {
+            Ok(rate) => {
+                let sample = RateSample {
+                    recorded_at: unix_timestamp(),
+                    pair: DEFAULT_PAIR.to_owned(),
+                    rate: quote_params.apply(rate),
+                };
+                let result = db
+                    .do_in_transaction(|conn| RateForm::new(&sample)?.insert(conn))
+                    .await;
+                if let Err(e) = result {
+                    tracing::error!("failed to record rate: {:#}", e);
+                }
+            }
+            Err(e) => tracing::warn!("cannot record rate: {:#}", e),
+        }
+
+        tokio::select! {
+            _ = sleep(SAMPLE_INTERVAL) => {}
+            _ = shutdown.clone().triggered() => return,
+        }
+    }
+}
+
+/// Which part of the rate history to look up.
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct RateHistoryQuery {
+    /// Unix timestamp of the start of the range, defaults to a day
+    /// before `to`.
+    pub from: Option<u64>,
+    /// Unix timestamp of the end of the range, defaults to now.
+    pub to: Option<u64>,
+    /// Seconds between two returned rates, defaults to the interval
+    /// at which we record them.
+    pub resolution: Option<u64>,
+}
+
+/// The rates we offered for the default pair within the range of the
+/// `query`, oldest first.
+///
+/// Each rate is the last one we recorded within its interval of
+/// `resolution` seconds, and is reported at the start of it.
+pub async fn history(db: &Database, query: RateHistoryQuery, now: u64) -> Result<Vec<RateSample>> {
+    let to = query.to.unwrap_or(now);
+    let from = query
+        .from
+        .unwrap_or_else(|| to.saturating_sub(DEFAULT_RANGE));
+    let resolution = query.resolution.unwrap_or(SAMPLE_INTERVAL.as_secs());
+
+    if from >= to {
+        bail!(InvalidRateHistoryQuery("`from` must be before `to`"))
+    }
+    if resolution == 0 {
+        bail!(InvalidRateHistoryQuery("`resolution` must be positive"))
+    }
+    if (to - from) / resolution > MAX_POINTS {
+        bail!(InvalidRateHistoryQuery(
+            "range spans too many points at this resolution"
+        ))
+    }
+
+    let samples = db
+        .do_in_transaction(|conn| queries::get_rates(conn, DEFAULT_PAIR, from, to))
+        .await
+        .context("failed to load rates")?;
+
+    Ok(downsample(samples, resolution))
+}
+
+/// Keep the last of the `samples` within every interval of
+/// `resolution` seconds.
+///
+/// The `samples` must be ordered oldest first.
+fn downsample(samples: Vec<RateSample>, resolution: u64) -> Vec<RateSample> {
+    let mut downsampled: Vec<RateSample> = Vec::new();
+
+    for mut sample in samples {
+        sample.recorded_at -= sample.recorded_at % resolution;
+
+        match downsampled.last_mut() {
+            Some(last) if last.recorded_at == sample.recorded_at => *last = sample,
+            _ => downsampled.push(sample),
+        }
+    }
+
+    downsampled
+}
+
+/// The rate history query is malformed or too large.
+#[derive(Debug, Clone, Copy)]
+pub struct InvalidRateHistoryQuery(&'static str);
+
+impl fmt::Display for InvalidRateHistoryQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid rate history query: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidRateHistoryQuery {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{LiquidUsdt, Rate};
+
+    #[test]
+    fn keeps_last_rate_per_interval() {
+        let sample = |recorded_at, ask| RateSample {
+            recorded_at,
+            pair: DEFAULT_PAIR.to_owned(),
+            rate: Rate {
+                ask: LiquidUsdt::from_satodollar(ask),
+                bid: LiquidUsdt::from_satodollar(ask - 1),
+            },
+        };
+
+        let downsampled = downsample(
+            vec![
+                sample(60, 10),
+                sample(120, 20),
+                sample(300, 30),
+                sample(360, 40),
+            ],
+            300,
+        );
+
+        assert_eq!(downsampled, vec![sample(0, 20), sample(300, 40)]);
+    }
+}
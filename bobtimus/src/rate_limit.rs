@@ -0,0 +1,94 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// Lets through at most one request per key in every `interval`.
+#[derive(Debug)]
+pub struct RateLimiter<K> {
+    interval: Duration,
+    last_requests: HashMap<K, Instant>,
+}
+
+impl<K> RateLimiter<K>
+where
+    K: Eq + Hash,
+{
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            last_requests: HashMap::new(),
+        }
+    }
+
+    /// Record a request for `key` made at `now`, unless the previous
+    /// request for it was made less than `interval` ago.
+    pub fn try_acquire(&mut self, key: K, now: Instant) -> Result<(), TooManyRequests> {
+        let interval = self.interval;
+        self.last_requests
+            .retain(|_, last_request| now.saturating_duration_since(*last_request) < interval);
+
+        if let Some(last_request) = self.last_requests.get(&key) {
+            return Err(TooManyRequests {
+                retry_after: interval - now.saturating_duration_since(*last_request),
+            });
+        }
+
+        self.last_requests.insert(key, now);
+
+        Ok(())
+    }
+}
+
+/// The same client made another request too soon.
+#[derive(Debug, Clone, Copy)]
+pub struct TooManyRequests {
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for TooManyRequests {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "too many requests, try again in {}s",
+            self.retry_after.as_secs()
+        )
+    }
+}
+
+impl std::error::Error for TooManyRequests {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_second_request_within_interval() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        limiter.try_acquire("alice", now).unwrap();
+        let error = limiter
+            .try_acquire("alice", now + Duration::from_secs(10))
+            .unwrap_err();
+
+        assert_eq!(error.retry_after, Duration::from_secs(50));
+        assert!(limiter
+            .try_acquire("bob", now + Duration::from_secs(10))
+            .is_ok());
+    }
+
+    #[test]
+    fn accepts_request_after_interval() {
+        let mut limiter = RateLimiter::new(Duration::from_secs(60));
+        let now = Instant::now();
+
+        limiter.try_acquire("alice", now).unwrap();
+
+        assert!(limiter
+            .try_acquire("alice", now + Duration::from_secs(60))
+            .is_ok());
+    }
+}
@@ -3,5 +3,96 @@ table! {
         id -> Text,
         tx_hex -> Text,
         locktime -> BigInt,
+        status -> Text,
+        liquidation_txid -> Nullable<Text>,
+        fee_level -> BigInt,
+        attempts -> BigInt,
+        last_attempt_height -> Nullable<BigInt>,
+        last_error -> Nullable<Text>,
     }
 }
+
+table! {
+    liquidation_fee_bumps (loan_id, fee_level) {
+        loan_id -> Text,
+        fee_level -> BigInt,
+        tx_hex -> Text,
+    }
+}
+
+table! {
+    loans (id) {
+        id -> Text,
+        collateral_amount -> BigInt,
+        principal_amount -> BigInt,
+        liquidation_ltv_bps -> BigInt,
+        created_at -> BigInt,
+        repayment_address -> Nullable<Text>,
+    }
+}
+
+table! {
+    limit_orders (id) {
+        id -> Text,
+        created_at -> BigInt,
+        expires_at -> BigInt,
+        pair -> Text,
+        direction -> Text,
+        limit_rate -> BigInt,
+        amount -> BigInt,
+        payload -> Text,
+        status -> Text,
+        txid -> Nullable<Text>,
+        tx_hex -> Nullable<Text>,
+        failure -> Nullable<Text>,
+    }
+}
+
+table! {
+    quotes (id) {
+        id -> Text,
+        created_at -> BigInt,
+        expires_at -> BigInt,
+        pair -> Text,
+        direction -> Text,
+        rate -> BigInt,
+        base_amount -> BigInt,
+        quote_amount -> BigInt,
+        requested_amount -> BigInt,
+        status -> Text,
+        txid -> Nullable<Text>,
+        failure -> Nullable<Text>,
+    }
+}
+
+table! {
+    rates (recorded_at, pair) {
+        recorded_at -> BigInt,
+        pair -> Text,
+        ask -> BigInt,
+        bid -> BigInt,
+    }
+}
+
+table! {
+    trades (txid) {
+        txid -> Text,
+        created_at -> BigInt,
+        pair -> Text,
+        direction -> Text,
+        base_amount -> BigInt,
+        quote_amount -> BigInt,
+        rate -> BigInt,
+        fee -> BigInt,
+    }
+}
+
+table! {
+    inventory (recorded_at, asset) {
+        recorded_at -> BigInt,
+        asset -> Text,
+        balance -> BigInt,
+    }
+}
+
+allow_tables_to_appear_in_same_query!(liquidations, loans);
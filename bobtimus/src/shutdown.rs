@@ -0,0 +1,55 @@
+use anyhow::Result;
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+
+/// Lets the long-running parts of bobtimus find out that we are
+/// shutting down, so that they can stop at a point where no swap or
+/// liquidation is left half-done.
+#[derive(Clone, Debug)]
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    /// Trigger the shutdown once we receive SIGINT or SIGTERM.
+    pub fn on_signal() -> Self {
+        let (sender, receiver) = watch::channel(false);
+
+        tokio::spawn(async move {
+            if let Err(e) = wait_for_signal().await {
+                tracing::error!("failed to listen for shutdown signals: {:#}", e);
+                return;
+            }
+
+            tracing::info!("Shutting down, waiting for in-flight requests to finish");
+            let _ = sender.send(true);
+        });
+
+        Self(receiver)
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// Resolves once the shutdown has been triggered.
+    pub async fn triggered(mut self) {
+        while !*self.0.borrow() {
+            if self.0.changed().await.is_err() {
+                // nobody is left to trigger the shutdown
+                futures::future::pending::<()>().await;
+            }
+        }
+    }
+}
+
+async fn wait_for_signal() -> Result<()> {
+    let mut sigterm = signal(SignalKind::terminate())?;
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        _ = sigterm.recv() => {}
+    }
+
+    Ok(())
+}
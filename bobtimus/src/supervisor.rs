@@ -0,0 +1,116 @@
+use crate::{metrics, shutdown::Shutdown};
+use std::{
+    any::Any,
+    future::Future,
+    time::{Duration, Instant},
+};
+use tokio::{task::JoinHandle, time::sleep};
+
+const INITIAL_RESTART_DELAY: Duration = Duration::from_secs(1);
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// A task which ran for at least this long before it failed gets
+/// restarted right away again, as if it had never failed before.
+const HEALTHY_RUNTIME: Duration = Duration::from_secs(10 * 60);
+
+/// Run the background task produced by `start` until the `shutdown`
+/// is triggered.
+///
+/// The task is expected to run until it notices the `shutdown`
+/// itself. If it panics or stops before that, it is restarted with
+/// an exponential backoff, so that a bug in one component does not
+/// silently disable it for as long as bobtimus is running.
+pub fn supervise<F, Fut>(name: &'static str, shutdown: Shutdown, mut start: F) -> JoinHandle<()>
+where
+    F: FnMut() -> Fut + Send + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut restart_delay = INITIAL_RESTART_DELAY;
+
+        loop {
+            let started_at = Instant::now();
+            let result = tokio::spawn(start()).await;
+
+            if shutdown.is_triggered() {
+                return;
+            }
+            if started_at.elapsed() >= HEALTHY_RUNTIME {
+                restart_delay = INITIAL_RESTART_DELAY;
+            }
+
+            match result {
+                Ok(()) => tracing::error!(
+                    "{} stopped unexpectedly, restarting in {}s",
+                    name,
+                    restart_delay.as_secs()
+                ),
+                Err(e) if e.is_panic() => tracing::error!(
+                    "{} panicked, restarting in {}s: {}",
+                    name,
+                    restart_delay.as_secs(),
+                    panic_message(e.into_panic())
+                ),
+                Err(e) => tracing::error!(
+                    "{} was cancelled, restarting in {}s: {}",
+                    name,
+                    restart_delay.as_secs(),
+                    e
+                ),
+            }
+            metrics::TASK_RESTARTS.with_label_values(&[name]).inc();
+
+            tokio::select! {
+                _ = sleep(restart_delay) => {}
+                _ = shutdown.clone().triggered() => return,
+            }
+            restart_delay = (restart_delay * 2).min(MAX_RESTART_DELAY);
+        }
+    })
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return (*message).to_owned();
+    }
+
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(_) => "unknown panic".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tokio::test]
+    async fn restarts_task_after_panic() {
+        let starts = Arc::new(AtomicUsize::new(0));
+
+        let _handle = supervise("test task", Shutdown::on_signal(), {
+            let starts = starts.clone();
+            move || {
+                let starts = starts.clone();
+                async move {
+                    if starts.fetch_add(1, Ordering::SeqCst) == 0 {
+                        panic!("first start fails");
+                    }
+                    futures::future::pending::<()>().await;
+                }
+            }
+        });
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while starts.load(Ordering::SeqCst) < 2 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("task to be restarted");
+    }
+}
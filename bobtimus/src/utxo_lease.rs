@@ -0,0 +1,82 @@
+use elements::OutPoint;
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+/// How long the inputs of a swap or loan transaction stay reserved
+/// for it.
+///
+/// Alice has to broadcast the transaction within this time, after
+/// which we may spend its inputs in another swap. It matches the time
+/// we hand out the same transaction for repeated requests.
+const LEASE_DURATION: Duration = Duration::from_secs(10 * 60);
+
+/// Wallet UTXOs we put into swap or loan transactions which have not
+/// been broadcast yet.
+///
+/// Leased UTXOs are also locked in elementsd, so that it does not
+/// select them for another transaction until their lease expires.
+/// The leases only live in memory, so the wallet task unlocks
+/// whatever elementsd still has locked without a lease when it starts.
+#[derive(Debug, Default)]
+pub struct UtxoLeases {
+    leases: HashMap<OutPoint, Instant>,
+}
+
+impl UtxoLeases {
+    /// Reserve the `utxos` from `now` on.
+    pub fn lease(&mut self, utxos: &[OutPoint], now: Instant) {
+        for utxo in utxos {
+            self.leases.insert(*utxo, now + LEASE_DURATION);
+        }
+    }
+
+    /// Give up the lease on the `utxos` early, e.g. because the swap
+    /// transaction could not be built after all.
+    pub fn release(&mut self, utxos: &[OutPoint]) {
+        for utxo in utxos {
+            self.leases.remove(utxo);
+        }
+    }
+
+    /// Forget about all leases which expired by `now` and return the
+    /// UTXOs they reserved.
+    pub fn take_expired(&mut self, now: Instant) -> Vec<OutPoint> {
+        let expired = self
+            .leases
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(utxo, _)| *utxo)
+            .collect::<Vec<_>>();
+        self.release(&expired);
+
+        expired
+    }
+
+    pub fn is_leased(&self, utxo: &OutPoint) -> bool {
+        self.leases.contains_key(utxo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use elements::Txid;
+
+    #[test]
+    fn lease_expires_after_lease_duration() {
+        let mut leases = UtxoLeases::default();
+        let now = Instant::now();
+        let utxo = OutPoint::new(Txid::default(), 1);
+
+        leases.lease(&[utxo], now);
+
+        assert!(leases
+            .take_expired(now + Duration::from_secs(60))
+            .is_empty());
+        assert!(leases.is_leased(&utxo));
+        assert_eq!(leases.take_expired(now + LEASE_DURATION), vec![utxo]);
+        assert!(!leases.is_leased(&utxo));
+    }
+}
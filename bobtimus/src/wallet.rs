@@ -0,0 +1,315 @@
+use crate::{
+    elements_rpc::{Client, ElementsRpc},
+    utxo_lease::UtxoLeases,
+};
+use anyhow::{anyhow, Context, Result};
+use baru::input::Input;
+use elements::{bitcoin::Amount, secp256k1_zkp::SecretKey, AssetId, OutPoint};
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::{
+    sync::{mpsc, oneshot, watch, Mutex},
+    time::interval,
+};
+
+/// How often we look up our balances if no swap or loan asked us to.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Maximum number of requests waiting for the wallet task.
+const QUEUE_SIZE: usize = 64;
+
+/// Our balances at some point in time, for quoting without asking
+/// elementsd.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalletSnapshot {
+    /// Balances of the assets the wallet tracks.
+    pub balances: HashMap<AssetId, Amount>,
+    pub taken_at: Instant,
+}
+
+impl WalletSnapshot {
+    /// Our balance of the `asset_id`, as long as the wallet tracks it.
+    pub fn balance(&self, asset_id: AssetId) -> Result<Amount> {
+        self.balances
+            .get(&asset_id)
+            .copied()
+            .with_context(|| format!("balance of {} is not tracked", asset_id))
+    }
+}
+
+enum Request {
+    SelectInputs {
+        asset_id: AssetId,
+        amount: Amount,
+        lease: bool,
+        reply: oneshot::Sender<Result<Vec<Input>>>,
+    },
+    Release {
+        utxos: Vec<OutPoint>,
+    },
+    Refresh,
+}
+
+/// Handle to the task which owns the state of our wallet.
+///
+/// Everything which changes which UTXOs are available to us goes
+/// through the task's queue one request at a time, so that concurrent
+/// swaps never select the same UTXOs. Balances are read from the
+/// latest snapshot the task took, so quoting never waits for
+/// elementsd.
+#[derive(Clone)]
+pub struct Wallet {
+    requests: mpsc::Sender<Request>,
+    snapshot: watch::Receiver<Option<WalletSnapshot>>,
+}
+
+impl Wallet {
+    /// A handle to the wallet of `elementsd` and the task serving it,
+    /// which still needs to be run.
+    ///
+    /// The snapshots have the balances of each of the `assets`.
+    pub fn new(elementsd: Client, assets: Vec<AssetId>) -> (Self, WalletTask) {
+        let (requests, receiver) = mpsc::channel(QUEUE_SIZE);
+        let (snapshot_sender, snapshot) = watch::channel(None);
+
+        let wallet = Self { requests, snapshot };
+        let task = WalletTask {
+            elementsd,
+            assets,
+            requests: Arc::new(Mutex::new(receiver)),
+            leases: Arc::new(Mutex::new(UtxoLeases::default())),
+            snapshot: Arc::new(snapshot_sender),
+        };
+
+        (wallet, task)
+    }
+
+    /// Our latest balances, or an error if the wallet task could not
+    /// look them up yet.
+    pub fn snapshot(&self) -> Result<WalletSnapshot> {
+        let snapshot = self.snapshot.borrow().clone();
+
+        snapshot.ok_or_else(|| anyhow!("wallet balances are not known yet"))
+    }
+
+    /// Select our UTXOs worth at least `amount` of the `asset_id`.
+    ///
+    /// If the inputs are to be `lease`d, they are locked until the
+    /// lease expires or they are released, so that no other swap
+    /// selects them in the meantime.
+    pub async fn select_inputs(
+        &self,
+        asset_id: AssetId,
+        amount: Amount,
+        lease: bool,
+    ) -> Result<Vec<Input>> {
+        let (reply, response) = oneshot::channel();
+        self.send(Request::SelectInputs {
+            asset_id,
+            amount,
+            lease,
+            reply,
+        })
+        .await?;
+
+        response.await.context("wallet task stopped")?
+    }
+
+    /// Make the leased `utxos` available again, e.g. because the swap
+    /// transaction could not be built after all.
+    pub async fn release(&self, utxos: Vec<OutPoint>) -> Result<()> {
+        self.send(Request::Release { utxos }).await
+    }
+
+    /// Look up our balances again, e.g. after a swap or loan changed
+    /// them.
+    pub async fn refresh(&self) -> Result<()> {
+        self.send(Request::Refresh).await
+    }
+
+    async fn send(&self, request: Request) -> Result<()> {
+        self.requests
+            .send(request)
+            .await
+            .map_err(|_| anyhow!("wallet task stopped"))
+    }
+}
+
+/// The task behind a [`Wallet`].
+///
+/// Its state outlives every run, so that it can be restarted by a
+/// supervisor without losing track of leased UTXOs.
+#[derive(Clone)]
+pub struct WalletTask {
+    elementsd: Client,
+    assets: Vec<AssetId>,
+    requests: Arc<Mutex<mpsc::Receiver<Request>>>,
+    leases: Arc<Mutex<UtxoLeases>>,
+    snapshot: Arc<watch::Sender<Option<WalletSnapshot>>>,
+}
+
+impl WalletTask {
+    /// Serve wallet requests until every handle to the wallet is
+    /// dropped.
+    ///
+    /// Unlike other background tasks, it does not stop on shutdown, so
+    /// that in-flight swaps can still finish.
+    pub async fn run(self) {
+        let mut requests = self.requests.lock().await;
+        self.release_stale_locks().await;
+        let mut refresh = interval(REFRESH_INTERVAL);
+
+        loop {
+            tokio::select! {
+                request = requests.recv() => match request {
+                    Some(request) => self.handle(request).await,
+                    // every handle to the wallet was dropped
+                    None => return,
+                },
+                _ = refresh.tick() => self.refresh_snapshot().await,
+            }
+        }
+    }
+
+    async fn handle(&self, request: Request) {
+        match request {
+            Request::SelectInputs {
+                asset_id,
+                amount,
+                lease,
+                reply,
+            } => {
+                let result = self.select_inputs(asset_id, amount, lease).await;
+                let _ = reply.send(result);
+            }
+            Request::Release { utxos } => {
+                self.leases.lock().await.release(&utxos);
+                self.unlock_utxos(utxos).await;
+            }
+            Request::Refresh => self.refresh_snapshot().await,
+        }
+    }
+
+    async fn select_inputs(
+        &self,
+        asset_id: AssetId,
+        amount: Amount,
+        lease: bool,
+    ) -> Result<Vec<Input>> {
+        if !lease {
+            return find_inputs(&self.elementsd, asset_id, amount, false).await;
+        }
+
+        let expired = self.leases.lock().await.take_expired(Instant::now());
+        self.unlock_utxos(expired).await;
+
+        let inputs = find_inputs(&self.elementsd, asset_id, amount, true).await?;
+        let leased = inputs.iter().map(|input| input.txin).collect::<Vec<_>>();
+        self.leases.lock().await.lease(&leased, Instant::now());
+
+        Ok(inputs)
+    }
+
+    /// Unlock the UTXOs elementsd has locked although we hold no lease
+    /// on them, e.g. those of swaps we built before we restarted.
+    ///
+    /// elementsd only forgets its locks when it restarts itself, so
+    /// they would never be selected again otherwise.
+    async fn release_stale_locks(&self) {
+        let locked = match self.elementsd.locked_utxos().await {
+            Ok(locked) => locked,
+            Err(e) => {
+                tracing::warn!("failed to look up locked UTXOs: {:#}", e);
+                return;
+            }
+        };
+
+        let stale = {
+            let leases = self.leases.lock().await;
+            locked
+                .into_iter()
+                .filter(|utxo| !leases.is_leased(utxo))
+                .collect::<Vec<_>>()
+        };
+        if !stale.is_empty() {
+            tracing::info!("Unlocking {} UTXOs we hold no lease on", stale.len());
+        }
+        self.unlock_utxos(stale).await;
+    }
+
+    async fn unlock_utxos(&self, utxos: Vec<OutPoint>) {
+        for utxo in utxos {
+            // UTXOs spent by a broadcast swap cannot be unlocked, but
+            // they are not selected anymore either
+            if let Err(e) = self.elementsd.unlock_utxos(vec![utxo]).await {
+                tracing::debug!("failed to unlock UTXO {}: {:#}", utxo, e);
+            }
+        }
+    }
+
+    async fn refresh_snapshot(&self) {
+        let balances = async {
+            let mut balances = HashMap::new();
+            for asset_id in self.assets.iter().copied() {
+                balances.insert(asset_id, self.elementsd.get_balance(asset_id).await?);
+            }
+
+            Result::<_, anyhow::Error>::Ok(balances)
+        };
+
+        match balances.await {
+            Ok(balances) => {
+                let _ = self.snapshot.send(Some(WalletSnapshot {
+                    balances,
+                    taken_at: Instant::now(),
+                }));
+            }
+            Err(e) => tracing::warn!("failed to look up wallet balances: {:#}", e),
+        }
+    }
+}
+
+async fn find_inputs(
+    elements_client: &Client,
+    asset_id: AssetId,
+    input_amount: Amount,
+    should_lock: bool,
+) -> Result<Vec<Input>> {
+    let bob_inputs = elements_client
+        .select_inputs_for(asset_id, input_amount, should_lock)
+        .await
+        .context("failed to select inputs for swap")?;
+
+    let master_blinding_key = elements_client
+        .dumpmasterblindingkey()
+        .await
+        .context("failed to dump master blinding key")?;
+
+    let master_blinding_key = hex::decode(master_blinding_key)?;
+
+    let bob_inputs = bob_inputs
+        .into_iter()
+        .map(|(outpoint, txout)| {
+            use hmac::{Hmac, Mac, NewMac};
+            use sha2::Sha256;
+
+            let mut mac = Hmac::<Sha256>::new_varkey(&master_blinding_key)
+                .expect("HMAC can take key of any size");
+            mac.update(txout.script_pubkey.as_bytes());
+
+            let result = mac.finalize();
+            let input_blinding_sk = SecretKey::from_slice(&result.into_bytes())?;
+
+            Result::<_, anyhow::Error>::Ok(Input {
+                txin: outpoint,
+                original_txout: txout,
+                blinding_key: input_blinding_sk,
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(bob_inputs)
+}
@@ -0,0 +1,128 @@
+use crate::{database::TradeDirection, unix_timestamp};
+use anyhow::Result;
+use elements::Txid;
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::{header::CONTENT_TYPE, Url};
+use serde::Serialize;
+use sha2::Sha256;
+
+/// Header carrying the Unix timestamp at which we sent an event.
+const TIMESTAMP_HEADER: &str = "x-bobtimus-timestamp";
+
+/// Header carrying the signature of an event, as in
+/// `sha256=<hex-encoded HMAC>`.
+const SIGNATURE_HEADER: &str = "x-bobtimus-signature";
+
+/// Something happened to a trade or loan which the operator may want
+/// to act on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// We signed our side of a swap transaction and handed it to
+    /// Alice, who is left to broadcast it.
+    SwapCompleted {
+        txid: Txid,
+        pair: String,
+        direction: TradeDirection,
+        base_amount: u64,
+        quote_amount: u64,
+    },
+    /// We broadcast the transaction of a loan we agreed to.
+    LoanOriginated {
+        loan_txid: Txid,
+        /// In satoshi of L-BTC.
+        collateral: u64,
+        /// In satodollar.
+        principal: u64,
+    },
+    /// The borrower reclaimed the collateral of a loan.
+    LoanRepaid { loan_txid: Txid },
+    /// The liquidation transaction of a loan confirmed.
+    LoanLiquidated {
+        loan_txid: Txid,
+        liquidation_txid: Txid,
+    },
+}
+
+/// URLs we POST every [`Event`] to as JSON.
+///
+/// If there is a secret, every request carries the HMAC-SHA256 of
+/// `<timestamp>.<body>` under it, so that receivers can tell that the
+/// event is from us and not replayed.
+#[derive(Debug, Clone, Default)]
+pub struct Webhooks {
+    pub urls: Vec<Url>,
+    pub secret: Option<String>,
+}
+
+impl Webhooks {
+    /// Send the `event` to all webhooks in the background.
+    ///
+    /// Failures are logged, they never affect the trade or loan the
+    /// event is about.
+    pub fn notify(&self, event: Event) {
+        if self.urls.is_empty() {
+            return;
+        }
+
+        let webhooks = self.clone();
+        tokio::spawn(async move {
+            for url in webhooks.urls.iter() {
+                if let Err(e) = webhooks.send(url, &event).await {
+                    tracing::error!("failed to send {:?} to webhook {}: {:#}", event, url, e);
+                }
+            }
+        });
+    }
+
+    async fn send(&self, url: &Url, event: &Event) -> Result<()> {
+        let body = serde_json::to_string(event)?;
+        let timestamp = unix_timestamp().to_string();
+
+        let mut request = reqwest::Client::new()
+            .post(url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(TIMESTAMP_HEADER, &timestamp);
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &timestamp, &body));
+        }
+
+        request.body(body).send().await?.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_varkey(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signature_covers_timestamp_and_body() {
+        let event = Event::LoanRepaid {
+            loan_txid: Txid::default(),
+        };
+        let body = serde_json::to_string(&event).unwrap();
+
+        assert_eq!(
+            body,
+            format!(
+                r#"{{"event":"loan_repaid","loan_txid":"{}"}}"#,
+                Txid::default()
+            )
+        );
+        assert_eq!(sign("secret", "1", &body), sign("secret", "1", &body));
+        assert_ne!(sign("secret", "1", &body), sign("secret", "2", &body));
+        assert_ne!(sign("secret", "1", &body), sign("other", "1", &body));
+    }
+}
@@ -0,0 +1,269 @@
+//! Typed client for the HTTP API of bobtimus.
+//!
+//! The request and response types mirror what bobtimus serves, so
+//! that consumers do not have to hand-write them against its routes.
+//! Builds for wasm as well, where requests are made through `fetch`.
+
+use anyhow::{Context, Result};
+use baru::loan::{LoanRequest, LoanResponse};
+use elements::{
+    encode::{deserialize, serialize_hex},
+    secp256k1_zkp::SecretKey,
+    Address, OutPoint, Transaction, Txid,
+};
+use reqwest::{RequestBuilder, Response, Url};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// The only pair bobtimus trades so far.
+pub const DEFAULT_PAIR: &str = "lbtc-lusdt";
+
+/// Alice's request to swap the `amount` of her `alice_inputs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CreateSwapPayload {
+    pub alice_inputs: Vec<AliceInput>,
+    pub address: Address,
+    /// In the smallest unit of the asset Alice sells.
+    pub amount: u64,
+    /// The fee rate Alice funded her inputs for, in sat/vbyte.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fee_rate: Option<u64>,
+    /// A quote handed out earlier, to trade at its rate instead of the
+    /// latest one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote: Option<Quote>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct AliceInput {
+    pub outpoint: OutPoint,
+    pub blinding_key: SecretKey,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TradeDirection {
+    /// Alice buys the base asset of the pair.
+    Buy,
+    /// Alice sells the base asset of the pair.
+    Sell,
+}
+
+impl fmt::Display for TradeDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TradeDirection::Buy => f.write_str("buy"),
+            TradeDirection::Sell => f.write_str("sell"),
+        }
+    }
+}
+
+/// Terms signed by bobtimus, which it honours until they expire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Quote {
+    pub id: String,
+    pub pair: String,
+    pub direction: TradeDirection,
+    /// Price of one whole unit of the base asset, in the smallest unit
+    /// of the quote asset.
+    pub rate: u64,
+    pub base_amount: u64,
+    pub quote_amount: u64,
+    pub requested_amount: u64,
+    /// Unix timestamp in seconds.
+    pub expires_at: u64,
+    /// Hex-encoded DER signature of the terms.
+    pub signature: String,
+}
+
+/// Ask and bid in L-USDt per L-BTC.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Rate {
+    pub ask: f64,
+    pub bid: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoanOffer {
+    /// The rate at which the collateral is valued.
+    pub rate: Rate,
+    pub max_ltv_bps: u16,
+    pub liquidation_ltv_bps: u16,
+    pub min_timelock: u32,
+    pub max_timelock: u32,
+    /// In L-USDt.
+    pub max_principal: f64,
+}
+
+/// An error response of bobtimus.
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("bobtimus returned {status}: {title}{}", .detail.as_ref().map(|detail| format!(" ({})", detail)).unwrap_or_default())]
+pub struct Problem {
+    pub status: u16,
+    pub title: String,
+    pub detail: Option<String>,
+}
+
+/// A bobtimus instance.
+#[derive(Debug, Clone)]
+pub struct Client {
+    base_url: Url,
+    api_key: Option<String>,
+    inner: reqwest::Client,
+}
+
+impl Client {
+    pub fn new(base_url: Url) -> Self {
+        Self {
+            base_url,
+            api_key: None,
+            inner: reqwest::Client::new(),
+        }
+    }
+
+    /// Authenticate with the `api_key` of an instance which requires
+    /// one.
+    pub fn with_api_key(self, api_key: String) -> Self {
+        Self {
+            api_key: Some(api_key),
+            ..self
+        }
+    }
+
+    /// Signed terms to trade the `amount` of the asset Alice sells in
+    /// the `direction`.
+    pub async fn quote(&self, direction: TradeDirection, amount: u64) -> Result<Quote> {
+        let path = format!("api/quote/{}/{}?amount={}", DEFAULT_PAIR, direction, amount);
+        let response = self.send(self.inner.get(self.url(&path)?)).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Bob's swap transaction for Alice's `payload`, in which she sells
+    /// L-BTC for L-USDt.
+    pub async fn create_sell_swap(&self, payload: &CreateSwapPayload) -> Result<Transaction> {
+        self.create_swap(TradeDirection::Sell, payload).await
+    }
+
+    /// Bob's swap transaction for Alice's `payload`, in which she buys
+    /// L-BTC with L-USDt.
+    pub async fn create_buy_swap(&self, payload: &CreateSwapPayload) -> Result<Transaction> {
+        self.create_swap(TradeDirection::Buy, payload).await
+    }
+
+    pub async fn loan_offer(&self) -> Result<LoanOffer> {
+        let response = self
+            .send(self.inner.get(self.url("api/loan/offer")?))
+            .await?;
+
+        Ok(response.json().await?)
+    }
+
+    pub async fn request_loan(&self, loan_request: &LoanRequest) -> Result<LoanResponse> {
+        let path = format!("api/loan/{}", DEFAULT_PAIR);
+        let response = self.send(self.post(&path)?.json(loan_request)).await?;
+
+        Ok(response.json().await?)
+    }
+
+    /// Hand the borrower's signature of the loan `transaction` to Bob,
+    /// who broadcasts it.
+    pub async fn finalize_loan(&self, transaction: &Transaction) -> Result<Txid> {
+        let path = format!("api/loan/{}/finalize", DEFAULT_PAIR);
+        let payload = serde_json::json!({ "tx_hex": serialize_hex(transaction) });
+        let response = self.send(self.post(&path)?.json(&payload)).await?;
+
+        Ok(response.json().await?)
+    }
+
+    async fn create_swap(
+        &self,
+        direction: TradeDirection,
+        payload: &CreateSwapPayload,
+    ) -> Result<Transaction> {
+        let path = format!("api/swap/{}/{}", DEFAULT_PAIR, direction);
+        let response = self.send(self.post(&path)?.json(payload)).await?;
+
+        let transaction = hex::decode(response.text().await?.trim())
+            .context("swap transaction is not hex-encoded")?;
+
+        Ok(deserialize(&transaction)?)
+    }
+
+    fn url(&self, path: &str) -> Result<Url> {
+        let url = self
+            .base_url
+            .join(path)
+            .with_context(|| format!("invalid path {}", path))?;
+
+        Ok(url)
+    }
+
+    fn post(&self, path: &str) -> Result<RequestBuilder> {
+        let request = self.inner.post(self.url(path)?);
+
+        Ok(match &self.api_key {
+            Some(api_key) => request.bearer_auth(api_key),
+            None => request,
+        })
+    }
+
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let response = request.send().await?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(response);
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        let problem = serde_json::from_str::<ProblemBody>(&body).unwrap_or(ProblemBody {
+            title: body,
+            detail: None,
+        });
+
+        Err(Problem {
+            status: status.as_u16(),
+            title: problem.title,
+            detail: problem.detail,
+        }
+        .into())
+    }
+}
+
+/// The fields of an RFC 7807 problem we show.
+#[derive(Deserialize)]
+struct ProblemBody {
+    title: String,
+    detail: Option<String>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_without_quote_serializes_like_before() {
+        let payload = CreateSwapPayload {
+            alice_inputs: vec![],
+            address: "ex1qnvt8ra6mhrfv3ef0h3xe6yu8g3v39f9jtwtfq4"
+                .parse()
+                .unwrap(),
+            amount: 100_000,
+            fee_rate: Some(1),
+            quote: None,
+        };
+
+        let json = serde_json::to_value(&payload).unwrap();
+
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "alice_inputs": [],
+                "address": "ex1qnvt8ra6mhrfv3ef0h3xe6yu8g3v39f9jtwtfq4",
+                "amount": 100_000,
+                "fee_rate": 1,
+            })
+        );
+    }
+}
@@ -0,0 +1,134 @@
+/// The maximum number of branches we explore before giving up.
+///
+/// Same limit as the one used by Bitcoin Core.
+const TOTAL_TRIES: usize = 100_000;
+
+/// Search for a subset of `effective_values` whose sum lies between
+/// `target` and `target + cost_of_change`.
+///
+/// Any such subset can be spent without a change output: the excess
+/// is smaller than what it would cost us to create (and later spend)
+/// the change, so we are better off paying it to the miners.
+///
+/// This is a depth-first search over the inclusion/exclusion tree of
+/// the candidates, sorted by descending effective value, as described
+/// in Mark Erhardt's "An Evaluation of Coin Selection Strategies".
+///
+/// Returns the indices into `effective_values` of the selection with
+/// the least excess, or `None` if no changeless solution was found
+/// within [`TOTAL_TRIES`] iterations.
+pub fn select(effective_values: &[u64], target: u64, cost_of_change: u64) -> Option<Vec<usize>> {
+    let mut pool = effective_values
+        .iter()
+        .copied()
+        .enumerate()
+        .collect::<Vec<_>>();
+    pool.sort_by(|(_, a), (_, b)| b.cmp(a));
+
+    let mut available = pool.iter().map(|(_, value)| value).sum::<u64>();
+    if available < target {
+        return None;
+    }
+
+    let mut current_value = 0;
+    let mut path = Vec::<bool>::with_capacity(pool.len());
+    let mut best: Option<(u64, Vec<bool>)> = None;
+
+    for _ in 0..TOTAL_TRIES {
+        let backtrack =
+            if current_value + available < target || current_value > target + cost_of_change {
+                true
+            } else if current_value >= target {
+                let excess = current_value - target;
+                if best
+                    .as_ref()
+                    .map_or(true, |(best_excess, _)| excess <= *best_excess)
+                {
+                    best = Some((excess, path.clone()));
+                }
+
+                if excess == 0 {
+                    break;
+                }
+
+                true
+            } else {
+                false
+            };
+
+        if backtrack {
+            // undo all the trailing exclusions, making their values
+            // available again
+            while path.last() == Some(&false) {
+                path.pop();
+                available += pool[path.len()].1;
+            }
+
+            // and exclude the last included candidate
+            let depth = path.len();
+            match path.last_mut() {
+                Some(included) => {
+                    *included = false;
+                    current_value -= pool[depth - 1].1;
+                }
+                // we have explored the whole tree
+                None => break,
+            }
+        } else {
+            let (_, value) = pool[path.len()];
+            available -= value;
+            current_value += value;
+            path.push(true);
+        }
+    }
+
+    best.map(|(_, path)| {
+        path.iter()
+            .zip(pool.iter())
+            .filter(|(included, _)| **included)
+            .map(|(_, (index, _))| *index)
+            .collect()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_exact_match() {
+        let values = [1_000, 5_000, 2_000, 7_000];
+
+        let mut selection = select(&values, 8_000, 0).unwrap();
+        selection.sort_unstable();
+
+        assert_eq!(selection, vec![0, 3]);
+    }
+
+    #[test]
+    fn accepts_excess_below_cost_of_change() {
+        let values = [3_000, 6_000];
+
+        let selection = select(&values, 5_500, 600).unwrap();
+
+        assert_eq!(selection, vec![1]);
+    }
+
+    #[test]
+    fn gives_up_if_every_solution_needs_change() {
+        let values = [3_000, 6_000];
+
+        let selection = select(&values, 5_000, 500);
+
+        assert_eq!(selection, None);
+    }
+
+    #[test]
+    fn gives_up_if_funds_are_insufficient() {
+        let values = [3_000, 6_000];
+
+        let selection = select(&values, 10_000, 500);
+
+        assert_eq!(selection, None);
+    }
+}
@@ -1,24 +1,48 @@
 use bdk::{
-    bitcoin::{Amount, Denomination},
     database::{BatchOperations, Database},
     wallet::coin_selection::{
         BranchAndBoundCoinSelection, CoinSelectionAlgorithm, CoinSelectionResult,
     },
 };
-use elements::{AssetId, OutPoint, Script};
+use elements::{bitcoin::Amount, AssetId, OutPoint, Script};
 use estimate_transaction_size::avg_vbytes;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use std::{cmp::Reverse, collections::HashMap};
+
+mod bnb;
+
+/// Weight of an input without its witness: previous outpoint (36
+/// bytes), sequence (4 bytes) and an empty `script_sig` (1 byte).
+const TXIN_BASE_WEIGHT: usize = (32 + 4 + 4 + 1) * 4;
 
 /// Select a subset of `utxos` to cover the `target` amount.
 ///
-/// It makes use of a Branch and Bound coin selection algorithm
-/// provided by `bdk`.
+/// The selected coins are returned as indices into `utxos`.
+///
+/// How the coins are picked depends on `options.strategy`.
+///
+/// UTXOs which cost more to spend than they are worth at the given
+/// fee rate are never selected. If the change would be below
+/// `options.dust_limit`, it is added to the `recommended_fee` instead.
+///
+/// With `options.consolidation` set, small UTXOs are spent on top of
+/// the selection whenever the fee rate is low enough.
 ///
-/// Only supports P2PK, P2PKH and P2WPKH UTXOs.
+/// No more than `options.max_inputs` UTXOs are ever selected, and
+/// only those with enough confirmations if `options.min_confirmations`
+/// is set.
+///
+/// With `options.group_by_address` set, selections which only spend
+/// from a single address are preferred.
+///
+/// Only supports P2PK, P2PKH and P2WPKH UTXOs, unless they come with
+/// a `satisfaction_weight`.
 pub fn coin_select(
-    utxos: Vec<Utxo>,
+    utxos: &[Utxo],
     target: Amount,
     fee_rate_sat_per_vbyte: f32,
     fee_offset: Amount,
+    options: Options,
 ) -> Result<Output, Error> {
     let asset = utxos
         .first()
@@ -32,23 +56,373 @@ pub fn coin_select(
         return Err(Error::HeterogeneousUtxos);
     }
 
-    let bdk_utxos = utxos
+    coin_select_from(
+        utxos,
+        0..utxos.len(),
+        target,
+        fee_rate_sat_per_vbyte,
+        fee_offset,
+        true,
+        options,
+    )
+}
+
+/// Select subsets of `utxos` to cover several `(asset, amount)`
+/// targets at once.
+///
+/// Besides the change outputs of the selection, the transaction has
+/// `outputs` other outputs, such as the ones paying the targets or
+/// those of a counterparty.
+///
+/// Fees can only be paid in `fee_asset`. The fee of the `outputs`,
+/// and of every input and change output needed for the other assets,
+/// is added on top of the `fee_asset` target. If `fee_asset` is not
+/// one of the targets, we still select enough of it to pay for the
+/// fee, unless the fee rate is zero.
+///
+/// Only change of the `fee_asset` which is below `options.dust_limit`
+/// goes to the fee. Change of the other assets is always returned,
+/// however small, because it cannot be paid to the miners.
+///
+/// Returns one [`Output`] per selected asset, whose coins are indices
+/// into `utxos`.
+pub fn coin_select_many(
+    utxos: &[Utxo],
+    targets: &[(AssetId, Amount)],
+    fee_asset: AssetId,
+    fee_rate_sat_per_vbyte: f32,
+    outputs: usize,
+    options: Options,
+) -> Result<Vec<(AssetId, Output)>, Error> {
+    let mut utxos_by_asset = HashMap::<AssetId, Vec<usize>>::new();
+    for (index, utxo) in utxos.iter().enumerate() {
+        utxos_by_asset.entry(utxo.asset).or_default().push(index);
+    }
+
+    let mut fee_offset = Amount::from_sat(outputs_fee(outputs, fee_rate_sat_per_vbyte));
+    let mut outputs = Vec::with_capacity(targets.len() + 1);
+    for (asset, target) in targets.iter().filter(|(asset, _)| *asset != fee_asset) {
+        let candidates = utxos_by_asset.remove(asset).unwrap_or_default();
+        let output = coin_select_from(
+            utxos,
+            candidates,
+            *target,
+            0.0,
+            Amount::ZERO,
+            false,
+            options,
+        )?;
+
+        fee_offset += output.fee_contribution(utxos, fee_rate_sat_per_vbyte);
+        outputs.push((*asset, output));
+    }
+
+    let fee_asset_target = targets
         .iter()
-        .cloned()
-        .filter_map(|utxo| {
-            max_satisfaction_weight(&utxo.script_pubkey).map(|weight| (utxo, weight))
+        .find_map(|(asset, target)| (*asset == fee_asset).then(|| *target));
+    if fee_asset_target.is_some() || fee_rate_sat_per_vbyte > 0.0 {
+        let candidates = utxos_by_asset.remove(&fee_asset).unwrap_or_default();
+        let output = coin_select_from(
+            utxos,
+            candidates,
+            fee_asset_target.unwrap_or(Amount::ZERO),
+            fee_rate_sat_per_vbyte,
+            fee_offset,
+            true,
+            options,
+        )?;
+
+        outputs.push((fee_asset, output));
+    }
+
+    Ok(outputs)
+}
+
+/// Select a subset of the `candidates`, given as indices into `utxos`,
+/// to cover the `target` amount.
+///
+/// Dust change is only added to the fee if the selected asset
+/// `pays_fee`.
+fn coin_select_from(
+    utxos: &[Utxo],
+    candidates: impl IntoIterator<Item = usize>,
+    target: Amount,
+    fee_rate_sat_per_vbyte: f32,
+    fee_offset: Amount,
+    pays_fee: bool,
+    options: Options,
+) -> Result<Output, Error> {
+    let candidates = candidates
+        .into_iter()
+        .filter(|index| effective_value(&utxos[*index], fee_rate_sat_per_vbyte).is_some())
+        .filter(|index| match options.min_confirmations {
+            Some(min_confirmations) => min_confirmations.is_satisfied_by(&utxos[*index]),
+            None => true,
         })
-        .map(|(utxo, weight)| (bdk::UTXO::from(utxo), weight))
-        .collect();
+        .collect::<Vec<_>>();
 
-    // a change is a regular output
-    let size_of_change = avg_vbytes::OUTPUT;
+    let mut rng = match options.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+
+    let max_inputs = options.max_inputs.unwrap_or(usize::MAX);
+    let single_address_output = if options.group_by_address {
+        select_from_single_address(
+            utxos,
+            &candidates,
+            target,
+            fee_rate_sat_per_vbyte,
+            fee_offset,
+            options.strategy,
+            max_inputs,
+            &mut rng,
+        )
+    } else {
+        None
+    };
+
+    let mut output = match single_address_output {
+        Some(output) => output,
+        None => select(
+            utxos,
+            &candidates,
+            target,
+            fee_rate_sat_per_vbyte,
+            fee_offset,
+            options.strategy,
+            max_inputs,
+            &mut rng,
+        )?,
+    };
+
+    if let Some(consolidation) = options.consolidation {
+        if fee_rate_sat_per_vbyte <= consolidation.max_fee_rate_sat_per_vbyte {
+            consolidate(
+                &mut output,
+                utxos,
+                &candidates,
+                fee_rate_sat_per_vbyte,
+                consolidation.max_utxo_value,
+                max_inputs,
+            );
+        }
+    }
+
+    let change = output.recommended_change();
+    if pays_fee && change > Amount::ZERO && change < options.dust_limit {
+        // a change output this small is not worth creating
+        output.recommended_fee += change;
+    }
+
+    output.estimated_vbytes = output.estimate_vbytes(utxos, fee_rate_sat_per_vbyte, fee_offset);
+
+    Ok(output)
+}
+
+/// Select a subset of the `candidates` to cover the `target` amount,
+/// using the given `strategy` and no more than `max_inputs` UTXOs.
+#[allow(clippy::too_many_arguments)]
+fn select<R: Rng>(
+    utxos: &[Utxo],
+    candidates: &[usize],
+    target: Amount,
+    fee_rate_sat_per_vbyte: f32,
+    fee_offset: Amount,
+    strategy: Strategy,
+    max_inputs: usize,
+    rng: &mut R,
+) -> Result<Output, Error> {
+    let output = match strategy {
+        Strategy::BranchAndBound => {
+            match changeless_coin_select(
+                utxos,
+                candidates,
+                target,
+                fee_rate_sat_per_vbyte,
+                fee_offset,
+            ) {
+                Some(output) => output,
+                None => bdk_coin_select(
+                    utxos,
+                    candidates,
+                    target,
+                    fee_rate_sat_per_vbyte,
+                    fee_offset,
+                )?,
+            }
+        }
+        Strategy::LargestFirst => {
+            let mut candidates = candidates.to_vec();
+            candidates.sort_by_key(|index| Reverse(utxos[*index].value));
+
+            select_in_order(
+                utxos,
+                candidates,
+                target,
+                fee_rate_sat_per_vbyte,
+                fee_offset,
+            )?
+        }
+        Strategy::OldestFirst => {
+            let mut candidates = candidates.to_vec();
+            candidates.sort_by_key(|index| utxos[*index].height.unwrap_or(u64::MAX));
+
+            select_in_order(
+                utxos,
+                candidates,
+                target,
+                fee_rate_sat_per_vbyte,
+                fee_offset,
+            )?
+        }
+        Strategy::RandomImprove => random_improve(
+            utxos,
+            candidates,
+            target,
+            fee_rate_sat_per_vbyte,
+            fee_offset,
+            rng,
+        )?,
+    };
+
+    if output.coins.len() <= max_inputs {
+        return Ok(output);
+    }
+
+    // the biggest UTXOs give us the best chance to stay within the
+    // limit
+    let mut candidates = candidates.to_vec();
+    candidates.sort_by_key(|index| Reverse(utxos[*index].value));
+    candidates.truncate(max_inputs);
+
+    select_in_order(
+        utxos,
+        candidates,
+        target,
+        fee_rate_sat_per_vbyte,
+        fee_offset,
+    )
+    .map_err(|_| Error::TooManyInputs { max_inputs })
+}
+
+/// Look for a selection of the `candidates` which all share the same
+/// `script_pubkey`, so that the transaction does not link any of our
+/// addresses together.
+///
+/// Addresses holding less are tried first, to keep the bigger UTXO
+/// sets around for bigger payments.
+#[allow(clippy::too_many_arguments)]
+fn select_from_single_address<R: Rng>(
+    utxos: &[Utxo],
+    candidates: &[usize],
+    target: Amount,
+    fee_rate_sat_per_vbyte: f32,
+    fee_offset: Amount,
+    strategy: Strategy,
+    max_inputs: usize,
+    rng: &mut R,
+) -> Option<Output> {
+    let mut utxos_by_address = HashMap::<&Script, Vec<usize>>::new();
+    for index in candidates {
+        utxos_by_address
+            .entry(&utxos[*index].script_pubkey)
+            .or_default()
+            .push(*index);
+    }
+
+    let mut groups = utxos_by_address.into_iter().collect::<Vec<_>>();
+    groups.sort_by_key(|(script_pubkey, candidates)| {
+        (
+            candidates
+                .iter()
+                .map(|index| utxos[*index].value)
+                .sum::<u64>(),
+            script_pubkey.to_bytes(),
+        )
+    });
+
+    groups.into_iter().find_map(|(_, candidates)| {
+        select(
+            utxos,
+            &candidates,
+            target,
+            fee_rate_sat_per_vbyte,
+            fee_offset,
+            strategy,
+            max_inputs,
+            rng,
+        )
+        .ok()
+    })
+}
+
+/// Look for a selection of the `candidates` which covers `target` and
+/// the fees without leaving any change.
+///
+/// The excess of the selection is paid to the miners and included in
+/// the `recommended_fee`.
+fn changeless_coin_select(
+    utxos: &[Utxo],
+    candidates: &[usize],
+    target: Amount,
+    fee_rate_sat_per_vbyte: f32,
+    fee_offset: Amount,
+) -> Option<Output> {
+    let (candidates, effective_values) = candidates
+        .iter()
+        .filter_map(|index| {
+            let effective_value = effective_value(&utxos[*index], fee_rate_sat_per_vbyte)?;
+
+            Some((*index, effective_value))
+        })
+        .unzip::<_, _, Vec<_>, Vec<_>>();
+
+    let cost_of_change = change_fee(fee_rate_sat_per_vbyte);
+
+    let selection = bnb::select(
+        &effective_values,
+        target.as_sat() + fee_offset.as_sat(),
+        cost_of_change,
+    )?;
+
+    let coins = selection
+        .into_iter()
+        .map(|position| candidates[position])
+        .collect::<Vec<_>>();
+    let selected_amount = coins.iter().map(|index| utxos[*index].value).sum::<u64>();
+
+    Some(Output {
+        coins,
+        target_amount: target,
+        recommended_fee: Amount::from_sat(selected_amount - target.as_sat()),
+        estimated_vbytes: 0,
+        selected_amount: Amount::from_sat(selected_amount),
+    })
+}
+
+/// Select a subset of the `candidates` to cover the `target` amount
+/// using the coin selection algorithm provided by `bdk`.
+fn bdk_coin_select(
+    utxos: &[Utxo],
+    candidates: &[usize],
+    target: Amount,
+    fee_rate_sat_per_vbyte: f32,
+    fee_offset: Amount,
+) -> Result<Output, Error> {
+    let bdk_utxos = candidates
+        .iter()
+        .filter_map(|index| {
+            let utxo = &utxos[*index];
+            max_satisfaction_weight(utxo).map(|weight| (bdk::UTXO::from(utxo.clone()), weight))
+        })
+        .collect();
 
     let CoinSelectionResult {
         selected: selected_utxos,
         fee_amount,
         ..
-    } = BranchAndBoundCoinSelection::new(size_of_change)
+    } = BranchAndBoundCoinSelection::new(avg_vbytes::OUTPUT)
         .coin_select(
             &DummyDb,
             Vec::new(),
@@ -64,30 +438,300 @@ pub fn coin_select(
             _ => Error::Bdk(e),
         })?;
 
-    let selected_utxos = selected_utxos
+    let coins = selected_utxos
         .iter()
         .map(|bdk_utxo| {
-            utxos
+            candidates
                 .iter()
-                .find(|utxo| {
-                    bdk_utxo.outpoint.txid.as_hash() == utxo.outpoint.txid.as_hash()
-                        && bdk_utxo.outpoint.vout == utxo.outpoint.vout
+                .copied()
+                .find(|index| {
+                    let outpoint = utxos[*index].outpoint;
+                    bdk_utxo.outpoint.txid.as_hash() == outpoint.txid.as_hash()
+                        && bdk_utxo.outpoint.vout == outpoint.vout
                 })
                 .expect("same source of utxos")
         })
-        .cloned()
-        .collect();
+        .collect::<Vec<_>>();
+    let selected_amount = coins.iter().map(|index| utxos[*index].value).sum::<u64>();
 
-    let recommended_fee =
-        Amount::from_float_in(fee_amount.into(), Denomination::Satoshi).map_err(Error::ParseFee)?;
+    // bdk does not pay for the change output, and whatever is too
+    // little to be worth one goes to the miners
+    let fee = fee_amount.ceil() as u64 + change_fee(fee_rate_sat_per_vbyte);
+    let recommended_fee = fee.min(selected_amount - target.as_sat());
 
     Ok(Output {
-        coins: selected_utxos,
+        coins,
         target_amount: target,
-        recommended_fee,
+        recommended_fee: Amount::from_sat(recommended_fee),
+        estimated_vbytes: 0,
+        selected_amount: Amount::from_sat(selected_amount),
     })
 }
 
+/// Select `candidates` in the given order until they cover `target`,
+/// `fee_offset` and the fees for spending them.
+fn select_in_order(
+    utxos: &[Utxo],
+    candidates: impl IntoIterator<Item = usize>,
+    target: Amount,
+    fee_rate_sat_per_vbyte: f32,
+    fee_offset: Amount,
+) -> Result<Output, Error> {
+    let needed = target.as_sat() + fee_offset.as_sat();
+
+    let mut coins = Vec::new();
+    let mut selected_effective_value = 0;
+    let mut available = 0;
+    for index in candidates {
+        let utxo = &utxos[index];
+
+        available += utxo.value;
+        if selected_effective_value >= needed {
+            continue;
+        }
+
+        selected_effective_value += effective_value(utxo, fee_rate_sat_per_vbyte).unwrap_or(0);
+        coins.push(index);
+    }
+
+    if selected_effective_value < needed {
+        return Err(Error::InsufficientFunds { needed, available });
+    }
+
+    Ok(Output::with_fees(
+        utxos,
+        coins,
+        target,
+        fee_rate_sat_per_vbyte,
+        fee_offset,
+    ))
+}
+
+/// Randomly select `candidates` until they cover `target`, and then
+/// keep adding random UTXOs as long as that brings the change closer
+/// to `target`.
+///
+/// This is the Random-Improve algorithm used by Cardano wallets. The
+/// change ends up looking like another payment, which improves our
+/// privacy and leaves the wallet with UTXOs of a useful size.
+fn random_improve<R: Rng>(
+    utxos: &[Utxo],
+    candidates: &[usize],
+    target: Amount,
+    fee_rate_sat_per_vbyte: f32,
+    fee_offset: Amount,
+    rng: &mut R,
+) -> Result<Output, Error> {
+    let mut candidates = candidates.to_vec();
+    candidates.shuffle(rng);
+
+    let Output { mut coins, .. } = select_in_order(
+        utxos,
+        candidates.iter().copied(),
+        target,
+        fee_rate_sat_per_vbyte,
+        fee_offset,
+    )?;
+
+    let ideal = 2 * (target.as_sat() + fee_offset.as_sat());
+    let maximum = 3 * (target.as_sat() + fee_offset.as_sat());
+    let mut selected_effective_value = coins
+        .iter()
+        .filter_map(|index| effective_value(&utxos[*index], fee_rate_sat_per_vbyte))
+        .sum::<u64>();
+
+    for index in candidates.into_iter().skip(coins.len()) {
+        let effective_value = effective_value(&utxos[index], fee_rate_sat_per_vbyte).unwrap_or(0);
+        let improved_effective_value = selected_effective_value + effective_value;
+
+        let distance = |value: u64| (ideal as i128 - value as i128).abs();
+        if improved_effective_value <= maximum
+            && distance(improved_effective_value) < distance(selected_effective_value)
+        {
+            selected_effective_value = improved_effective_value;
+            coins.push(index);
+        }
+    }
+
+    Ok(Output::with_fees(
+        utxos,
+        coins,
+        target,
+        fee_rate_sat_per_vbyte,
+        fee_offset,
+    ))
+}
+
+/// Add unselected `candidates` worth less than `max_utxo_value` to the
+/// `output`, up to a total of `max_inputs`, sending their value back
+/// to us as change.
+///
+/// Nothing is added if the extra inputs (and the change output, if we
+/// did not already have one) would cost more than they are worth.
+fn consolidate(
+    output: &mut Output,
+    utxos: &[Utxo],
+    candidates: &[usize],
+    fee_rate_sat_per_vbyte: f32,
+    max_utxo_value: Amount,
+    max_inputs: usize,
+) {
+    let extra_coins = candidates
+        .iter()
+        .copied()
+        .filter(|index| {
+            utxos[*index].value < max_utxo_value.as_sat() && !output.coins.contains(index)
+        })
+        .take(max_inputs.saturating_sub(output.coins.len()))
+        .collect::<Vec<_>>();
+
+    let extra_value = extra_coins
+        .iter()
+        .map(|index| utxos[*index].value)
+        .sum::<u64>();
+    let inputs_fee = extra_coins
+        .iter()
+        .filter_map(|index| max_satisfaction_weight(&utxos[*index]))
+        .map(|weight| input_fee(weight, fee_rate_sat_per_vbyte))
+        .sum::<u64>();
+    let change_fee = if output.recommended_change() == Amount::ZERO {
+        change_fee(fee_rate_sat_per_vbyte)
+    } else {
+        0
+    };
+
+    if extra_value <= inputs_fee + change_fee {
+        return;
+    }
+
+    output.coins.extend(extra_coins);
+    output.selected_amount += Amount::from_sat(extra_value);
+    output.recommended_fee += Amount::from_sat(inputs_fee + change_fee);
+}
+
+/// Fee to pay for spending an input with a witness of weight
+/// `satisfaction_weight`.
+fn input_fee(satisfaction_weight: usize, fee_rate_sat_per_vbyte: f32) -> u64 {
+    let vbytes = (TXIN_BASE_WEIGHT + satisfaction_weight) as f32 / 4.0;
+
+    (vbytes * fee_rate_sat_per_vbyte).ceil() as u64
+}
+
+/// Value of `utxo` minus the fee to spend it, or `None` if it costs
+/// more to spend than it is worth.
+fn effective_value(utxo: &Utxo, fee_rate_sat_per_vbyte: f32) -> Option<u64> {
+    let weight = max_satisfaction_weight(utxo)?;
+    let fee = input_fee(weight, fee_rate_sat_per_vbyte);
+
+    utxo.value.checked_sub(fee).filter(|value| *value > 0)
+}
+
+/// Fee to pay for including a change output.
+fn change_fee(fee_rate_sat_per_vbyte: f32) -> u64 {
+    // a change is a regular output
+    outputs_fee(1, fee_rate_sat_per_vbyte)
+}
+
+fn outputs_fee(outputs: usize, fee_rate_sat_per_vbyte: f32) -> u64 {
+    ((outputs as u64 * avg_vbytes::OUTPUT) as f32 * fee_rate_sat_per_vbyte).ceil() as u64
+}
+
+/// Knobs to tune the behaviour of the coin selection.
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// The algorithm used to pick coins.
+    pub strategy: Strategy,
+    /// Change below this amount is paid to the miners instead of
+    /// being sent back to us.
+    pub dust_limit: Amount,
+    /// Spend small UTXOs in addition to the ones needed to cover the
+    /// target, if fees are cheap enough.
+    pub consolidation: Option<Consolidation>,
+    /// The maximum number of UTXOs to select, if any.
+    pub max_inputs: Option<usize>,
+    /// Only select UTXOs which are buried deep enough, if set.
+    pub min_confirmations: Option<MinConfirmations>,
+    /// Prefer spending UTXOs of a single address over mixing UTXOs of
+    /// several addresses in one transaction.
+    pub group_by_address: bool,
+    /// Seed for the strategies which pick UTXOs at random.
+    ///
+    /// Selecting from the same UTXOs with the same seed always yields
+    /// the same result, except for the fallback of
+    /// [`Strategy::BranchAndBound`], which is left to `bdk`.
+    pub seed: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            strategy: Strategy::BranchAndBound,
+            dust_limit: Amount::from_sat(546),
+            consolidation: None,
+            max_inputs: None,
+            min_confirmations: None,
+            group_by_address: false,
+            seed: None,
+        }
+    }
+}
+
+/// The available coin selection algorithms.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Look for a selection which does not need a change output,
+    /// falling back to the coin selection algorithm provided by `bdk`
+    /// if there is none.
+    ///
+    /// Minimises fees.
+    BranchAndBound,
+    /// Spend the biggest UTXOs first.
+    ///
+    /// Keeps the number of inputs low, at the cost of revealing our
+    /// biggest UTXOs.
+    LargestFirst,
+    /// Spend the UTXOs which have been confirmed the longest first,
+    /// leaving unconfirmed ones for last.
+    ///
+    /// Minimises the risk of spending UTXOs which may still be
+    /// reorged out.
+    OldestFirst,
+    /// Pick UTXOs at random, aiming for change of the same size as the
+    /// target.
+    ///
+    /// Makes it harder to tell the payment and the change apart.
+    RandomImprove,
+}
+
+/// How many confirmations a UTXO needs to be selected.
+#[derive(Debug, Clone, Copy)]
+pub struct MinConfirmations {
+    /// A UTXO included in the chain tip has one confirmation.
+    pub confirmations: u64,
+    /// Height of the current chain tip.
+    pub tip_height: u64,
+}
+
+impl MinConfirmations {
+    fn is_satisfied_by(&self, utxo: &Utxo) -> bool {
+        match utxo.height {
+            Some(height) if height <= self.tip_height => {
+                self.tip_height - height + 1 >= self.confirmations
+            }
+            _ => self.confirmations == 0,
+        }
+    }
+}
+
+/// When and what to consolidate during coin selection.
+#[derive(Debug, Clone, Copy)]
+pub struct Consolidation {
+    /// Only consolidate if the fee rate is at most this.
+    pub max_fee_rate_sat_per_vbyte: f32,
+    /// Only consolidate UTXOs worth less than this.
+    pub max_utxo_value: Amount,
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Insufficient funds: needed at least {needed}, got {available}")]
@@ -99,8 +743,8 @@ pub enum Error {
     },
     #[error("All UTXOs must have the same asset ID")]
     HeterogeneousUtxos,
-    #[error("Failed to parse recommended fee: {0}")]
-    ParseFee(#[from] bdk::bitcoin::util::amount::ParseAmountError),
+    #[error("Cannot cover the target with at most {max_inputs} inputs")]
+    TooManyInputs { max_inputs: usize },
     #[error("Error from bdk: {0}")]
     Bdk(#[from] bdk::Error),
 }
@@ -111,6 +755,14 @@ pub struct Utxo {
     pub value: u64,
     pub script_pubkey: Script,
     pub asset: AssetId,
+    /// Height of the block which includes the UTXO, if it is
+    /// confirmed.
+    pub height: Option<u64>,
+    /// Maximum weight of the witness needed to spend the UTXO.
+    ///
+    /// Only needed if it cannot be derived from the `script_pubkey`,
+    /// e.g. for P2WSH outputs.
+    pub satisfaction_weight: Option<usize>,
 }
 
 impl From<Utxo> for bdk::UTXO {
@@ -136,26 +788,131 @@ impl From<Utxo> for bdk::UTXO {
 /// Result of running the coin selection algorithm succesfully.
 #[derive(Debug)]
 pub struct Output {
-    pub coins: Vec<Utxo>,
+    /// Indices of the selected coins in the UTXOs we selected from.
+    pub coins: Vec<usize>,
     pub target_amount: Amount,
     pub recommended_fee: Amount,
+    /// Estimated size of what the `recommended_fee` pays for: the
+    /// selected inputs, the change output, if any, and whatever the
+    /// `fee_offset` was accounting for.
+    pub estimated_vbytes: u64,
+    selected_amount: Amount,
 }
 
 impl Output {
+    /// Build the output for spending `coins`, paying for their inputs,
+    /// `fee_offset` and the change output at the given fee rate.
+    ///
+    /// If the excess is not worth a change output, it is paid to the
+    /// miners instead. `coins` must be enough to pay for everything.
+    fn with_fees(
+        utxos: &[Utxo],
+        coins: Vec<usize>,
+        target: Amount,
+        fee_rate_sat_per_vbyte: f32,
+        fee_offset: Amount,
+    ) -> Self {
+        let selected_effective_value = coins
+            .iter()
+            .filter_map(|index| effective_value(&utxos[*index], fee_rate_sat_per_vbyte))
+            .sum::<u64>();
+        let selected_amount = coins.iter().map(|index| utxos[*index].value).sum::<u64>();
+
+        let excess = selected_effective_value - target.as_sat() - fee_offset.as_sat();
+        let change_fee = change_fee(fee_rate_sat_per_vbyte);
+        let change = excess.saturating_sub(change_fee);
+
+        Self {
+            coins,
+            target_amount: target,
+            recommended_fee: Amount::from_sat(selected_amount - target.as_sat() - change),
+            estimated_vbytes: 0,
+            selected_amount: Amount::from_sat(selected_amount),
+        }
+    }
+
+    /// The selected coins, looked up in the `utxos` we selected from.
+    pub fn selected_utxos<'a>(&'a self, utxos: &'a [Utxo]) -> impl Iterator<Item = &'a Utxo> {
+        self.coins.iter().map(move |index| &utxos[*index])
+    }
+
     pub fn recommended_change(&self) -> Amount {
-        self.selected_amount() - self.target_amount - self.recommended_fee
+        self.selected_amount - self.target_amount - self.recommended_fee
     }
 
     pub fn selected_amount(&self) -> Amount {
-        let amount = self.coins.iter().fold(0, |acc, utxo| acc + utxo.value);
-        Amount::from_sat(amount)
+        self.selected_amount
+    }
+
+    /// The fee rate we actually end up paying with the
+    /// `recommended_fee`, which is higher than the requested one if
+    /// the excess of the selection was paid to the miners.
+    pub fn effective_fee_rate_sat_per_vbyte(&self) -> f32 {
+        if self.estimated_vbytes == 0 {
+            return 0.0;
+        }
+
+        self.recommended_fee.as_sat() as f32 / self.estimated_vbytes as f32
+    }
+
+    fn estimate_vbytes(
+        &self,
+        utxos: &[Utxo],
+        fee_rate_sat_per_vbyte: f32,
+        fee_offset: Amount,
+    ) -> u64 {
+        let inputs_weight = self
+            .selected_utxos(utxos)
+            .filter_map(max_satisfaction_weight)
+            .map(|weight| TXIN_BASE_WEIGHT + weight)
+            .sum::<usize>();
+        let inputs_vbytes = (inputs_weight as f32 / 4.0).ceil() as u64;
+
+        let change_vbytes = if self.recommended_change() > Amount::ZERO {
+            avg_vbytes::OUTPUT
+        } else {
+            0
+        };
+
+        let fee_offset_vbytes = if fee_rate_sat_per_vbyte > 0.0 {
+            (fee_offset.as_sat() as f32 / fee_rate_sat_per_vbyte).round() as u64
+        } else {
+            0
+        };
+
+        inputs_vbytes + change_vbytes + fee_offset_vbytes
+    }
+
+    /// The fee needed to spend the selected coins and to pay back the
+    /// change, if there is any.
+    fn fee_contribution(&self, utxos: &[Utxo], fee_rate_sat_per_vbyte: f32) -> Amount {
+        let inputs_fee = self
+            .selected_utxos(utxos)
+            .filter_map(max_satisfaction_weight)
+            .map(|weight| input_fee(weight, fee_rate_sat_per_vbyte))
+            .sum::<u64>();
+
+        let change_fee = if self.recommended_change() > Amount::ZERO {
+            change_fee(fee_rate_sat_per_vbyte)
+        } else {
+            0
+        };
+
+        Amount::from_sat(inputs_fee + change_fee)
     }
 }
 
 /// Return the maximum weight of a satisfying witness.
 ///
-/// Only supports P2PK, P2PKH and P2WPKH.
-fn max_satisfaction_weight(script_pubkey: &Script) -> Option<usize> {
+/// Supports P2PK, P2PKH and P2WPKH out of the box. Any other kind of
+/// UTXO, such as a P2WSH covenant, needs to come with its own
+/// `satisfaction_weight`.
+fn max_satisfaction_weight(utxo: &Utxo) -> Option<usize> {
+    if let Some(weight) = utxo.satisfaction_weight {
+        return Some(weight);
+    }
+
+    let script_pubkey = &utxo.script_pubkey;
     if script_pubkey.is_p2pk() {
         Some(4 * (1 + 73))
     } else if script_pubkey.is_p2pkh() {
@@ -170,33 +927,416 @@ fn max_satisfaction_weight(script_pubkey: &Script) -> Option<usize> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use elements::{Address, Txid};
+    use elements::{script::Builder, Address, Txid};
     use std::str::FromStr;
 
     #[test]
     fn trivial_coin_selection() {
-        let utxo = Utxo {
-            outpoint: OutPoint {
-                txid: Txid::default(),
-                vout: 0,
-            },
-            value: 100_000_000,
-            script_pubkey: Address::from_str("ert1qxzlkf3t275hwszualaf35spcfuq4s5tqtxj4tl")
-                .unwrap()
-                .script_pubkey(),
-            asset: AssetId::default(),
-        };
+        let utxos = utxos_with_heights(&[(100_000_000, None)]);
 
         let target_amount = Amount::from_sat(90_000_000);
-        let selection = coin_select(vec![utxo.clone()], target_amount, 1.0, Amount::ZERO).unwrap();
+        let selection =
+            coin_select(&utxos, target_amount, 1.0, Amount::ZERO, Options::default()).unwrap();
 
-        assert!(selection.coins.len() == 1);
-        assert!(selection.coins.contains(&utxo));
+        assert_eq!(selection.coins, vec![0]);
 
         assert_eq!(
             selection.selected_amount() - target_amount - selection.recommended_fee,
             selection.recommended_change()
         );
+        assert!(selection.effective_fee_rate_sat_per_vbyte() >= 1.0);
+    }
+
+    #[test]
+    fn prefers_selection_without_change() {
+        let utxos =
+            utxos_with_heights(&[(60_000_000, None), (30_000_000, None), (10_000_000, None)]);
+
+        let target_amount = Amount::from_sat(40_000_000);
+        let selection =
+            coin_select(&utxos, target_amount, 0.0, Amount::ZERO, Options::default()).unwrap();
+
+        assert_eq!(selection.selected_amount(), target_amount);
+        assert_eq!(selection.recommended_change(), Amount::ZERO);
+    }
+
+    #[test]
+    fn selects_fee_asset_to_pay_for_other_assets() {
+        let btc = AssetId::from_slice(&[1u8; 32]).unwrap();
+        let usdt = AssetId::from_slice(&[2u8; 32]).unwrap();
+
+        let mut utxos = utxos_with_heights(&[(100_000_000, None), (100_000_000, None)]);
+        utxos[0].asset = btc;
+        utxos[1].asset = usdt;
+        let usdt_target = Amount::from_sat(50_000_000);
+        let outputs = coin_select_many(
+            &utxos,
+            &[(usdt, usdt_target)],
+            btc,
+            1.0,
+            0,
+            Options::default(),
+        )
+        .unwrap();
+
+        let (_, usdt_output) = outputs.iter().find(|(asset, _)| *asset == usdt).unwrap();
+        let (_, btc_output) = outputs.iter().find(|(asset, _)| *asset == btc).unwrap();
+
+        assert_eq!(usdt_output.coins, vec![1]);
+        assert_eq!(usdt_output.recommended_fee, Amount::ZERO);
+        assert_eq!(btc_output.coins, vec![0]);
+        assert!(btc_output.recommended_fee >= usdt_output.fee_contribution(&utxos, 1.0));
+    }
+
+    #[test]
+    fn selects_fee_of_the_other_outputs() {
+        let utxos = utxos_with_heights(&[(100_000_000, None)]);
+        let target = Amount::from_sat(50_000_000);
+
+        let without_outputs = coin_select_many(
+            &utxos,
+            &[(AssetId::default(), target)],
+            AssetId::default(),
+            1.0,
+            0,
+            Options::default(),
+        )
+        .unwrap();
+        let with_outputs = coin_select_many(
+            &utxos,
+            &[(AssetId::default(), target)],
+            AssetId::default(),
+            1.0,
+            3,
+            Options::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            with_outputs[0].1.recommended_fee - without_outputs[0].1.recommended_fee,
+            Amount::from_sat(3 * avg_vbytes::OUTPUT)
+        );
+    }
+
+    #[test]
+    fn change_below_dust_limit_goes_to_fee() {
+        let utxos = utxos_with_heights(&[(100_000, None)]);
+
+        let target_amount = Amount::from_sat(96_000);
+        let selection = coin_select(
+            &utxos,
+            target_amount,
+            1.0,
+            Amount::ZERO,
+            Options {
+                dust_limit: Amount::from_sat(5_000),
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(selection.recommended_change(), Amount::ZERO);
+        assert_eq!(selection.recommended_fee, Amount::from_sat(4_000));
+    }
+
+    #[test]
+    fn dust_change_of_other_assets_is_returned() {
+        let btc = AssetId::from_slice(&[1u8; 32]).unwrap();
+        let usdt = AssetId::from_slice(&[2u8; 32]).unwrap();
+
+        let mut utxos = utxos_with_heights(&[(100_000_000, None), (100_000, None)]);
+        utxos[0].asset = btc;
+        utxos[1].asset = usdt;
+
+        let outputs = coin_select_many(
+            &utxos,
+            &[(usdt, Amount::from_sat(96_000))],
+            btc,
+            1.0,
+            0,
+            Options {
+                dust_limit: Amount::from_sat(5_000),
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        let (_, usdt_output) = outputs.iter().find(|(asset, _)| *asset == usdt).unwrap();
+
+        assert_eq!(usdt_output.recommended_fee, Amount::ZERO);
+        assert_eq!(usdt_output.recommended_change(), Amount::from_sat(4_000));
+    }
+
+    #[test]
+    fn consolidates_small_utxos_if_fees_are_cheap() {
+        let utxos = utxos_with_heights(&[(100_000_000, None), (10_000, None), (20_000, None)]);
+
+        let target_amount = Amount::from_sat(90_000_000);
+        let selection = coin_select(
+            &utxos,
+            target_amount,
+            1.0,
+            Amount::ZERO,
+            Options {
+                consolidation: Some(Consolidation {
+                    max_fee_rate_sat_per_vbyte: 1.0,
+                    max_utxo_value: Amount::from_sat(50_000),
+                }),
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(selection.coins.len(), 3);
+        assert!(selection.recommended_change() > Amount::ZERO);
+    }
+
+    #[test]
+    fn largest_first_selects_biggest_utxo() {
+        let utxos = utxos_with_heights(&[
+            (10_000_000, Some(1)),
+            (60_000_000, Some(2)),
+            (30_000_000, Some(3)),
+        ]);
+
+        let selection = coin_select(
+            &utxos,
+            Amount::from_sat(20_000_000),
+            1.0,
+            Amount::ZERO,
+            Options {
+                strategy: Strategy::LargestFirst,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(selection.coins, vec![1]);
+    }
+
+    #[test]
+    fn oldest_first_leaves_unconfirmed_utxos_for_last() {
+        let utxos = utxos_with_heights(&[
+            (60_000_000, None),
+            (30_000_000, Some(20)),
+            (30_000_000, Some(10)),
+        ]);
+
+        let selection = coin_select(
+            &utxos,
+            Amount::from_sat(50_000_000),
+            1.0,
+            Amount::ZERO,
+            Options {
+                strategy: Strategy::OldestFirst,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(selection.coins, vec![2, 1]);
+    }
+
+    #[test]
+    fn falls_back_to_fewer_inputs_if_over_the_limit() {
+        let utxos = utxos_with_heights(&[
+            (10_000_000, Some(1)),
+            (10_000_000, Some(2)),
+            (10_000_000, Some(3)),
+            (40_000_000, Some(4)),
+        ]);
+
+        let selection = coin_select(
+            &utxos,
+            Amount::from_sat(25_000_000),
+            1.0,
+            Amount::ZERO,
+            Options {
+                strategy: Strategy::OldestFirst,
+                max_inputs: Some(2),
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(selection.coins, vec![3]);
+    }
+
+    #[test]
+    fn fails_if_target_needs_too_many_inputs() {
+        let utxos =
+            utxos_with_heights(&[(10_000_000, None), (10_000_000, None), (10_000_000, None)]);
+
+        let error = coin_select(
+            &utxos,
+            Amount::from_sat(25_000_000),
+            1.0,
+            Amount::ZERO,
+            Options {
+                max_inputs: Some(2),
+                ..Options::default()
+            },
+        )
+        .unwrap_err();
+
+        assert!(matches!(error, Error::TooManyInputs { max_inputs: 2 }));
+    }
+
+    #[test]
+    fn same_seed_yields_same_selection() {
+        let utxos = utxos_with_heights(&[
+            (10_000_000, None),
+            (20_000_000, None),
+            (30_000_000, None),
+            (40_000_000, None),
+            (50_000_000, None),
+        ]);
+        let options = Options {
+            strategy: Strategy::RandomImprove,
+            seed: Some(42),
+            ..Options::default()
+        };
+
+        let first = coin_select(
+            &utxos,
+            Amount::from_sat(35_000_000),
+            1.0,
+            Amount::ZERO,
+            options,
+        )
+        .unwrap();
+        let second = coin_select(
+            &utxos,
+            Amount::from_sat(35_000_000),
+            1.0,
+            Amount::ZERO,
+            options,
+        )
+        .unwrap();
+
+        assert_eq!(first.coins, second.coins);
+    }
+
+    #[test]
+    fn prefers_utxos_of_a_single_address() {
+        let first_address = Address::from_str("ert1qxzlkf3t275hwszualaf35spcfuq4s5tqtxj4tl")
+            .unwrap()
+            .script_pubkey();
+        let second_address = Address::from_str("ert1q00k0qsmqkep0gkm0klmuuyx6mlz3h4dkwg6ume")
+            .unwrap()
+            .script_pubkey();
+
+        let mut utxos = utxos_with_heights(&[
+            (30_000_000, None),
+            (30_000_000, None),
+            (20_000_000, None),
+            (20_000_000, None),
+        ]);
+        utxos[0].script_pubkey = first_address;
+        utxos[1].script_pubkey = second_address.clone();
+        utxos[2].script_pubkey = second_address.clone();
+        utxos[3].script_pubkey = second_address.clone();
+
+        let selection = coin_select(
+            &utxos,
+            Amount::from_sat(50_000_000),
+            1.0,
+            Amount::ZERO,
+            Options {
+                strategy: Strategy::LargestFirst,
+                group_by_address: true,
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        assert!(selection
+            .selected_utxos(&utxos)
+            .all(|utxo| utxo.script_pubkey == second_address));
+    }
+
+    #[test]
+    fn only_selects_utxos_with_enough_confirmations() {
+        let utxos = utxos_with_heights(&[
+            (60_000_000, None),
+            (50_000_000, Some(105)),
+            (30_000_000, Some(100)),
+        ]);
+
+        let selection = coin_select(
+            &utxos,
+            Amount::from_sat(20_000_000),
+            1.0,
+            Amount::ZERO,
+            Options {
+                strategy: Strategy::LargestFirst,
+                min_confirmations: Some(MinConfirmations {
+                    confirmations: 6,
+                    tip_height: 105,
+                }),
+                ..Options::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(selection.coins, vec![2]);
+    }
+
+    #[test]
+    fn accounts_for_weight_of_p2wsh_inputs() {
+        let p2wsh = Builder::new()
+            .push_int(0)
+            .push_slice(&[0u8; 32])
+            .into_script();
+
+        let mut covenant_utxo = utxos_with_heights(&[(1_000, None)]).remove(0);
+        covenant_utxo.script_pubkey = p2wsh;
+
+        let error = coin_select(
+            &[covenant_utxo.clone()],
+            Amount::from_sat(100),
+            1.0,
+            Amount::ZERO,
+            Options::default(),
+        )
+        .unwrap_err();
+        assert!(matches!(error, Error::InsufficientFunds { .. }));
+
+        covenant_utxo.satisfaction_weight = Some(2_000);
+        let selection = coin_select(
+            &[covenant_utxo],
+            Amount::from_sat(100),
+            1.0,
+            Amount::ZERO,
+            Options::default(),
+        )
+        .unwrap();
+        let input_vbytes = ((TXIN_BASE_WEIGHT + 2_000) as f32 / 4.0).ceil() as u64;
+
+        assert_eq!(selection.coins, vec![0]);
+        assert_eq!(selection.estimated_vbytes, input_vbytes);
+    }
+
+    fn utxos_with_heights(values_and_heights: &[(u64, Option<u64>)]) -> Vec<Utxo> {
+        let script_pubkey = Address::from_str("ert1qxzlkf3t275hwszualaf35spcfuq4s5tqtxj4tl")
+            .unwrap()
+            .script_pubkey();
+
+        values_and_heights
+            .iter()
+            .enumerate()
+            .map(|(vout, (value, height))| Utxo {
+                outpoint: OutPoint {
+                    txid: Txid::default(),
+                    vout: vout as u32,
+                },
+                value: *value,
+                script_pubkey: script_pubkey.clone(),
+                asset: AssetId::default(),
+                height: *height,
+                satisfaction_weight: None,
+            })
+            .collect()
     }
 }
 
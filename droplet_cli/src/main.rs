@@ -0,0 +1,215 @@
+use anyhow::{bail, Context, Result};
+use bobtimus_client::Client;
+use elements::{
+    bitcoin::{Amount, Denomination},
+    AssetId,
+};
+use network_config::Network;
+use reqwest::Url;
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+use structopt::StructOpt;
+use wallet::native::{self, NativeWallet, TradeSide};
+
+/// Where the password of the wallet is read from, so that it does not
+/// end up in the shell history.
+const PASSWORD_VAR: &str = "DROPLET_PASSWORD";
+
+#[derive(structopt::StructOpt, Debug)]
+#[structopt(
+    name = "droplet-cli",
+    about = "Swap and borrow with a bobtimus instance from the command line"
+)]
+struct Opts {
+    /// Directory the wallet is kept in
+    #[structopt(long = "data-dir", parse(from_os_str))]
+    data_dir: Option<PathBuf>,
+    #[structopt(default_value = "liquid", long = "network")]
+    network: Network,
+    /// Esplora instance to use, defaults to the public one of the
+    /// network
+    #[structopt(long = "esplora")]
+    esplora_url: Option<Url>,
+    /// Asset id of L-BTC, defaults to the one of the network
+    #[structopt(long = "btc-asset-id")]
+    btc_asset_id: Option<AssetId>,
+    /// Asset id of L-USDt, defaults to the one of the network
+    #[structopt(long = "usdt-asset-id")]
+    usdt_asset_id: Option<AssetId>,
+    #[structopt(default_value = "http://127.0.0.1:3030", long = "bobtimus")]
+    bobtimus_url: Url,
+    /// API key of the bobtimus instance, if it requires one
+    #[structopt(long = "api-key")]
+    api_key: Option<String>,
+    #[structopt(default_value = "droplet", long = "wallet-name")]
+    wallet_name: String,
+    /// Sign swaps and loans without asking for confirmation
+    #[structopt(short = "y", long = "yes")]
+    yes: bool,
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(structopt::StructOpt, Debug)]
+enum Command {
+    /// Create a new wallet
+    Create,
+    /// Print the address of the wallet
+    Address,
+    /// Print the balance of the wallet per asset
+    Balance,
+    /// Sell L-BTC for L-USDt
+    Sell {
+        /// Amount of L-BTC to sell
+        #[structopt(parse(try_from_str = parse_amount))]
+        btc: Amount,
+    },
+    /// Buy L-BTC with L-USDt
+    Buy {
+        /// Amount of L-USDt to spend
+        #[structopt(parse(try_from_str = parse_amount))]
+        usdt: Amount,
+    },
+    /// Borrow L-USDt against L-BTC
+    Borrow {
+        /// Amount of L-BTC to lock up as collateral
+        #[structopt(parse(try_from_str = parse_amount))]
+        collateral: Amount,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let opts = Opts::from_args();
+
+    let data_dir = match opts.data_dir {
+        Some(data_dir) => data_dir,
+        None => default_data_dir()?,
+    };
+    env::set_var("WALLET_STORAGE_DIR", &data_dir);
+
+    let network = opts.network;
+    let esplora_url = match opts.esplora_url {
+        Some(esplora_url) => esplora_url,
+        None => network
+            .esplora_url()
+            .with_context(|| format!("--esplora is required on {}", network))?
+            .parse()?,
+    };
+    let btc_asset_id = opts
+        .btc_asset_id
+        .or_else(|| network.btc_asset_id())
+        .with_context(|| format!("--btc-asset-id is required on {}", network))?;
+    let usdt_asset_id = opts
+        .usdt_asset_id
+        .or_else(|| network.usdt_asset_id())
+        .with_context(|| format!("--usdt-asset-id is required on {}", network))?;
+    native::configure(network, &esplora_url, btc_asset_id, usdt_asset_id)?;
+
+    let password = env::var(PASSWORD_VAR)
+        .with_context(|| format!("{} must be set to the password of the wallet", PASSWORD_VAR))?;
+
+    if let Command::Create = opts.command {
+        let wallet = NativeWallet::create(opts.wallet_name, password).await?;
+        println!("Created wallet in {}", data_dir.display());
+        println!("{}", wallet.address().await?);
+
+        return Ok(());
+    }
+
+    let wallet = NativeWallet::load(opts.wallet_name, password).await?;
+    let bobtimus = match opts.api_key {
+        Some(api_key) => Client::new(opts.bobtimus_url).with_api_key(api_key),
+        None => Client::new(opts.bobtimus_url),
+    };
+
+    match opts.command {
+        Command::Create => unreachable!("handled above"),
+        Command::Address => println!("{}", wallet.address().await?),
+        Command::Balance => {
+            for balance in wallet.balances().await? {
+                println!("{} {}", balance.value, balance.ticker);
+            }
+        }
+        Command::Sell { btc } => {
+            let payload = wallet.sell_payload(btc).await?;
+            let transaction = bobtimus.create_sell_swap(&payload).await?;
+            swap(&wallet, transaction, opts.yes).await?;
+        }
+        Command::Buy { usdt } => {
+            let payload = wallet.buy_payload(usdt).await?;
+            let transaction = bobtimus.create_buy_swap(&payload).await?;
+            swap(&wallet, transaction, opts.yes).await?;
+        }
+        Command::Borrow { collateral } => {
+            let loan_request = wallet.loan_request(collateral).await?;
+            let loan_response = bobtimus.request_loan(&loan_request).await?;
+
+            let details = wallet.extract_loan(loan_response).await?;
+            print_side("Collateral", &details.collateral);
+            print_side("Principal", &details.principal);
+            println!("Repayment: {}", details.principal_repayment);
+            println!("Term: {}", details.term);
+            confirm(opts.yes)?;
+
+            let transaction = wallet.sign_loan().await?;
+            let txid = bobtimus.finalize_loan(&transaction).await?;
+            println!("{}", txid);
+        }
+    }
+
+    Ok(())
+}
+
+async fn swap(wallet: &NativeWallet, transaction: elements::Transaction, yes: bool) -> Result<()> {
+    let trade = wallet.extract_trade(transaction.clone()).await?;
+    print_side("Sell", &trade.sell);
+    print_side("Buy", &trade.buy);
+    confirm(yes)?;
+
+    let txid = wallet.sign_and_send_swap(transaction).await?;
+    println!("{}", txid);
+
+    Ok(())
+}
+
+fn print_side(label: &str, side: &TradeSide) {
+    println!(
+        "{}: {} {} (balance {} -> {})",
+        label, side.amount, side.ticker, side.balance_before, side.balance_after
+    );
+}
+
+/// Ask the user to agree to the terms printed before, unless they
+/// already did on the command line.
+fn confirm(yes: bool) -> Result<()> {
+    if yes {
+        return Ok(());
+    }
+
+    print!("Sign? [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    if !matches!(answer.trim(), "y" | "Y" | "yes") {
+        bail!("aborted")
+    }
+
+    Ok(())
+}
+
+fn parse_amount(s: &str) -> Result<Amount> {
+    let amount = Amount::from_str_in(s, Denomination::Bitcoin)?;
+
+    Ok(amount)
+}
+
+fn default_data_dir() -> Result<PathBuf> {
+    directories::ProjectDirs::from("", "", "droplet-cli")
+        .map(|proj_dirs| proj_dirs.data_dir().to_path_buf())
+        .context("Could not generate default system data-dir dir path")
+}
@@ -20,6 +20,10 @@ module.exports = function override(config, env) {
         new WasmPackPlugin({
             crateDirectory: path.resolve(__dirname, "wallet/"),
             outDir: path.resolve(__dirname, "src/wallet"),
+            // release builds leave out logging and use a smaller allocator
+            extraArgs: env === "production"
+                ? "-- --no-default-features --features console_error_panic_hook,wee_alloc"
+                : "",
         }),
         // delete the warning about "Critical dependency: the request of a dependency is an expression" in the generated binding code
         new webpack.ContextReplacementPlugin(
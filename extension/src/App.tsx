@@ -9,12 +9,15 @@ import {
     getBalances,
     getLoanToSign,
     getOpenLoans,
+    getOriginToAuthorize,
     getSwapToSign,
     getWalletStatus,
     rejectLoan,
+    rejectOrigin,
     rejectSwap,
 } from "./background-proxy";
 import AddressQr from "./components/AddressQr";
+import AuthorizeOrigin from "./components/AuthorizeOrigin";
 import WalletBalances from "./components/Balances";
 import ConfirmLoan from "./components/ConfirmLoan";
 import ConfirmSwap from "./components/ConfirmSwap";
@@ -30,12 +33,14 @@ const App = () => {
     const swapToSignHook = useAsync({ promiseFn: getSwapToSign });
     const loanToSignHook = useAsync({ promiseFn: getLoanToSign });
     const openLoansHook = useAsync({ promiseFn: getOpenLoans });
+    const originToAuthorizeHook = useAsync({ promiseFn: getOriginToAuthorize });
 
     let { data: walletStatus, reload: reloadWalletStatus, error } = walletStatusHook;
     let { data: balanceUpdates, reload: reloadWalletBalances } = walletBalanceHook;
     let { data: swapToSign, reload: reloadSwapToSign } = swapToSignHook;
     let { data: loanToSign, reload: reloadLoanToSign } = loanToSignHook;
     let { data: openLoans, reload: reloadOpenLoans } = openLoansHook;
+    let { data: originToAuthorize, reload: reloadOriginToAuthorize } = originToAuthorizeHook;
 
     const refreshAll = () => {
         reloadWalletBalances();
@@ -43,6 +48,7 @@ const App = () => {
         reloadSwapToSign();
         reloadLoanToSign();
         reloadOpenLoans();
+        reloadOriginToAuthorize();
     };
 
     // we want to either sign a swap or the loan but not both:
@@ -50,6 +56,8 @@ const App = () => {
     if (!swapToSign && loanToSign) {
         signLoan = true;
     }
+    // and only ask to authorize a page when there is nothing to sign
+    const authorize = !signLoan && !swapToSign && originToAuthorize;
 
     return (
         <ChakraProvider theme={theme}>
@@ -62,9 +70,10 @@ const App = () => {
                 {walletStatus?.status === Status.Loaded
                     && <>
                         {balanceUpdates && <WalletBalances balanceUpdates={balanceUpdates} />}
-                        {!signLoan && !swapToSign && <AddressQr />}
-                        {!signLoan && !swapToSign && <WithdrawAll />}
-                        {!signLoan && !swapToSign && <OpenLoans openLoans={openLoans} onRepayed={refreshAll} />}
+                        {!signLoan && !swapToSign && !authorize && <AddressQr />}
+                        {!signLoan && !swapToSign && !authorize && <WithdrawAll />}
+                        {!signLoan && !swapToSign && !authorize
+                            && <OpenLoans openLoans={openLoans} onRepayed={refreshAll} />}
 
                         {swapToSign && <ConfirmSwap
                             onCancel={async (tabId: number) => {
@@ -83,6 +92,15 @@ const App = () => {
                                 onSuccess={refreshAll}
                                 loanToSign={loanToSign!}
                             />}
+                        {authorize
+                            && <AuthorizeOrigin
+                                onCancel={async (tabId: number) => {
+                                    await rejectOrigin(tabId);
+                                    refreshAll();
+                                }}
+                                onSuccess={refreshAll}
+                                originToAuthorize={originToAuthorize!}
+                            />}
                     </>}
                 {walletStatus?.status === Status.NotLoaded
                     && <>
@@ -104,7 +122,7 @@ const App = () => {
                 {!walletStatus && error
                     && <Center>
                         Something is wrong. Can you catch the <FontAwesomeIcon size="7x" icon={faBug} />?
-                        {error}
+                        {error.message}
                     </Center>}
             </Box>
         </ChakraProvider>
@@ -0,0 +1,19 @@
+import { authorizedOrigins, authorizeOrigin, isAuthorized, revokeOrigin } from "./authorizedOrigins";
+
+beforeEach(() => localStorage.clear());
+
+test("origins are only authorized until they are revoked", () => {
+    expect(isAuthorized("https://example.com")).toBe(false);
+
+    authorizeOrigin("https://example.com");
+    authorizeOrigin("https://example.com");
+    authorizeOrigin("https://example.org");
+
+    expect(authorizedOrigins()).toEqual(["https://example.com", "https://example.org"]);
+    expect(isAuthorized("https://example.com")).toBe(true);
+
+    revokeOrigin("https://example.com");
+
+    expect(isAuthorized("https://example.com")).toBe(false);
+    expect(isAuthorized("https://example.org")).toBe(true);
+});
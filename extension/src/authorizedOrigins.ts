@@ -0,0 +1,27 @@
+// Origins the user allowed to see the wallet's UTXOs. They are kept in
+// local storage next to the other settings, so that they can be revoked
+// on the options page.
+const AUTHORIZED_ORIGINS = "AUTHORIZED_ORIGINS";
+
+export function authorizedOrigins(): string[] {
+    const stored = localStorage.getItem(AUTHORIZED_ORIGINS);
+
+    return stored ? JSON.parse(stored) : [];
+}
+
+export function isAuthorized(origin: string): boolean {
+    return authorizedOrigins().includes(origin);
+}
+
+export function authorizeOrigin(origin: string) {
+    if (!isAuthorized(origin)) {
+        localStorage.setItem(AUTHORIZED_ORIGINS, JSON.stringify([...authorizedOrigins(), origin]));
+    }
+}
+
+export function revokeOrigin(origin: string) {
+    localStorage.setItem(
+        AUTHORIZED_ORIGINS,
+        JSON.stringify(authorizedOrigins().filter(authorized => authorized !== origin)),
+    );
+}
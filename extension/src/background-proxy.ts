@@ -1,5 +1,14 @@
 import { browser } from "webextension-polyfill-ts";
-import { Address, BalanceUpdate, LoanDetails, LoanToSign, SwapToSign, Txid, WalletStatus } from "./models";
+import {
+    Address,
+    BalanceUpdate,
+    LoanDetails,
+    LoanToSign,
+    OriginToAuthorize,
+    SwapToSign,
+    Txid,
+    WalletStatus,
+} from "./models";
 
 const proxy = browser.extension.getBackgroundPage();
 
@@ -38,6 +47,21 @@ export async function rejectSwap(tabId: number): Promise<void> {
     return proxy.rejectSwap(tabId);
 }
 
+export async function getOriginToAuthorize(): Promise<OriginToAuthorize | undefined> {
+    // @ts-ignore
+    return proxy.getOriginToAuthorize();
+}
+
+export async function authorizeOrigin(tabId: number): Promise<void> {
+    // @ts-ignore
+    return proxy.authorizeOrigin(tabId);
+}
+
+export async function rejectOrigin(tabId: number): Promise<void> {
+    // @ts-ignore
+    return proxy.rejectOrigin(tabId);
+}
+
 export async function withdrawAll(address: string): Promise<Txid> {
     // @ts-ignore
     return proxy.withdrawAll(address);
@@ -1,15 +1,48 @@
 import Debug from "debug";
-import { browser } from "webextension-polyfill-ts";
-import { Direction, Message, MessageKind } from "../messages";
-import { LoanDetails, LoanToSign, SwapToSign } from "../models";
+import { browser, Runtime } from "webextension-polyfill-ts";
+import { authorizedOrigins, authorizeOrigin, isAuthorized } from "../authorizedOrigins";
+import {
+    checkAssetAmount,
+    decodeTx,
+    Direction,
+    encodeTx,
+    ErrorCode,
+    EventKind,
+    formatAmount,
+    isSupportedVersion,
+    PORT_NAME,
+    PROTOCOL_VERSION,
+    Request,
+    RequestKind,
+    Response,
+    RESPONSE_KIND,
+    ResponseKind,
+    RpcError,
+    toRpcError,
+    unsupportedVersionError,
+    WalletEvent,
+} from "../messages";
+import {
+    Feature,
+    LoanDetails,
+    LoanOffer,
+    LoanToSign,
+    OriginToAuthorize,
+    PageWalletStatus,
+    Status,
+    SwapToSign,
+    WalletState,
+} from "../models";
 import {
     createWallet,
     extractLoan,
     extractTrade,
     getAddress,
     getBalances,
+    getNetwork,
     getOpenLoans,
     getPastTransactions,
+    getUtxos,
     makeBuyCreateSwapPayload,
     makeLoanRequestPayload,
     makeSellCreateSwapPayload,
@@ -22,7 +55,9 @@ import {
 } from "../wasmProxy";
 
 // TODO: Is this global or do we need one per file?
-Debug.enable("*");
+if (process.env.NODE_ENV !== "production") {
+    Debug.enable("*");
+}
 const debug = Debug("background");
 const error = Debug("background:error");
 
@@ -32,86 +67,251 @@ loadSettings();
 debug("Hello world from background script");
 
 const walletName = "demo";
+
+// How often we look for changes of the wallet's state we did not cause
+// ourselves, most importantly transactions confirming.
+const WALLET_STATE_POLL_INTERVAL_MS = 15_000;
 var swapToSign: SwapToSign | undefined;
+var receivedLoan: { details: LoanDetails; tabId: number } | undefined;
 var loanToSign: LoanToSign | undefined;
+var originToAuthorize: OriginToAuthorize | undefined;
+var walletState: WalletState | undefined;
+// The authorized origins at the time we last pushed the wallet's state,
+// which tells each tab whether it is authorized.
+var walletStateOrigins: string[] = [];
+
+// The port of the content script of every tab, through which its requests
+// are answered and anything else is pushed to its page.
+const ports = new Map<number, Runtime.Port>();
+
+browser.runtime.onConnect.addListener(port => {
+    const tabId = port.sender?.tab?.id;
+    if (port.name !== PORT_NAME || tabId === undefined) {
+        return;
+    }
+
+    debug(`Tab ${tabId} connected`);
+    ports.set(tabId, port);
+    if (walletState) {
+        port.postMessage(walletStateChanged(walletState, port));
+    }
+
+    port.onMessage.addListener(async (msg: Request<any>) => {
+        const message = await handleRequest(msg, port.sender!);
+        if (message) {
+            port.postMessage(message);
+        }
+    });
+    port.onDisconnect.addListener(() => {
+        debug(`Tab ${tabId} disconnected`);
+        if (ports.get(tabId) === port) {
+            ports.delete(tabId);
+        }
+    });
+});
 
-browser.runtime.onMessage.addListener(async (msg: Message<any>, sender) => {
+async function handleRequest(
+    msg: Request<any>,
+    sender: Runtime.MessageSender,
+): Promise<Response<any> | undefined> {
     debug(
         `Received: "${JSON.stringify(msg)}" from tab ${sender.tab?.id}`,
     );
 
-    if (msg.direction === Direction.ToBackground) {
-        let message;
-        switch (msg.kind) {
-            case MessageKind.WalletStatusRequest:
-                message = await call_wallet(() => walletStatus(walletName), MessageKind.WalletStatusResponse);
-                break;
-            case MessageKind.SellRequest:
-                message = await call_wallet(
-                    async () => await makeSellCreateSwapPayload(walletName, msg.payload),
-                    MessageKind.SellResponse,
-                );
-                break;
-            case MessageKind.BuyRequest:
-                message = await call_wallet(
-                    async () => await makeBuyCreateSwapPayload(walletName, msg.payload),
-                    MessageKind.BuyResponse,
-                );
-                break;
-            case MessageKind.AddressRequest:
-                message = await call_wallet(
-                    async () => await getAddress(walletName),
-                    MessageKind.AddressResponse,
-                );
-                break;
-            case MessageKind.LoanRequest:
-                message = await call_wallet(
-                    async () => await makeLoanRequestPayload(walletName, msg.payload),
-                    MessageKind.LoanResponse,
-                );
-                break;
-            case MessageKind.SignAndSendSwap:
-                try {
-                    const txHex = msg.payload;
-                    const decoded = await extractTrade(walletName, txHex);
-                    swapToSign = { txHex, decoded, tabId: sender.tab!.id! };
-                    updateBadge();
-                } catch (e) {
-                    error(e);
-                    message = { kind: MessageKind.SwapTxid, direction: Direction.ToPage, error: e };
-                }
-                break;
-            case MessageKind.SignLoan:
-                try {
+    if (msg.direction !== Direction.ToBackground) {
+        return;
+    }
+
+    if (!isSupportedVersion(msg)) {
+        error(`Rejecting message of unsupported version ${msg.version}`);
+        return response(ResponseKind.UnsupportedVersion, msg.requestId, undefined, unsupportedVersionError(msg));
+    }
+
+    const responseKind = RESPONSE_KIND[msg.kind];
+    switch (msg.kind) {
+        case RequestKind.WalletStatus:
+            return call_wallet(() => pageWalletStatus(originOf(sender)), responseKind, msg.requestId);
+        case RequestKind.Sell:
+            return call_wallet(
+                () => makeSellCreateSwapPayload(walletName, walletAmount(msg.payload, "LBTC_ASSET_ID")),
+                responseKind,
+                msg.requestId,
+            );
+        case RequestKind.Buy:
+            return call_wallet(
+                () => makeBuyCreateSwapPayload(walletName, walletAmount(msg.payload, "LUSDT_ASSET_ID")),
+                responseKind,
+                msg.requestId,
+            );
+        case RequestKind.Address:
+            return call_wallet(() => getAddress(walletName), responseKind, msg.requestId);
+        case RequestKind.GetLoanOffer:
+            return call_wallet(() => fetchLoanOffer(sender.url!), responseKind, msg.requestId);
+        case RequestKind.MakeLoanRequest:
+            return call_wallet(
+                () => makeLoanRequestPayload(walletName, walletAmount(msg.payload, "LBTC_ASSET_ID")),
+                responseKind,
+                msg.requestId,
+            );
+        case RequestKind.LoanResponseReceived:
+            return call_wallet(
+                async () => {
                     const details = await extractLoan(walletName, msg.payload);
-                    loanToSign = { details, tabId: sender.tab!.id! };
-                    updateBadge();
-                } catch (e) {
-                    error(e);
-                    message = { kind: MessageKind.SignedLoan, direction: Direction.ToPage, error: e };
-                }
-                break;
-        }
-        return message;
+                    receivedLoan = { details, tabId: sender.tab!.id! };
+                    return details;
+                },
+                responseKind,
+                msg.requestId,
+            );
+        case RequestKind.SignAndSendSwap:
+            try {
+                const txHex = decodeTx(msg.payload);
+                const decoded = await extractTrade(walletName, txHex);
+                swapToSign = { txHex, decoded, tabId: sender.tab!.id!, requestId: msg.requestId };
+                updateBadge();
+            } catch (e) {
+                error(e);
+                return response(responseKind, msg.requestId, undefined, toRpcError(e));
+            }
+            // answered once the user confirmed or rejected the swap
+            return;
+        case RequestKind.SignLoan:
+            if (!receivedLoan || receivedLoan.tabId !== sender.tab!.id!) {
+                return response(responseKind, msg.requestId, undefined, {
+                    code: ErrorCode.InvalidRequest,
+                    message: "No loan response received",
+                });
+            }
+            loanToSign = { ...receivedLoan, requestId: msg.requestId };
+            receivedLoan = undefined;
+            updateBadge();
+            // answered once the user confirmed or rejected the loan
+            return;
+        case RequestKind.Utxos:
+            if (isAuthorized(originOf(sender))) {
+                return call_wallet(() => getUtxos(walletName), responseKind, msg.requestId);
+            }
+            originToAuthorize = { origin: originOf(sender), tabId: sender.tab!.id!, requestId: msg.requestId };
+            updateBadge();
+            // answered once the user authorized the origin or not
+            return;
+        case RequestKind.Network:
+            return call_wallet(() => getNetwork(), responseKind, msg.requestId);
     }
-});
+}
 
-async function call_wallet<T>(wallet_fn: () => Promise<T>, kind: MessageKind): Promise<Message<T | undefined>> {
-    let payload;
-    let err;
+// Push the wallet's state to all tabs if it changed since we last did.
+async function broadcastWalletState() {
+    let state: WalletState;
     try {
-        payload = await wallet_fn();
+        // whether a page is authorized is filled in per tab
+        const status = await pageWalletStatus(undefined);
+        const balances = status.status === Status.Loaded ? await getBalances(walletName) : [];
+        state = { status, balances };
     } catch (e) {
         error(e);
-        err = e;
+        return;
+    }
+
+    const origins = authorizedOrigins();
+    if (
+        JSON.stringify(state) === JSON.stringify(walletState)
+        && JSON.stringify(origins) === JSON.stringify(walletStateOrigins)
+    ) {
+        return;
+    }
+
+    walletState = state;
+    walletStateOrigins = origins;
+    ports.forEach(port => port.postMessage(walletStateChanged(state, port)));
+}
+
+setInterval(() => {
+    if (ports.size > 0) {
+        broadcastWalletState();
+    }
+}, WALLET_STATE_POLL_INTERVAL_MS);
+
+function walletStateChanged(state: WalletState, port: Runtime.Port): WalletEvent<WalletState> {
+    return {
+        kind: EventKind.WalletStateChanged,
+        direction: Direction.ToPage,
+        version: PROTOCOL_VERSION,
+        payload: { ...state, status: { ...state.status, authorized: isAuthorized(originOf(port.sender!)) } },
+    };
+}
+
+// Messages to tabs which went away in the meantime are dropped.
+function sendToTab(tabId: number, message: Response<any>) {
+    const port = ports.get(tabId);
+    if (!port) {
+        debug(`Dropping message to disconnected tab ${tabId}: ${JSON.stringify(message)}`);
+        return;
     }
 
-    return { kind, direction: Direction.ToPage, payload, error: err };
+    port.postMessage(message);
+}
+
+// The status as the page of `origin` sees it.
+async function pageWalletStatus(origin: string | undefined): Promise<PageWalletStatus> {
+    return {
+        ...await walletStatus(walletName),
+        network: await getNetwork(),
+        version: browser.runtime.getManifest().version,
+        features: [Feature.Swap, Feature.Loan],
+        authorized: origin !== undefined && isAuthorized(origin),
+    };
+}
+
+function originOf(sender: Runtime.MessageSender): string {
+    return new URL(sender.url!).origin;
+}
+
+// Check that the `payload` is an amount of the asset configured under
+// `assetSetting` and format it the way the wallet expects.
+function walletAmount(payload: any, assetSetting: string): string {
+    const { amount } = checkAssetAmount(payload, localStorage.getItem(assetSetting)!);
+
+    // all assets we trade have 8 decimals
+    return formatAmount(amount, 8);
+}
+
+// Lenders serve the pages borrowing from them.
+async function fetchLoanOffer(pageUrl: string): Promise<LoanOffer> {
+    const res = await fetch(`${new URL(pageUrl).origin}/api/loan/offer`, {
+        headers: {
+            Accept: "application/json",
+        },
+    });
+
+    if (res.status !== 200) {
+        throw new Error("failed to get loan offer");
+    }
+
+    return await res.json();
+}
+
+function response<T>(kind: ResponseKind, requestId: string, payload: T, error?: RpcError): Response<T> {
+    return { kind, direction: Direction.ToPage, version: PROTOCOL_VERSION, requestId, payload, error };
+}
+
+async function call_wallet<T>(
+    wallet_fn: () => Promise<T>,
+    kind: ResponseKind,
+    requestId: string,
+): Promise<Response<T>> {
+    try {
+        return response(kind, requestId, await wallet_fn());
+    } catch (e) {
+        error(e);
+        return response(kind, requestId, undefined, toRpcError(e));
+    }
 }
 
 // @ts-ignore
 window.createWallet = async (password: string) => {
-    return createWallet(walletName, password);
+    await createWallet(walletName, password);
+    broadcastWalletState();
 };
 // @ts-ignore
 window.getWalletStatus = async () => {
@@ -119,7 +319,8 @@ window.getWalletStatus = async () => {
 };
 // @ts-ignore
 window.unlockWallet = async (password: string) => {
-    return unlockWallet(walletName, password);
+    await unlockWallet(walletName, password);
+    broadcastWalletState();
 };
 // @ts-ignore
 window.getBalances = async () => {
@@ -135,23 +336,31 @@ window.getSwapToSign = async () => {
 };
 // @ts-ignore
 window.signAndSendSwap = async (txHex: string, tabId: number) => {
-    let payload;
-    let err;
+    const requestId = swapToSign!.requestId;
 
+    let message;
     try {
-        payload = await signAndSendSwap(walletName, txHex);
+        const txid = await signAndSendSwap(walletName, txHex);
+        message = response(ResponseKind.SwapTxid, requestId, txid);
     } catch (e) {
         error(e);
-        err = e;
+        message = response(ResponseKind.SwapTxid, requestId, undefined, toRpcError(e));
     }
 
-    browser.tabs.sendMessage(tabId, { direction: Direction.ToPage, kind: MessageKind.SwapTxid, payload, error: err });
+    sendToTab(tabId, message);
     swapToSign = undefined;
     updateBadge();
+    broadcastWalletState();
 };
 // @ts-ignore
 window.rejectSwap = (tabId: number) => {
-    browser.tabs.sendMessage(tabId, { direction: Direction.ToPage, kind: MessageKind.SwapRejected });
+    sendToTab(
+        tabId,
+        response(ResponseKind.SwapTxid, swapToSign!.requestId, undefined, {
+            code: ErrorCode.UserRejected,
+            message: "User rejected swap",
+        }),
+    );
     swapToSign = undefined;
     updateBadge();
 };
@@ -166,29 +375,67 @@ window.signLoan = async (tabId: number) => {
     // storage. It would be better to send around the swap ID to check
     // that the wallet is signing the same transaction the user has authorised
 
-    let payload;
-    let err;
+    const requestId = loanToSign!.requestId;
 
+    let message;
     try {
-        payload = await signLoan(walletName);
+        const loanTx = await signLoan(walletName);
+        message = response(ResponseKind.SignedLoan, requestId, encodeTx(loanTx));
     } catch (e) {
         error(e);
-        err = e;
+        message = response(ResponseKind.SignedLoan, requestId, undefined, toRpcError(e));
     }
 
-    browser.tabs.sendMessage(tabId, { direction: Direction.ToPage, kind: MessageKind.SignedLoan, payload, error: err });
+    sendToTab(tabId, message);
     loanToSign = undefined;
     updateBadge();
+    broadcastWalletState();
 };
 // @ts-ignore
 window.rejectLoan = (tabId: number) => {
-    browser.tabs.sendMessage(tabId, { direction: Direction.ToPage, kind: MessageKind.LoanRejected });
+    sendToTab(
+        tabId,
+        response(ResponseKind.SignedLoan, loanToSign!.requestId, undefined, {
+            code: ErrorCode.UserRejected,
+            message: "User rejected loan",
+        }),
+    );
     loanToSign = undefined;
     updateBadge();
 };
 // @ts-ignore
+window.getOriginToAuthorize = () => {
+    return originToAuthorize;
+};
+// @ts-ignore
+window.authorizeOrigin = async (tabId: number) => {
+    const { origin, requestId } = originToAuthorize!;
+
+    authorizeOrigin(origin);
+    const message = await call_wallet(() => getUtxos(walletName), ResponseKind.Utxos, requestId);
+
+    sendToTab(tabId, message);
+    originToAuthorize = undefined;
+    updateBadge();
+    broadcastWalletState();
+};
+// @ts-ignore
+window.rejectOrigin = (tabId: number) => {
+    sendToTab(
+        tabId,
+        response(ResponseKind.Utxos, originToAuthorize!.requestId, undefined, {
+            code: ErrorCode.Unauthorized,
+            message: `User did not authorize ${originToAuthorize!.origin}`,
+        }),
+    );
+    originToAuthorize = undefined;
+    updateBadge();
+};
+// @ts-ignore
 window.withdrawAll = async (address: string) => {
-    return withdrawAll(walletName, address);
+    const txid = await withdrawAll(walletName, address);
+    broadcastWalletState();
+    return txid;
 };
 // @ts-ignore
 window.getOpenLoans = async (): LoanDetails[] => {
@@ -196,7 +443,8 @@ window.getOpenLoans = async (): LoanDetails[] => {
 };
 // @ts-ignore
 window.repayLoan = async (txid: string): void => {
-    return repayLoan(walletName, txid);
+    await repayLoan(walletName, txid);
+    broadcastWalletState();
 };
 // @ts-ignore
 window.getPastTransactions = async (): Txid[] => {
@@ -207,6 +455,7 @@ function updateBadge() {
     let count = 0;
     if (loanToSign) count++;
     if (swapToSign) count++;
+    if (originToAuthorize) count++;
     browser.browserAction.setBadgeText(
         { text: (count === 0 ? null : count.toString()) },
     );
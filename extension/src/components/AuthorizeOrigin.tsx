@@ -0,0 +1,56 @@
+import { Box, Button, Heading, Text } from "@chakra-ui/react";
+import React from "react";
+import { useAsync } from "react-async";
+import { authorizeOrigin } from "../background-proxy";
+import { OriginToAuthorize } from "../models";
+
+interface AuthorizeOriginProps {
+    onCancel: (tabId: number) => void;
+    onSuccess: () => void;
+    originToAuthorize: OriginToAuthorize;
+}
+
+export default function AuthorizeOrigin(
+    { onCancel, onSuccess, originToAuthorize }: AuthorizeOriginProps,
+) {
+    let { isPending, run } = useAsync({
+        deferFn: async () => {
+            await authorizeOrigin(originToAuthorize.tabId);
+            onSuccess();
+        },
+    });
+
+    return (<Box>
+        <form
+            onSubmit={async e => {
+                e.preventDefault();
+                run();
+            }}
+            data-cy="authorize-origin-form"
+        >
+            <Heading>Authorize Site</Heading>
+            <Box h="80px" p="1">
+                <Text>
+                    {originToAuthorize.origin} wants to see the coins in your wallet. You can revoke this in the
+                    settings.
+                </Text>
+            </Box>
+
+            <Button
+                variant="secondary"
+                mr={3}
+                onClick={() => onCancel(originToAuthorize.tabId)}
+            >
+                Cancel
+            </Button>
+            <Button
+                type="submit"
+                variant="primary"
+                isLoading={isPending}
+                data-cy="data-cy-authorize-origin-button"
+            >
+                Authorize
+            </Button>
+        </form>
+    </Box>);
+}
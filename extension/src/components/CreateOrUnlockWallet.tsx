@@ -6,7 +6,9 @@ import { useAsync } from "react-async";
 import { createWallet, unlockWallet } from "../background-proxy";
 import { Status } from "../models";
 
-Debug.enable("*");
+if (process.env.NODE_ENV !== "production") {
+    Debug.enable("*");
+}
 const debug = Debug("unlock-wallet");
 
 type CreateOrUnlockWalletProps = {
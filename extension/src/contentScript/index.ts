@@ -1,48 +1,87 @@
 import Debug from "debug";
 import { browser } from "webextension-polyfill-ts";
-import { Direction, Message } from "../messages";
+import {
+    Direction,
+    disconnectedError,
+    Message,
+    PORT_NAME,
+    PROTOCOL_VERSION,
+    Request,
+    Response,
+    RESPONSE_KIND,
+    WalletEvent,
+} from "../messages";
 
-Debug.enable("*");
+if (process.env.NODE_ENV !== "production") {
+    Debug.enable("*");
+}
 const debug = Debug("content");
 
 debug("Hello world from content script");
 
-async function forwardToBackground(message: Message<any>): Promise<Message<any>> {
-    try {
-        return await browser.runtime.sendMessage(message);
-    } catch (error) {
-        debug(`Error: ${JSON.stringify(error)}`);
-        throw error;
+// Requests of the page which the background script has not answered yet.
+const pending = new Map<string, Request<any>>();
+
+const port = browser.runtime.connect({ name: PORT_NAME });
+let connected = true;
+
+// Besides answers to our requests, the background script pushes messages
+// which depend on user interaction via the pop-up and changes of the
+// wallet's state through the port, so we forward everything it sends to
+// the in-page script.
+port.onMessage.addListener((msg: Response<any> | WalletEvent<any>) => {
+    if (msg.direction !== Direction.ToPage) {
+        return;
     }
-}
 
-window.addEventListener("message", async function(event: MessageEvent<Message<any>>) {
+    debug(`Forwarding message from bs to ips: ${JSON.stringify(msg)}`);
+    if ("requestId" in msg) {
+        pending.delete(msg.requestId);
+    }
+    window.postMessage(msg, "*");
+});
+
+// The background script only goes away if the extension is reloaded or
+// updated, after which this content script cannot reach it anymore.
+port.onDisconnect.addListener(() => {
+    debug("Disconnected from bs");
+    connected = false;
+
+    pending.forEach(request => rejectDisconnected(request));
+    pending.clear();
+});
+
+window.addEventListener("message", function(event: MessageEvent<Message<any>>) {
     if (
-        event.source === window
-        && event.data.direction === Direction.ToBackground
+        event.source !== window
+        || event.data.direction !== Direction.ToBackground
     ) {
-        debug(`Forwarding request from ips to bs: ${JSON.stringify(event.data)}`);
-        let response = await forwardToBackground(event.data);
-
-        if (response) {
-            debug(`Forwarding response from bs to ips: ${JSON.stringify(response)}`);
-            window.postMessage(response, "*");
-        }
+        return;
     }
-});
 
-browser.runtime.onMessage.addListener(async function(msg: Message<any>) {
-    // Some messages from the background script (the ones that depend on
-    // user interaction via the pop-up), are not a direct response to a
-    // message we send fro the content script, so we must be ready to
-    // listen for messages from the background script and forward them to
-    // the content script too
-    if (msg.direction === Direction.ToPage) {
-        debug(`Forwarding message from bs to ips: ${JSON.stringify(msg)}`);
-        window.postMessage(msg, "*");
+    const request = event.data as Request<any>;
+    if (!connected) {
+        rejectDisconnected(request);
+        return;
     }
+
+    debug(`Forwarding request from ips to bs: ${JSON.stringify(request)}`);
+    pending.set(request.requestId, request);
+    port.postMessage(request);
 });
 
+function rejectDisconnected(request: Request<any>) {
+    const response: Response<undefined> = {
+        kind: RESPONSE_KIND[request.kind],
+        direction: Direction.ToPage,
+        version: PROTOCOL_VERSION,
+        requestId: request.requestId,
+        payload: undefined,
+        error: disconnectedError(),
+    };
+    window.postMessage(response, "*");
+}
+
 /**
  * Injects a script tag into the current document
  *
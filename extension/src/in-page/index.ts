@@ -1,222 +1,212 @@
 import Debug from "debug";
-import { Direction, Message, MessageKind } from "../messages";
-import { Address, CreateSwapPayload, LoanRequestPayload, Tx, Txid, WalletStatus } from "../models";
-
-Debug.enable("*");
+import {
+    decodeTx,
+    Direction,
+    encodeTx,
+    ErrorCode,
+    EventKind,
+    isSupportedVersion,
+    Message,
+    newRequestId,
+    parseAssetAmount,
+    PROTOCOL_VERSION,
+    Request,
+    RequestKind,
+    Response,
+    RESPONSE_KIND,
+    ResponseKind,
+    ResponsePayloads,
+    unsupportedVersionError,
+} from "../messages";
+import {
+    Address,
+    CreateSwapPayload,
+    LoanDetails,
+    LoanOffer,
+    LoanRequestPayload,
+    Network,
+    NetworkAsset,
+    Tx,
+    Txid,
+    PageWalletStatus,
+    Utxo,
+    WalletState,
+} from "../models";
+
+if (process.env.NODE_ENV !== "production") {
+    Debug.enable("*");
+}
 const debug = Debug("inpage");
 
+const BTC_TICKER = "L-BTC";
+const USDT_TICKER = "L-USDt";
+
 export default class WavesProvider {
-    public async walletStatus(): Promise<WalletStatus> {
+    private network: Promise<Network> | undefined;
+
+    public async walletStatus(): Promise<PageWalletStatus> {
         debug("Requesting wallet status");
-        let promise = new Promise<WalletStatus>((resolve, reject) => {
-            let listener = async function(event: MessageEvent<Message<WalletStatus>>) {
-                if (
-                    event.data.direction === Direction.ToPage
-                    && event.data.kind === MessageKind.WalletStatusResponse
-                ) {
-                    if (event.data.error) {
-                        reject(event.data.error);
-                    } else {
-                        debug(`Received wallet status: ${JSON.stringify(event.data)}`);
-
-                        window.removeEventListener("message", listener);
-                        resolve(event.data.payload);
-                    }
-                }
-            };
-            window.addEventListener("message", listener);
-        });
-        window.postMessage({
-            kind: MessageKind.WalletStatusRequest,
-            direction: Direction.ToBackground,
-        }, "*");
-        return promise;
+        return request(RequestKind.WalletStatus, undefined);
     }
 
     public async getSellCreateSwapPayload(btc: string): Promise<CreateSwapPayload> {
         debug("Getting sell create-swap payload");
-        let promise = new Promise<CreateSwapPayload>((resolve, reject) => {
-            let listener = async function(event: MessageEvent<Message<CreateSwapPayload>>) {
-                if (
-                    event.data.direction === Direction.ToPage
-                    && event.data.kind === MessageKind.SellResponse
-                ) {
-                    if (event.data.error) {
-                        reject(event.data.error);
-                    } else {
-                        debug(`Received sell response: ${JSON.stringify(event.data)}`);
-
-                        window.removeEventListener("message", listener);
-                        resolve(event.data.payload);
-                    }
-                }
-            };
-            window.addEventListener("message", listener);
-        });
-        window.postMessage({
-            kind: MessageKind.SellRequest,
-            direction: Direction.ToBackground,
-            payload: btc,
-        }, "*");
-        return promise;
+        return request(RequestKind.Sell, parseAssetAmount(await this.asset(BTC_TICKER), btc));
     }
 
     public async getBuyCreateSwapPayload(usdt: string): Promise<CreateSwapPayload> {
         debug("Getting buy create-swap payload");
-        let promise = new Promise<CreateSwapPayload>((resolve, reject) => {
-            let listener = async function(event: MessageEvent<Message<CreateSwapPayload>>) {
-                if (
-                    event.data.direction === Direction.ToPage
-                    && event.data.kind === MessageKind.BuyResponse
-                ) {
-                    if (event.data.error) {
-                        reject(event.data.error);
-                    } else {
-                        debug(`Received buy response: ${JSON.stringify(event.data)}`);
-
-                        window.removeEventListener("message", listener);
-                        resolve(event.data.payload);
-                    }
-                }
-            };
-            window.addEventListener("message", listener);
-        });
-        window.postMessage({
-            kind: MessageKind.BuyRequest,
-            direction: Direction.ToBackground,
-            payload: usdt,
-        }, "*");
-        return promise;
+        return request(RequestKind.Buy, parseAssetAmount(await this.asset(USDT_TICKER), usdt));
     }
 
     public async getNewAddress(): Promise<Address> {
         debug("Getting address");
-        let promise = new Promise<Address>((resolve, reject) => {
-            let listener = async function(event: MessageEvent<Message<Address>>) {
-                if (
-                    event.data.direction === Direction.ToPage
-                    && event.data.kind === MessageKind.AddressResponse
-                ) {
-                    if (event.data.error) {
-                        reject(event.data.kind);
-                    } else {
-                        debug(`Received address: ${JSON.stringify(event.data)}`);
-
-                        window.removeEventListener("message", listener);
-                        resolve(event.data.payload);
-                    }
-                }
-            };
-            window.addEventListener("message", listener);
-        });
-        window.postMessage({
-            kind: MessageKind.AddressRequest,
-            direction: Direction.ToBackground,
-        }, "*");
-        return promise;
+        return request(RequestKind.Address, undefined);
+    }
+
+    // The offer of the lender serving this page.
+    public async getLoanOffer(): Promise<LoanOffer> {
+        debug("Getting loan offer");
+        return request(RequestKind.GetLoanOffer, undefined);
     }
 
     public async makeLoanRequestPayload(collateral: string): Promise<LoanRequestPayload> {
         debug("Making loan request payload");
-        let promise = new Promise<LoanRequestPayload>((resolve, reject) => {
-            let listener = async function(event: MessageEvent<Message<LoanRequestPayload>>) {
-                if (
-                    event.data.direction === Direction.ToPage
-                    && event.data.kind === MessageKind.LoanResponse
-                ) {
-                    if (event.data.error) {
-                        reject(event.data.error);
-                    } else {
-                        debug(`Received loan request payload: ${JSON.stringify(event.data)}`);
-
-                        window.removeEventListener("message", listener);
-                        resolve(event.data.payload);
-                    }
-                }
-            };
-            window.addEventListener("message", listener);
-        });
-        window.postMessage({
-            kind: MessageKind.LoanRequest,
-            direction: Direction.ToBackground,
-            payload: collateral,
-        }, "*");
-        return promise;
+        return request(RequestKind.MakeLoanRequest, parseAssetAmount(await this.asset(BTC_TICKER), collateral));
+    }
+
+    // Hand the lender's response to our loan request to the wallet, which
+    // remembers it until it is signed.
+    public async loanResponseReceived(loan_response: any): Promise<LoanDetails> {
+        debug("Handing loan response to wallet");
+        return request(RequestKind.LoanResponseReceived, loan_response);
     }
 
     public async signAndSendSwap(tx_hex: string): Promise<Txid> {
         debug("Signing and sending swap");
-        let promise = new Promise<Txid>((resolve, reject) => {
-            let listener = async function(event: MessageEvent<Message<Txid>>) {
-                if (
-                    event.data.direction === Direction.ToPage
-                ) {
-                    if (event.data.kind === MessageKind.SwapTxid) {
-                        if (event.data.error) {
-                            reject(event.data.error);
-                        } else {
-                            debug(`Received swap txid: ${JSON.stringify(event.data)}`);
-
-                            window.removeEventListener("message", listener);
-                            resolve(event.data.payload);
-                        }
-                    } else if (event.data.kind === MessageKind.SwapRejected) {
-                        debug(`Swap rejected: ${JSON.stringify(event.data)}`);
-
-                        window.removeEventListener("message", listener);
-                        reject("User rejected swap");
-                    }
-                }
-            };
-            window.addEventListener("message", listener);
-        });
-        window.postMessage({
-            kind: MessageKind.SignAndSendSwap,
-            direction: Direction.ToBackground,
-            payload: tx_hex,
-        }, "*");
-        return promise;
+        return request(RequestKind.SignAndSendSwap, encodeTx(tx_hex));
     }
 
-    public async signLoan(loan_response: any): Promise<Tx> {
+    // Sign the loan of the last loan response after the user confirmed it.
+    public async signLoan(): Promise<Tx> {
         debug("Signing loan after user confirmation");
-        let promise = new Promise<Tx>((resolve, reject) => {
-            let listener = async function(event: MessageEvent<Message<Tx>>) {
-                if (
-                    event.data.direction === Direction.ToPage
-                ) {
-                    if (event.data.kind === MessageKind.SignedLoan) {
-                        if (event.data.error) {
-                            reject(event.data.error);
-                        } else {
-                            debug(`Received signed loan: ${JSON.stringify(event.data)}`);
-
-                            window.removeEventListener("message", listener);
-                            resolve(event.data.payload);
-                        }
-                    } else if (event.data.kind === MessageKind.LoanRejected) {
-                        debug(`Loan rejected: ${JSON.stringify(event.data)}`);
-
-                        window.removeEventListener("message", listener);
-                        reject("User rejected loan");
-                    }
+        return decodeTx(await request(RequestKind.SignLoan, undefined));
+    }
+
+    // Only answered while the wallet is unlocked and once the user
+    // authorized the origin of the page, because the UTXOs reveal the
+    // amounts hidden by confidential transactions.
+    public async getUtxos(): Promise<Utxo[]> {
+        debug("Getting UTXOs");
+        return request(RequestKind.Utxos, undefined);
+    }
+
+    public async getNetwork(): Promise<Network> {
+        debug("Getting network");
+        return request(RequestKind.Network, undefined);
+    }
+
+    // Call the `listener` whenever the state of the wallet changes, e.g.
+    // because it was unlocked in the pop-up or a transaction confirmed.
+    // Returns a function which stops listening.
+    public onWalletStateChanged(listener: (state: WalletState) => void): () => void {
+        const handler = (event: MessageEvent<Message<any>>) => {
+            if (
+                event.source === window
+                && event.data.direction === Direction.ToPage
+                && event.data.kind === EventKind.WalletStateChanged
+                && isSupportedVersion(event.data)
+            ) {
+                debug(`Wallet state changed: ${JSON.stringify(event.data)}`);
+                listener(event.data.payload);
+            }
+        };
+        window.addEventListener("message", handler);
+
+        return () => window.removeEventListener("message", handler);
+    }
+
+    private async asset(ticker: string): Promise<NetworkAsset> {
+        if (!this.network) {
+            this.network = this.getNetwork();
+            this.network.catch(() => this.network = undefined);
+        }
+
+        const asset = (await this.network).assets.find(asset => asset.ticker === ticker);
+        if (!asset) {
+            throw { code: ErrorCode.Internal, message: `Wallet does not know asset ${ticker}` };
+        }
+
+        return asset;
+    }
+}
+
+/**
+ * Sends a request to the background script and waits for the response answering it
+ *
+ * Only messages carrying the id of this request are considered, so that
+ * concurrent requests of the same kind each get their own response.
+ *
+ * @param {RequestKind} kind - Kind of the request
+ * @param {any} payload - Payload of the request
+ */
+function request<K extends RequestKind>(kind: K, payload: any): Promise<ResponsePayloads[K]> {
+    const requestId = newRequestId();
+    const responseKind = RESPONSE_KIND[kind];
+
+    let promise = new Promise<ResponsePayloads[K]>((resolve, reject) => {
+        let listener = async function(event: MessageEvent<Response<ResponsePayloads[K]>>) {
+            if (
+                event.source !== window
+                || event.data.direction !== Direction.ToPage
+                || event.data.requestId !== requestId
+            ) {
+                return;
+            }
+
+            if (event.data.kind === ResponseKind.UnsupportedVersion || !isSupportedVersion(event.data)) {
+                debug(`Unsupported version: ${JSON.stringify(event.data)}`);
+
+                window.removeEventListener("message", listener);
+                reject(event.data.error ?? unsupportedVersionError(event.data));
+            } else if (event.data.kind === responseKind) {
+                window.removeEventListener("message", listener);
+
+                if (event.data.error) {
+                    debug(`Request failed: ${JSON.stringify(event.data)}`);
+                    reject(event.data.error);
+                } else {
+                    debug(`Received ${responseKind}: ${JSON.stringify(event.data)}`);
+                    resolve(event.data.payload!);
                 }
-            };
-            window.addEventListener("message", listener);
-        });
-        window.postMessage({
-            kind: MessageKind.SignLoan,
-            direction: Direction.ToBackground,
-            payload: loan_response,
-        }, "*");
-        return promise;
+            }
+        };
+        window.addEventListener("message", listener);
+    });
+    const message: Request<any> = {
+        kind,
+        direction: Direction.ToBackground,
+        version: PROTOCOL_VERSION,
+        requestId,
+        payload,
+    };
+    window.postMessage(message, "*");
+    return promise;
+}
+
+declare global {
+    interface Window {
+        droplet?: WavesProvider;
     }
 }
 
 const initializeProvider = () => {
     debug("I was injected 🥳");
-    // @ts-ignore `provider` is not known on `window`. That's why we are defining it ;)
-    window.wavesProvider = new WavesProvider();
+    Object.defineProperty(window, "droplet", {
+        value: new WavesProvider(),
+        writable: false,
+        configurable: false,
+    });
 };
 
 initializeProvider();
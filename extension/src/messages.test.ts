@@ -0,0 +1,22 @@
+import { decodeTx, encodeTx, formatAmount, parseAssetAmount, TxEncoding } from "./messages";
+
+test("transaction survives base64 encoding", () => {
+    const txHex = "0200000001ff00a1";
+
+    const encoded = encodeTx(txHex);
+
+    expect(encoded).toEqual({ encoding: TxEncoding.Base64, data: "AgAAAAH/AKE=" });
+    expect(decodeTx(encoded)).toBe(txHex);
+});
+
+test("amounts are parsed into the smallest unit of the asset", () => {
+    const btc = { assetId: "lbtc", ticker: "L-BTC", precision: 8 };
+
+    expect(parseAssetAmount(btc, "1.5")).toEqual({ asset: "lbtc", amount: 150_000_000 });
+    expect(parseAssetAmount(btc, "0.00000001")).toEqual({ asset: "lbtc", amount: 1 });
+    expect(formatAmount(150_000_000, 8)).toBe("1.50000000");
+    expect(formatAmount(1, 8)).toBe("0.00000001");
+    expect(() => parseAssetAmount(btc, "0.000000001")).toThrow();
+    expect(() => parseAssetAmount(btc, "-1")).toThrow();
+    expect(() => parseAssetAmount(btc, "0")).toThrow();
+});
@@ -1,30 +1,269 @@
-export enum MessageKind {
-    WalletStatusRequest = "WalletStatusRequest",
-    WalletStatusResponse = "WalletStatusResponse",
-    SellRequest = "SellRequest",
-    SellResponse = "SellResponse",
-    BuyRequest = "BuyRequest",
-    BuyResponse = "BuyResponse",
-    LoanRequest = "LoanRequest",
-    LoanResponse = "LoanResponse", // TODO: Choose a better name
+import {
+    Address,
+    CreateSwapPayload,
+    LoanDetails,
+    LoanOffer,
+    LoanRequestPayload,
+    Network,
+    NetworkAsset,
+    PageWalletStatus,
+    Tx,
+    Txid,
+    Utxo,
+    WalletState,
+} from "./models";
+
+// Requests the in-page script sends to the background script.
+export enum RequestKind {
+    WalletStatus = "WalletStatus",
+    Sell = "Sell",
+    Buy = "Buy",
+    Address = "Address",
+    GetLoanOffer = "GetLoanOffer",
+    MakeLoanRequest = "MakeLoanRequest",
+    LoanResponseReceived = "LoanResponseReceived",
     SignAndSendSwap = "SignAndSendSwap",
-    SwapTxid = "SwapTxid",
-    AddressRequest = "AddressRequest",
-    AddressResponse = "AddressResponse",
     SignLoan = "SignLoan",
+    Utxos = "Utxos",
+    Network = "Network",
+}
+
+// Responses the background script sends to the in-page script.
+export enum ResponseKind {
+    WalletStatus = "WalletStatus",
+    SellPayload = "SellPayload",
+    BuyPayload = "BuyPayload",
+    Address = "Address",
+    LoanOffer = "LoanOffer",
+    LoanRequestPayload = "LoanRequestPayload",
+    LoanDetails = "LoanDetails",
+    SwapTxid = "SwapTxid",
     SignedLoan = "SignedLoan",
-    LoanRejected = "LoanRejected",
-    SwapRejected = "SwapRejected",
+    Utxos = "Utxos",
+    Network = "Network",
+    UnsupportedVersion = "UnsupportedVersion",
+}
+
+// Events the background script pushes to the in-page scripts of all tabs
+// without being asked.
+export enum EventKind {
+    WalletStateChanged = "WalletStateChanged",
 }
 
+// The payload of each kind of event.
+export interface EventPayloads {
+    [EventKind.WalletStateChanged]: WalletState;
+}
+
+// The kind of response answering each kind of request.
+//
+// Requests which the user rejects are answered with the same kind of
+// response, carrying an error.
+export const RESPONSE_KIND: Record<RequestKind, ResponseKind> = {
+    [RequestKind.WalletStatus]: ResponseKind.WalletStatus,
+    [RequestKind.Sell]: ResponseKind.SellPayload,
+    [RequestKind.Buy]: ResponseKind.BuyPayload,
+    [RequestKind.Address]: ResponseKind.Address,
+    [RequestKind.GetLoanOffer]: ResponseKind.LoanOffer,
+    [RequestKind.MakeLoanRequest]: ResponseKind.LoanRequestPayload,
+    [RequestKind.LoanResponseReceived]: ResponseKind.LoanDetails,
+    [RequestKind.SignAndSendSwap]: ResponseKind.SwapTxid,
+    [RequestKind.SignLoan]: ResponseKind.SignedLoan,
+    [RequestKind.Utxos]: ResponseKind.Utxos,
+    [RequestKind.Network]: ResponseKind.Network,
+};
+
+// The payload of the response answering each kind of request.
+export interface ResponsePayloads {
+    [RequestKind.WalletStatus]: PageWalletStatus;
+    [RequestKind.Sell]: CreateSwapPayload;
+    [RequestKind.Buy]: CreateSwapPayload;
+    [RequestKind.Address]: Address;
+    [RequestKind.GetLoanOffer]: LoanOffer;
+    [RequestKind.MakeLoanRequest]: LoanRequestPayload;
+    [RequestKind.LoanResponseReceived]: LoanDetails;
+    [RequestKind.SignAndSendSwap]: Txid;
+    [RequestKind.SignLoan]: EncodedTx;
+    [RequestKind.Utxos]: Utxo[];
+    [RequestKind.Network]: Network;
+}
+
+// Version of the messages exchanged between the in-page and background
+// scripts. Bump it whenever a message changes in a way the other side
+// cannot understand, e.g. because the page still runs the in-page script
+// of the extension before it was updated.
+export const PROTOCOL_VERSION = 8;
+
+// Name of the port through which content scripts talk to the background
+// script. Messages on it are delivered in order and either side notices
+// when the other one goes away, e.g. because the extension was reloaded.
+export const PORT_NAME = "droplet";
+
 export enum Direction {
     ToBackground = "ToBackground",
     ToPage = "ToPage",
 }
 
-export interface Message<T> {
-    kind: MessageKind;
-    direction: Direction;
+export interface Request<T> {
+    kind: RequestKind;
+    direction: Direction.ToBackground;
+    version: number;
+    // Chosen by the in-page script for every request and copied onto
+    // the response answering it, so that concurrent requests cannot
+    // pick up each other's responses.
+    requestId: string;
+    payload: T;
+}
+
+export interface Response<T> {
+    kind: ResponseKind;
+    direction: Direction.ToPage;
+    version: number;
+    requestId: string;
+    payload: T | undefined;
+    error: RpcError | undefined;
+}
+
+export interface WalletEvent<T> {
+    kind: EventKind;
+    direction: Direction.ToPage;
+    version: number;
     payload: T;
-    error: string | undefined;
+}
+
+export type Message<T> = Request<T> | Response<T> | WalletEvent<T>;
+
+export enum ErrorCode {
+    WalletLocked = "WalletLocked",
+    InsufficientFunds = "InsufficientFunds",
+    InvalidRequest = "InvalidRequest",
+    UserRejected = "UserRejected",
+    // The user did not authorize the origin of the page to make the request.
+    Unauthorized = "Unauthorized",
+    UnsupportedVersion = "UnsupportedVersion",
+    Disconnected = "Disconnected",
+    Internal = "Internal",
+}
+
+// What a request failed with, so that pages can tell failure modes apart
+// without parsing the message.
+export interface RpcError {
+    code: ErrorCode;
+    message: string;
+}
+
+// The wallet rejects with an `RpcError` already, anything else it or the
+// browser throws is wrapped in one.
+export function toRpcError(e: any): RpcError {
+    if (e && typeof e.code === "string" && typeof e.message === "string") {
+        return { code: e.code, message: e.message };
+    }
+
+    const message = typeof e === "string" ? e : e?.message ?? JSON.stringify(e);
+    return { code: ErrorCode.Internal, message };
+}
+
+export function newRequestId(): string {
+    const bytes = new Uint8Array(8);
+    window.crypto.getRandomValues(bytes);
+
+    return Array.from(bytes, byte => byte.toString(16).padStart(2, "0")).join("");
+}
+
+export function isSupportedVersion(message: Message<any>): boolean {
+    return message.version === PROTOCOL_VERSION;
+}
+
+export function unsupportedVersionError(message: Message<any>): RpcError {
+    return {
+        code: ErrorCode.UnsupportedVersion,
+        message: `Unsupported message protocol version ${message.version ?? 0}, expected ${PROTOCOL_VERSION}. `
+            + "Please reload the page.",
+    };
+}
+
+// Transactions are the largest payloads and pass through three scripts on
+// their way between page and wallet. Carrying their consensus encoding as
+// base64 rather than hex makes them a third smaller.
+export enum TxEncoding {
+    Hex = "Hex",
+    Base64 = "Base64",
+}
+
+export interface EncodedTx {
+    encoding: TxEncoding;
+    data: string;
+}
+
+export function encodeTx(txHex: Tx): EncodedTx {
+    let binary = "";
+    for (let i = 0; i < txHex.length; i += 2) {
+        binary += String.fromCharCode(parseInt(txHex.substr(i, 2), 16));
+    }
+
+    return { encoding: TxEncoding.Base64, data: btoa(binary) };
+}
+
+export function decodeTx(tx: EncodedTx): Tx {
+    switch (tx.encoding) {
+        case TxEncoding.Hex:
+            return tx.data;
+        case TxEncoding.Base64:
+            return Array.from(atob(tx.data), char => char.charCodeAt(0).toString(16).padStart(2, "0")).join("");
+    }
+}
+
+// An amount of an asset, in the smallest unit of the asset.
+//
+// Amounts entered by the user are parsed into this before they are sent to
+// the background script, which checks them again before handing them to the
+// wallet.
+export interface AssetAmount {
+    asset: string;
+    amount: number;
+}
+
+export function parseAssetAmount(asset: NetworkAsset, amount: string): AssetAmount {
+    const match = /^(\d+)(?:\.(\d+))?$/.exec(amount.trim());
+    const fraction = match?.[2] ?? "";
+    if (!match || fraction.length > asset.precision) {
+        throw invalidRequest(`Invalid ${asset.ticker} amount "${amount}"`);
+    }
+
+    const smallestUnits = Number(match[1] + fraction.padEnd(asset.precision, "0"));
+    if (!Number.isSafeInteger(smallestUnits) || smallestUnits === 0) {
+        throw invalidRequest(`Invalid ${asset.ticker} amount "${amount}"`);
+    }
+
+    return { asset: asset.assetId, amount: smallestUnits };
+}
+
+export function checkAssetAmount(payload: any, asset: string): AssetAmount {
+    if (payload?.asset !== asset) {
+        throw invalidRequest(`Expected an amount of asset ${asset}, got ${JSON.stringify(payload)}`);
+    }
+    if (!Number.isSafeInteger(payload.amount) || payload.amount <= 0) {
+        throw invalidRequest(`Invalid amount ${JSON.stringify(payload.amount)}`);
+    }
+
+    return payload;
+}
+
+// Format the `amount` in whole units of an asset with the given `precision`,
+// which is how the wallet parses amounts.
+export function formatAmount(amount: number, precision: number): string {
+    const digits = amount.toString().padStart(precision + 1, "0");
+
+    return `${digits.slice(0, -precision)}.${digits.slice(-precision)}`;
+}
+
+export function disconnectedError(): RpcError {
+    return {
+        code: ErrorCode.Disconnected,
+        message: "Lost connection to the wallet, it was probably updated or reloaded. Please reload the page.",
+    };
+}
+
+function invalidRequest(message: string): RpcError {
+    return { code: ErrorCode.InvalidRequest, message };
 }
@@ -20,6 +20,24 @@ export interface WalletStatus {
     address?: Address;
 }
 
+export enum Feature {
+    Swap = "swap",
+    Loan = "loan",
+    SignMessage = "signMessage",
+}
+
+// The wallet status as pages see it, so that they can adapt to the wallet
+// without probing every call.
+export interface PageWalletStatus extends WalletStatus {
+    network: Network;
+    // Version of the extension.
+    version: string;
+    features: Feature[];
+    // Whether the user authorized the origin of the page to see the
+    // wallet's UTXOs.
+    authorized: boolean;
+}
+
 export interface BalanceEntry {
     assetId: string;
     ticker: string;
@@ -28,6 +46,36 @@ export interface BalanceEntry {
 
 export type BalanceUpdate = Array<BalanceEntry>;
 
+// What the background script pushes to every tab whenever it changes, e.g.
+// because the wallet was unlocked or a transaction confirmed.
+export interface WalletState {
+    status: PageWalletStatus;
+    // Empty while the wallet is locked.
+    balances: BalanceUpdate;
+}
+
+export interface Utxo {
+    txid: Txid;
+    vout: number;
+    asset: string;
+    // Only known for the assets we trade.
+    ticker?: string;
+    // In the smallest unit of the asset.
+    value: number;
+    confirmed: boolean;
+}
+
+export interface NetworkAsset {
+    assetId: string;
+    ticker: string;
+    precision: number;
+}
+
+export interface Network {
+    chain: "elements" | "liquid" | "liquid-testnet";
+    assets: NetworkAsset[];
+}
+
 export interface TradeSide {
     ticker: string;
     amount: number;
@@ -44,6 +92,7 @@ export interface SwapToSign {
     txHex: string;
     decoded: Trade;
     tabId: number;
+    requestId: string;
 }
 
 export interface LoanDetails {
@@ -54,9 +103,27 @@ export interface LoanDetails {
     txid: Txid;
 }
 
+export interface LoanOffer {
+    rate: { ask: number; bid: number };
+    max_ltv_bps: number;
+    liquidation_ltv_bps: number;
+    min_timelock: number;
+    max_timelock: number;
+    max_principal: number;
+}
+
 export interface LoanToSign {
     details: LoanDetails;
     tabId: number;
+    requestId: string;
+}
+
+// A page asking for the wallet's UTXOs before the user authorized its
+// origin.
+export interface OriginToAuthorize {
+    origin: string;
+    tabId: number;
+    requestId: string;
 }
 
 export type Tx = string;
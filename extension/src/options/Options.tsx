@@ -10,14 +10,18 @@ import {
     InputRightElement,
     Radio,
     RadioGroup,
+    Text,
     VStack,
 } from "@chakra-ui/react";
 import Debug from "debug";
 import { useState } from "react";
 import * as React from "react";
+import { authorizedOrigins, revokeOrigin } from "../authorizedOrigins";
 import "./Options.css";
 
-Debug.enable("*");
+if (process.env.NODE_ENV !== "production") {
+    Debug.enable("*");
+}
 const debug = Debug("options");
 
 function Options() {
@@ -40,6 +44,7 @@ function Options() {
                                 <HStack spacing="24px">
                                     <Radio value="elements">Elements</Radio>
                                     <Radio value="liquid">Liquid</Radio>
+                                    <Radio value="liquid-testnet">Liquid Testnet</Radio>
                                 </HStack>
                             </RadioGroup>
                         </HStack>
@@ -48,6 +53,7 @@ function Options() {
                     <KeyValueField keyName="ESPLORA_API_URL" title={"Esplora API URL"} />
                     <KeyValueField keyName="LBTC_ASSET_ID" title={"Bitcoin Asset ID (L-BTC)"} />
                     <KeyValueField keyName="LUSDT_ASSET_ID" title={"USD Asset ID (L-USDT)"} />
+                    <AuthorizedOrigins />
                 </VStack>
             </Center>
         </Box>
@@ -97,4 +103,27 @@ function KeyValueField({ keyName, title }: KeyValueFieldProps) {
     );
 }
 
+// Sites which may see the coins in the wallet.
+function AuthorizedOrigins() {
+    const [origins, setOrigins] = useState(authorizedOrigins());
+
+    const revoke = (origin: string) => {
+        revokeOrigin(origin);
+        setOrigins(authorizedOrigins());
+    };
+
+    return (
+        <FormControl as="fieldset">
+            <FormLabel as="legend">Authorized Sites</FormLabel>
+            {origins.length === 0 && <Text>None</Text>}
+            {origins.map(origin => (
+                <HStack key={origin}>
+                    <Text>{origin}</Text>
+                    <Button onClick={() => revoke(origin)}>Revoke</Button>
+                </HStack>
+            ))}
+        </FormControl>
+    );
+}
+
 export default Options;
@@ -1,7 +1,20 @@
 import Debug from "debug";
-import { Address, BalanceUpdate, CreateSwapPayload, LoanDetails, Status, Trade, Txid, WalletStatus } from "./models";
-
-Debug.enable("*");
+import {
+    Address,
+    BalanceUpdate,
+    CreateSwapPayload,
+    LoanDetails,
+    Network,
+    Status,
+    Trade,
+    Txid,
+    Utxo,
+    WalletStatus,
+} from "./models";
+
+if (process.env.NODE_ENV !== "production") {
+    Debug.enable("*");
+}
 const debug = Debug("wasmProxy");
 
 export async function walletStatus(name: string): Promise<WalletStatus> {
@@ -48,18 +61,40 @@ export async function getBalances(name: string): Promise<BalanceUpdate> {
     return get_balances(name);
 }
 
-export async function makeSellCreateSwapPayload(name: string, btc: string): Promise<CreateSwapPayload> {
+export async function getUtxos(name: string): Promise<Utxo[]> {
+    const { get_utxos } = await import("./wallet");
+
+    debug("getUtxos");
+    return get_utxos(name);
+}
+
+export async function getNetwork(): Promise<Network> {
+    const { get_network } = await import("./wallet");
+
+    debug("getNetwork");
+    return get_network();
+}
+
+export async function makeSellCreateSwapPayload(
+    name: string,
+    btc: string,
+    consolidate: boolean = false,
+): Promise<CreateSwapPayload> {
     const { make_sell_create_swap_payload } = await import("./wallet");
 
     debug("makeSellCreateSwapPayload");
-    return make_sell_create_swap_payload(name, btc);
+    return make_sell_create_swap_payload(name, btc, consolidate);
 }
 
-export async function makeBuyCreateSwapPayload(name: string, usdt: string): Promise<CreateSwapPayload> {
+export async function makeBuyCreateSwapPayload(
+    name: string,
+    usdt: string,
+    consolidate: boolean = false,
+): Promise<CreateSwapPayload> {
     const { make_buy_create_swap_payload } = await import("./wallet");
 
     debug("makeBuyCreateSwapPayload");
-    return make_buy_create_swap_payload(name, usdt);
+    return make_buy_create_swap_payload(name, usdt, consolidate);
 }
 
 export async function makeLoanRequestPayload(name: string, collateral: string): Promise<CreateSwapPayload> {
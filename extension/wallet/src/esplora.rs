@@ -4,7 +4,9 @@ use elements::{
     encode::{deserialize, serialize_hex},
     Address, BlockHash, Transaction, Txid,
 };
+use futures::{stream, StreamExt, TryStreamExt};
 use reqwest::StatusCode;
+use std::future::Future;
 use wasm_bindgen::UnwrapThrowExt;
 
 /// Fetch the UTXOs of an address.
@@ -45,6 +47,52 @@ pub async fn fetch_utxos(address: &Address) -> Result<Vec<Utxo>> {
         .context("failed to deserialize response")
 }
 
+/// Fetch the UTXOs of many addresses.
+///
+/// Esplora only allows us to query one address at a time, so we
+/// issue one request per address while making sure that there are
+/// never more than `max_concurrent_requests` requests in flight. A
+/// limit of 0 is treated as 1.
+///
+/// The UTXOs are returned grouped by the address they belong to.
+pub async fn fetch_utxos_for_addresses(
+    addresses: &[Address],
+    max_concurrent_requests: usize,
+) -> Result<Vec<(Address, Vec<Utxo>)>> {
+    fetch_concurrently(addresses, max_concurrent_requests, |address| async move {
+        fetch_utxos(&address).await
+    })
+    .await
+}
+
+/// Fetch the UTXOs of all `addresses` through `fetch`, failing if
+/// fetching those of any address fails.
+async fn fetch_concurrently<F, Fut>(
+    addresses: &[Address],
+    max_concurrent_requests: usize,
+    fetch: F,
+) -> Result<Vec<(Address, Vec<Utxo>)>>
+where
+    F: Fn(Address) -> Fut,
+    Fut: Future<Output = Result<Vec<Utxo>>>,
+{
+    stream::iter(addresses.iter().cloned())
+        .map(|address| {
+            let utxos = fetch(address.clone());
+
+            async move {
+                let utxos = utxos
+                    .await
+                    .with_context(|| format!("failed to fetch UTXOs of address {}", address))?;
+
+                Result::<_, anyhow::Error>::Ok((address, utxos))
+            }
+        })
+        .buffer_unordered(max_concurrent_requests.max(1))
+        .try_collect()
+        .await
+}
+
 /// Fetch transaction history for the specified address.
 ///
 /// Returns up to 50 mempool transactions plus the first 25 confirmed
@@ -235,6 +283,91 @@ pub struct UtxoStatus {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use futures::executor::block_on;
+
+    fn addresses() -> Vec<Address> {
+        [
+            "ert1qxzlkf3t275hwszualaf35spcfuq4s5tqtxj4tl",
+            "ert1q00k0qsmqkep0gkm0klmuuyx6mlz3h4dkwg6ume",
+            "ex1qnvt8ra6mhrfv3ef0h3xe6yu8g3v39f9jtwtfq4",
+        ]
+        .iter()
+        .map(|address| address.parse().unwrap())
+        .collect()
+    }
+
+    /// One unconfirmed UTXO per address, told apart by its `vout`.
+    fn utxo_of(addresses: &[Address], address: &Address) -> Utxo {
+        let vout = addresses.iter().position(|a| a == address).unwrap();
+
+        Utxo {
+            txid: Txid::default(),
+            vout: vout as u32,
+            status: UtxoStatus {
+                confirmed: false,
+                block_height: None,
+                block_hash: None,
+                block_time: None,
+            },
+        }
+    }
+
+    #[test]
+    fn merges_utxos_of_all_addresses() {
+        let addresses = addresses();
+
+        let mut utxos = block_on(fetch_concurrently(&addresses, 2, |address| {
+            let utxo = utxo_of(&addresses, &address);
+            async move { Ok(vec![utxo]) }
+        }))
+        .unwrap();
+        utxos.sort_by_key(|(_, utxos)| utxos[0].vout);
+
+        assert_eq!(
+            utxos,
+            addresses
+                .iter()
+                .map(|address| (address.clone(), vec![utxo_of(&addresses, address)]))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn fetches_one_at_a_time_if_no_concurrency_is_allowed() {
+        let addresses = addresses();
+
+        let utxos = block_on(fetch_concurrently(&addresses, 0, |address| {
+            let utxo = utxo_of(&addresses, &address);
+            async move { Ok(vec![utxo]) }
+        }))
+        .unwrap();
+
+        assert_eq!(utxos.len(), addresses.len());
+    }
+
+    #[test]
+    fn fails_if_any_address_fails() {
+        let addresses = addresses();
+        let failing = addresses[1].clone();
+
+        let error = block_on(fetch_concurrently(&addresses, 2, |address| {
+            let result = if address == failing {
+                Err(anyhow!("esplora is down"))
+            } else {
+                Ok(vec![utxo_of(&addresses, &address)])
+            };
+            async move { result }
+        }))
+        .unwrap_err();
+
+        assert_eq!(
+            format!("{:#}", error),
+            format!(
+                "failed to fetch UTXOs of address {}: esplora is down",
+                failing
+            )
+        );
+    }
 
     #[test]
     fn can_deserialize_confidential_utxo() {
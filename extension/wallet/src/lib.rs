@@ -3,10 +3,11 @@ use std::str::FromStr;
 use conquer_once::Lazy;
 use elements::{
     bitcoin::util::amount::{Amount, Denomination},
-    Address, AddressParams, Txid,
+    Address, Txid,
 };
 use futures::lock::Mutex;
 use js_sys::Promise;
+use network_config::Network as Chain;
 use wasm_bindgen::{prelude::*, JsCast};
 use web_sys::window;
 
@@ -17,6 +18,9 @@ mod assets;
 mod cache_storage;
 mod esplora;
 mod logger;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod native;
+mod rpc_error;
 mod storage;
 mod wallet;
 
@@ -26,6 +30,12 @@ use reqwest::Url;
 // TODO: make this configurable through extension option UI
 const DEFAULT_SAT_PER_VBYTE: u64 = 1;
 
+// Trades allocation speed for a smaller module, which the extension
+// has to download and compile before the wallet can do anything.
+#[cfg(all(feature = "wee_alloc", target_arch = "wasm32"))]
+#[global_allocator]
+static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
+
 static LOADED_WALLET: Lazy<Mutex<Option<Wallet>>> = Lazy::new(Mutex::default);
 
 // TODO: I was unable to use `futures::lock::Mutex` for these, but
@@ -150,6 +160,45 @@ pub async fn get_balances(name: String) -> Result<JsValue, JsValue> {
     Ok(balance_entries)
 }
 
+/// Get the UTXOs of the currently loaded wallet.
+///
+/// Returns an array of [`UtxoEntry`]s.
+///
+/// Fails if the wallet is currently not loaded or we cannot reach the block explorer for some reason.
+#[wasm_bindgen]
+pub async fn get_utxos(name: String) -> Result<JsValue, JsValue> {
+    let utxos = map_err_from_anyhow!(wallet::get_utxos(&name, &LOADED_WALLET).await)?;
+    let utxos = map_err_from_anyhow!(JsValue::from_serde(&utxos))?;
+
+    Ok(utxos)
+}
+
+/// Get the chain the wallet is configured for and the assets we
+/// trade on it.
+#[wasm_bindgen]
+pub fn get_network() -> Result<JsValue, JsValue> {
+    let chain = *CHAIN.lock().expect_throw("can get lock");
+    let assets = [
+        *BTC_ASSET_ID.lock().expect_throw("can get lock"),
+        *USDT_ASSET_ID.lock().expect_throw("can get lock"),
+    ]
+    .iter()
+    .filter_map(|asset_id| {
+        let (ticker, precision) = assets::lookup(*asset_id)?;
+
+        Some(NetworkAsset {
+            asset_id: *asset_id,
+            ticker,
+            precision,
+        })
+    })
+    .collect();
+
+    let network = map_err_from_anyhow!(JsValue::from_serde(&Network { chain, assets }))?;
+
+    Ok(network)
+}
+
 /// Withdraw all funds to the given address.
 ///
 /// Returns the transaction ID of the transaction that was broadcasted.
@@ -166,14 +215,17 @@ pub async fn withdraw_everything_to(name: String, address: String) -> Result<JsV
 /// Constructs a new [`CreateSwapPayload`] with the given USDt amount.
 ///
 /// This will select UTXOs from the wallet to cover the given amount.
+/// If `consolidate` is set, small UTXOs will be spent too as long as
+/// fees are low.
 #[wasm_bindgen]
 pub async fn make_buy_create_swap_payload(
     wallet_name: String,
     usdt: String,
+    consolidate: bool,
 ) -> Result<JsValue, JsValue> {
     let usdt = map_err_from_anyhow!(Amount::from_str_in(&usdt, Denomination::Bitcoin))?;
     let payload = map_err_from_anyhow!(
-        wallet::make_buy_create_swap_payload(wallet_name, &LOADED_WALLET, usdt).await
+        wallet::make_buy_create_swap_payload(wallet_name, &LOADED_WALLET, usdt, consolidate).await
     )?;
     let payload = map_err_from_anyhow!(JsValue::from_serde(&payload))?;
 
@@ -183,14 +235,17 @@ pub async fn make_buy_create_swap_payload(
 /// Constructs a new [`CreateSwapPayload`] with the given Bitcoin amount.
 ///
 /// This will select UTXOs from the wallet to cover the given amount.
+/// If `consolidate` is set, small UTXOs will be spent too as long as
+/// fees are low.
 #[wasm_bindgen]
 pub async fn make_sell_create_swap_payload(
     wallet_name: String,
     btc: String,
+    consolidate: bool,
 ) -> Result<JsValue, JsValue> {
     let btc = map_err_from_anyhow!(Amount::from_str_in(&btc, Denomination::Bitcoin))?;
     let payload = map_err_from_anyhow!(
-        wallet::make_sell_create_swap_payload(wallet_name, &LOADED_WALLET, btc).await
+        wallet::make_sell_create_swap_payload(wallet_name, &LOADED_WALLET, btc, consolidate).await
     )?;
     let payload = map_err_from_anyhow!(JsValue::from_serde(&payload))?;
 
@@ -355,38 +410,20 @@ fn handle_storage_update(event: web_sys::StorageEvent) -> Promise {
     Promise::resolve(&JsValue::null())
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
-enum Chain {
-    Elements,
-    Liquid,
+#[derive(Debug, serde::Serialize)]
+struct Network {
+    chain: Chain,
+    assets: Vec<NetworkAsset>,
 }
 
-impl From<Chain> for &AddressParams {
-    fn from(from: Chain) -> Self {
-        match from {
-            Chain::Elements => &AddressParams::ELEMENTS,
-            Chain::Liquid => &AddressParams::LIQUID,
-        }
-    }
+#[derive(Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NetworkAsset {
+    asset_id: elements::AssetId,
+    ticker: &'static str,
+    precision: u8,
 }
 
-impl FromStr for Chain {
-    type Err = WrongChain;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let lowercase = s.to_ascii_lowercase();
-        match lowercase.as_str() {
-            "elements" => Ok(Chain::Elements),
-            "liquid" => Ok(Chain::Liquid),
-            _ => Err(WrongChain(lowercase)),
-        }
-    }
-}
-
-#[derive(Debug, thiserror::Error)]
-#[error("Unsupported chain: {0}")]
-struct WrongChain(String);
-
 #[derive(serde::Serialize, serde::Deserialize)]
 struct Transaction {
     #[serde(with = "baru::loan::transaction_as_string")]
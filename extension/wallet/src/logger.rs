@@ -1,7 +1,17 @@
 // TODO: Make this configurable again. We used to be able to set and
 // read this using local storage, but after re-working the extension
 // it is no longer the case
+#[cfg(feature = "logging")]
 pub fn try_init() {
-    // TODO: make this configurable
-    wasm_logger::init(wasm_logger::Config::new(log::Level::Debug));
+    let level = if cfg!(debug_assertions) {
+        log::Level::Debug
+    } else {
+        log::Level::Info
+    };
+
+    wasm_logger::init(wasm_logger::Config::new(level));
 }
+
+/// Without a logger, all log statements are no-ops.
+#[cfg(not(feature = "logging"))]
+pub fn try_init() {}
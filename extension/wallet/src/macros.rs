@@ -27,7 +27,9 @@ macro_rules! map_err_from_anyhow {
     ($e:expr) => {
         match $e {
             Ok(i) => Ok(i),
-            Err(e) => Err(JsValue::from_str(&format!("{:#}", e))),
+            Err(e) => {
+                Err($crate::rpc_error::RpcError::from(::anyhow::Error::from(e)).into_js_value())
+            }
         }
     };
 }
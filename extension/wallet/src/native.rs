@@ -0,0 +1,114 @@
+//! The wallet for use outside of a browser.
+//!
+//! Everything a page asks the extension to do is available here as
+//! well, with the wallet's state kept in the directory named by
+//! `WALLET_STORAGE_DIR` instead of the browser's local storage.
+
+use crate::{storage::Storage, wallet, Wallet};
+use anyhow::Result;
+use baru::loan::{LoanRequest, LoanResponse};
+use elements::{bitcoin::Amount, AssetId, Transaction, Txid};
+use futures::lock::Mutex;
+use network_config::Network;
+use reqwest::Url;
+
+pub use crate::wallet::{BalanceEntry, CreateSwapPayload, LoanDetails, Trade, TradeSide};
+
+/// Tell the wallet which network it is used on.
+///
+/// Has to be called before anything else, because the settings are
+/// only read once.
+pub fn configure(
+    network: Network,
+    esplora_url: &Url,
+    btc_asset_id: AssetId,
+    usdt_asset_id: AssetId,
+) -> Result<()> {
+    let storage = Storage::local_storage()?;
+    storage.set_item("CHAIN", network)?;
+    storage.set_item("ESPLORA_API_URL", esplora_url)?;
+    storage.set_item("LBTC_ASSET_ID", btc_asset_id)?;
+    storage.set_item("LUSDT_ASSET_ID", usdt_asset_id)?;
+
+    Ok(())
+}
+
+/// An unlocked wallet.
+pub struct NativeWallet {
+    name: String,
+    current: Mutex<Option<Wallet>>,
+}
+
+impl NativeWallet {
+    pub async fn create(name: String, password: String) -> Result<Self> {
+        let current = Mutex::default();
+        wallet::create_new(name.clone(), password, &current).await?;
+
+        Ok(Self { name, current })
+    }
+
+    pub async fn load(name: String, password: String) -> Result<Self> {
+        let current = Mutex::default();
+        wallet::load_existing(name.clone(), password, &current).await?;
+
+        Ok(Self { name, current })
+    }
+
+    pub async fn address(&self) -> Result<elements::Address> {
+        wallet::get_address(self.name.clone(), &self.current).await
+    }
+
+    pub async fn balances(&self) -> Result<Vec<BalanceEntry>> {
+        wallet::get_balances(&self.name, &self.current).await
+    }
+
+    pub async fn sell_payload(&self, btc: Amount) -> Result<CreateSwapPayload> {
+        let payload =
+            wallet::make_sell_create_swap_payload(self.name.clone(), &self.current, btc, false)
+                .await?;
+
+        Ok(payload)
+    }
+
+    pub async fn buy_payload(&self, usdt: Amount) -> Result<CreateSwapPayload> {
+        let payload =
+            wallet::make_buy_create_swap_payload(self.name.clone(), &self.current, usdt, false)
+                .await?;
+
+        Ok(payload)
+    }
+
+    /// What we give and get in the swap `transaction`.
+    pub async fn extract_trade(&self, transaction: Transaction) -> Result<Trade> {
+        wallet::extract_trade(self.name.clone(), &self.current, transaction).await
+    }
+
+    pub async fn sign_and_send_swap(&self, transaction: Transaction) -> Result<Txid> {
+        let txid =
+            wallet::sign_and_send_swap_transaction(self.name.clone(), &self.current, transaction)
+                .await?;
+
+        Ok(txid)
+    }
+
+    pub async fn loan_request(&self, collateral: Amount) -> Result<LoanRequest> {
+        let loan_request =
+            wallet::make_loan_request(self.name.clone(), &self.current, collateral).await?;
+
+        Ok(loan_request)
+    }
+
+    /// The terms of the loan in the lender's `loan_response`, which is
+    /// remembered until the loan is signed.
+    pub async fn extract_loan(&self, loan_response: LoanResponse) -> Result<LoanDetails> {
+        let details = wallet::extract_loan(self.name.clone(), &self.current, loan_response).await?;
+
+        Ok(details)
+    }
+
+    pub async fn sign_loan(&self) -> Result<Transaction> {
+        let transaction = wallet::sign_loan(self.name.clone(), &self.current).await?;
+
+        Ok(transaction)
+    }
+}
@@ -0,0 +1,126 @@
+use crate::wallet::{
+    ExtractLoanError, MakeLoanRequestError, MakePayloadError, RepayLoanError, SignAndSendSwapError,
+    WalletNotLoaded,
+};
+use elements::{bitcoin::util::amount::ParseAmountError, AddressError};
+use serde::Serialize;
+use wasm_bindgen::JsValue;
+
+/// Error we reject a call into the wallet with.
+///
+/// The `code` tells pages what went wrong without parsing the
+/// `message`, which is meant for humans.
+#[derive(Debug, Serialize)]
+pub struct RpcError {
+    pub code: ErrorCode,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ErrorCode {
+    /// The wallet has to be unlocked first.
+    WalletLocked,
+    /// The wallet does not hold enough of an asset.
+    InsufficientFunds,
+    /// The arguments of the call are malformed.
+    InvalidRequest,
+    /// Anything the caller cannot do anything about.
+    Internal,
+}
+
+impl RpcError {
+    pub fn into_js_value(self) -> JsValue {
+        JsValue::from_serde(&self).unwrap_or_else(|_| JsValue::from_str(&self.message))
+    }
+}
+
+impl From<anyhow::Error> for RpcError {
+    fn from(error: anyhow::Error) -> Self {
+        Self {
+            code: ErrorCode::of(&error),
+            message: format!("{:#}", error),
+        }
+    }
+}
+
+impl ErrorCode {
+    fn of(error: &anyhow::Error) -> Self {
+        if let Some(error) = error.downcast_ref::<MakePayloadError>() {
+            return match error {
+                MakePayloadError::LoadWallet(_) => ErrorCode::WalletLocked,
+                MakePayloadError::CoinSelection(coin_selection::Error::InsufficientFunds {
+                    ..
+                }) => ErrorCode::InsufficientFunds,
+                _ => ErrorCode::Internal,
+            };
+        }
+
+        if let Some(error) = error.downcast_ref::<MakeLoanRequestError>() {
+            return match error {
+                MakeLoanRequestError::LoadWallet(_) => ErrorCode::WalletLocked,
+                _ => ErrorCode::Internal,
+            };
+        }
+
+        if let Some(error) = error.downcast_ref::<ExtractLoanError>() {
+            return match error {
+                ExtractLoanError::LoadWallet(_) => ErrorCode::WalletLocked,
+                ExtractLoanError::InsufficientCollateral => ErrorCode::InsufficientFunds,
+                ExtractLoanError::LoanResponseDeserialization(_) => ErrorCode::InvalidRequest,
+                _ => ErrorCode::Internal,
+            };
+        }
+
+        if let Some(error) = error.downcast_ref::<RepayLoanError>() {
+            return match error {
+                RepayLoanError::LoadWallet(_) => ErrorCode::WalletLocked,
+                _ => ErrorCode::Internal,
+            };
+        }
+
+        if let Some(error) = error.downcast_ref::<SignAndSendSwapError>() {
+            return match error {
+                SignAndSendSwapError::LoadWallet(_) => ErrorCode::WalletLocked,
+                _ => ErrorCode::Internal,
+            };
+        }
+
+        if error.is::<WalletNotLoaded>() {
+            return ErrorCode::WalletLocked;
+        }
+
+        if error.is::<ParseAmountError>()
+            || error.is::<AddressError>()
+            || error.is::<serde_json::Error>()
+        {
+            return ErrorCode::InvalidRequest;
+        }
+
+        ErrorCode::Internal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn classifies_wallet_errors() {
+        let not_loaded = anyhow!(WalletNotLoaded("demo".to_owned()));
+        let insufficient_funds = anyhow!(MakePayloadError::CoinSelection(
+            coin_selection::Error::InsufficientFunds {
+                needed: 2,
+                available: 1,
+            }
+        ));
+        let broken = anyhow!("esplora is down");
+
+        assert_eq!(ErrorCode::of(&not_loaded), ErrorCode::WalletLocked);
+        assert_eq!(
+            ErrorCode::of(&insufficient_funds),
+            ErrorCode::InsufficientFunds
+        );
+        assert_eq!(ErrorCode::of(&broken), ErrorCode::Internal);
+    }
+}
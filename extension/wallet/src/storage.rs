@@ -1,12 +1,22 @@
 use anyhow::{Context, Result};
 use std::{error::Error as StdError, str::FromStr};
-use web_sys::window;
 
 use crate::LoanDetails;
 
-/// A wrapper type around the cache storage.
+/// Where the wallet keeps everything which outlives a single call.
+///
+/// In the browser this is the local storage of the extension. Native
+/// builds keep one file per item in a directory instead, so that the
+/// wallet core can be used outside of a browser.
+pub trait Backend {
+    fn get_item(&self, name: &str) -> Result<Option<String>>;
+    fn set_item(&self, name: &str, value: &str) -> Result<()>;
+    fn remove_item(&self, name: &str) -> Result<()>;
+}
+
+/// A wrapper type around the storage backend of the platform.
 pub struct Storage {
-    inner: web_sys::Storage,
+    inner: Box<dyn Backend>,
 }
 
 impl Storage {
@@ -22,13 +32,29 @@ impl Storage {
         Ok(loans)
     }
 
+    #[cfg(target_arch = "wasm32")]
     pub fn local_storage() -> Result<Self> {
-        let storage = map_err_to_anyhow!(window()
+        let storage = map_err_to_anyhow!(web_sys::window()
             .context("failed to access window object")?
             .local_storage())?
         .context("no local storage available")?;
 
-        Ok(storage.into())
+        Ok(Self::new(storage))
+    }
+
+    /// The storage in the directory named by `WALLET_STORAGE_DIR`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn local_storage() -> Result<Self> {
+        let dir =
+            std::env::var_os("WALLET_STORAGE_DIR").context("WALLET_STORAGE_DIR is not set")?;
+
+        Ok(Self::new(native::FileStorage::new(dir.into())?))
+    }
+
+    pub fn new(backend: impl Backend + 'static) -> Self {
+        Self {
+            inner: Box::new(backend),
+        }
     }
 
     pub fn get_item<T>(&self, name: &str) -> Result<Option<T>>
@@ -36,7 +62,7 @@ impl Storage {
         T: FromStr,
         <T as FromStr>::Err: StdError + Send + Sync + 'static,
     {
-        let value = map_err_to_anyhow!(self.inner.get_item(name))?;
+        let value = self.inner.get_item(name)?;
 
         let value = match value {
             Some(value) => value,
@@ -52,20 +78,99 @@ impl Storage {
     where
         V: ToString,
     {
-        map_err_to_anyhow!(self.inner.set_item(name, &value.to_string()))?;
-
-        Ok(())
+        self.inner.set_item(name, &value.to_string())
     }
 
     pub fn remove_item(&self, name: &str) -> Result<()> {
-        map_err_to_anyhow!(self.inner.remove_item(name))?;
+        self.inner.remove_item(name)
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+impl Backend for web_sys::Storage {
+    fn get_item(&self, name: &str) -> Result<Option<String>> {
+        map_err_to_anyhow!(web_sys::Storage::get_item(self, name))
+    }
+
+    fn set_item(&self, name: &str, value: &str) -> Result<()> {
+        map_err_to_anyhow!(web_sys::Storage::set_item(self, name, value))
+    }
+
+    fn remove_item(&self, name: &str) -> Result<()> {
+        map_err_to_anyhow!(web_sys::Storage::remove_item(self, name))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod native {
+    use super::Backend;
+    use anyhow::{Context, Result};
+    use std::{fs, io::ErrorKind, path::PathBuf};
+
+    pub struct FileStorage {
+        dir: PathBuf,
+    }
+
+    impl FileStorage {
+        pub fn new(dir: PathBuf) -> Result<Self> {
+            fs::create_dir_all(&dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+
+            Ok(Self { dir })
+        }
+
+        // Item names are arbitrary strings, e.g. URLs of cached
+        // responses, so they are hex-encoded to be valid file names.
+        fn path(&self, name: &str) -> PathBuf {
+            self.dir.join(hex::encode(name))
+        }
+    }
+
+    impl Backend for FileStorage {
+        fn get_item(&self, name: &str) -> Result<Option<String>> {
+            match fs::read_to_string(self.path(name)) {
+                Ok(value) => Ok(Some(value)),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(e).with_context(|| format!("failed to read item {}", name)),
+            }
+        }
 
-        Ok(())
+        fn set_item(&self, name: &str, value: &str) -> Result<()> {
+            fs::write(self.path(name), value)
+                .with_context(|| format!("failed to write item {}", name))
+        }
+
+        fn remove_item(&self, name: &str) -> Result<()> {
+            match fs::remove_file(self.path(name)) {
+                Ok(()) => Ok(()),
+                Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(e).with_context(|| format!("failed to remove item {}", name)),
+            }
+        }
     }
 }
 
-impl From<web_sys::Storage> for Storage {
-    fn from(inner: web_sys::Storage) -> Self {
-        Self { inner }
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_storage_round_trips_items() {
+        let dir = std::env::temp_dir().join(format!("wallet-storage-{}", std::process::id()));
+        let storage = Storage::new(native::FileStorage::new(dir.clone()).unwrap());
+        let url = "http://localhost:3001/tx/abcd/hex";
+
+        assert_eq!(storage.get_item::<String>(url).unwrap(), None);
+
+        storage.set_item(url, "deadbeef").unwrap();
+        assert_eq!(
+            storage.get_item::<String>(url).unwrap().as_deref(),
+            Some("deadbeef")
+        );
+
+        storage.remove_item(url).unwrap();
+        assert_eq!(storage.get_item::<String>(url).unwrap(), None);
+
+        std::fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -17,7 +17,7 @@ use elements::{
     },
     confidential,
     secp256k1_zkp::{rand, PublicKey},
-    Address, AssetId, OutPoint, TxOut, Txid,
+    Address, AssetId, TxOut, Txid,
 };
 use futures::{
     lock::{MappedMutexGuard, Mutex, MutexGuard},
@@ -38,6 +38,7 @@ use std::{
 };
 use wasm_bindgen::UnwrapThrowExt;
 
+pub use bobtimus_client::{AliceInput, CreateSwapPayload};
 pub use create_new::create_new;
 pub use extract_loan::{extract_loan, Error as ExtractLoanError};
 pub use extract_trade::{extract_trade, Trade};
@@ -45,13 +46,16 @@ pub use get_address::get_address;
 pub use get_balances::get_balances;
 pub use get_status::{get_status, WalletStatus};
 pub use get_transaction_history::get_transaction_history;
+pub use get_utxos::{get_utxos, UtxoEntry};
 pub use load_existing::load_existing;
 pub use make_create_swap_payload::{
     make_buy_create_swap_payload, make_sell_create_swap_payload, Error as MakePayloadError,
 };
 pub use make_loan_request::{make_loan_request, Error as MakeLoanRequestError};
 pub use repay_loan::{repay_loan, Error as RepayLoanError};
-pub(crate) use sign_and_send_swap_transaction::sign_and_send_swap_transaction;
+pub(crate) use sign_and_send_swap_transaction::{
+    sign_and_send_swap_transaction, Error as SignAndSendSwapError,
+};
 pub(crate) use sign_loan::sign_loan;
 pub use unload_current::unload_current;
 pub use withdraw_everything_to::withdraw_everything_to;
@@ -63,6 +67,7 @@ mod get_address;
 mod get_balances;
 mod get_status;
 mod get_transaction_history;
+mod get_utxos;
 mod load_existing;
 mod make_create_swap_payload;
 mod make_loan_request;
@@ -72,16 +77,35 @@ mod sign_loan;
 mod unload_current;
 mod withdraw_everything_to;
 
+/// The maximum number of UTXO requests we send to esplora at once.
+const MAX_CONCURRENT_UTXO_REQUESTS: usize = 5;
+
+/// The fee rate in sat/vbyte we propose for swap transactions.
+///
+/// Based on esplora's estimate for confirmation within 6 blocks,
+/// rounded up to a whole sat/vbyte.
+async fn swap_fee_rate() -> Result<u64> {
+    let fee_estimates = esplora::get_fee_estimates().await?;
+    let fee_rate = fee_estimates
+        .b_6
+        .map(|fee_rate| fee_rate.ceil() as u64)
+        .unwrap_or(DEFAULT_SAT_PER_VBYTE);
+
+    Ok(fee_rate.max(DEFAULT_SAT_PER_VBYTE))
+}
+
 async fn get_txouts<T, FM: Fn(Utxo, TxOut) -> Result<Option<T>> + Copy>(
     wallet: &Wallet,
     filter_map: FM,
 ) -> Result<Vec<T>> {
-    let address = wallet.get_address();
+    let addresses = [wallet.get_address()];
 
-    let utxos = esplora::fetch_utxos(&address).await?;
+    let utxos = esplora::fetch_utxos_for_addresses(&addresses, MAX_CONCURRENT_UTXO_REQUESTS)
+        .await?
+        .into_iter()
+        .flat_map(|(_, utxos)| utxos);
 
     let txouts = utxos
-        .into_iter()
         .map(move |utxo| async move {
             let mut tx = esplora::fetch_transaction(utxo.txid).await?;
             let txout = tx.output.remove(utxo.vout as usize);
@@ -104,12 +128,16 @@ async fn current<'n, 'w>(
 
     match &mut *guard {
         Some(wallet) if wallet.name == name => {}
-        _ => bail!("wallet with name '{}' is currently not loaded", name),
+        _ => bail!(WalletNotLoaded(name.to_owned())),
     };
 
     Ok(MutexGuard::map(guard, |w| w.as_mut().unwrap()))
 }
 
+#[derive(Debug, thiserror::Error)]
+#[error("wallet with name '{0}' is currently not loaded")]
+pub struct WalletNotLoaded(pub String);
+
 #[derive(Debug)]
 pub struct Wallet {
     name: String,
@@ -186,7 +214,7 @@ impl Wallet {
                 key: public_key,
             },
             Some(blinding_key),
-            chain.into(),
+            chain.address_params(),
         )
     }
 
@@ -286,21 +314,6 @@ impl fmt::Display for ListOfWallets {
     }
 }
 
-/// Represents the payload for creating a swap.
-#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
-pub struct CreateSwapPayload {
-    pub alice_inputs: Vec<SwapUtxo>,
-    pub address: Address,
-    #[serde(with = "bdk::bitcoin::util::amount::serde::as_sat")]
-    pub amount: bdk::bitcoin::Amount,
-}
-
-#[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
-pub struct SwapUtxo {
-    pub outpoint: OutPoint,
-    pub blinding_key: SecretKey,
-}
-
 /// A single balance entry as returned by [`get_balances`].
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
 pub struct BalanceEntry {
@@ -0,0 +1,60 @@
+use crate::{
+    assets::lookup,
+    wallet::{current, get_txouts, Wallet},
+};
+use anyhow::Result;
+use elements::{bitcoin::secp256k1::SECP256K1, confidential, AssetId, TxOut, Txid};
+use futures::lock::Mutex;
+
+/// The UTXOs of the wallet, unblinded.
+///
+/// UTXOs which we fail to unblind are left out.
+pub async fn get_utxos(
+    name: &str,
+    current_wallet: &Mutex<Option<Wallet>>,
+) -> Result<Vec<UtxoEntry>> {
+    let wallet = current(name, current_wallet).await?;
+    let blinding_key = wallet.blinding_key();
+
+    let utxos = get_txouts(&wallet, move |utxo, txout| {
+        let (asset, value) = match txout {
+            TxOut {
+                asset: confidential::Asset::Explicit(asset),
+                value: confidential::Value::Explicit(value),
+                ..
+            } => (asset, value),
+            txout => match txout.unblind(SECP256K1, blinding_key) {
+                Ok(unblinded_txout) => (unblinded_txout.asset, unblinded_txout.value),
+                Err(e) => {
+                    log::warn!("failed to unblind txout: {}", e);
+                    return Ok(None);
+                }
+            },
+        };
+
+        Ok(Some(UtxoEntry {
+            txid: utxo.txid,
+            vout: utxo.vout,
+            asset,
+            ticker: lookup(asset).map(|(ticker, _)| ticker.to_owned()),
+            value,
+            confirmed: utxo.status.confirmed,
+        }))
+    })
+    .await?;
+
+    Ok(utxos)
+}
+
+/// A single UTXO as returned by [`get_utxos`].
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize, PartialEq)]
+pub struct UtxoEntry {
+    pub txid: Txid,
+    pub vout: u32,
+    pub asset: AssetId,
+    /// Only known for the assets we trade.
+    pub ticker: Option<String>,
+    /// In the smallest unit of the asset.
+    pub value: u64,
+    pub confirmed: bool,
+}
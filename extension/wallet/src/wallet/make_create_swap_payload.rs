@@ -1,18 +1,28 @@
 use crate::{
-    wallet::{current, get_txouts, CreateSwapPayload, SwapUtxo, Wallet},
+    wallet::{current, get_txouts, swap_fee_rate, AliceInput, CreateSwapPayload, Wallet},
     BTC_ASSET_ID, USDT_ASSET_ID,
 };
-use bdk::bitcoin::Amount;
-use coin_selection::{self, coin_select};
-use elements::{secp256k1_zkp::SECP256K1, AssetId, OutPoint};
-use estimate_transaction_size::avg_vbytes;
+use coin_selection::{self, coin_select_many};
+use elements::{bitcoin::Amount, secp256k1_zkp::SECP256K1, AssetId, OutPoint};
 use futures::lock::Mutex;
 use wasm_bindgen::UnwrapThrowExt;
 
+/// The fee rate in sat/vbyte up to which we consolidate small UTXOs
+/// if asked to.
+const MAX_CONSOLIDATION_FEE_RATE: f32 = 1.0;
+
+/// UTXOs worth less than this many sats are consolidated if asked to.
+const MAX_CONSOLIDATED_UTXO_VALUE: u64 = 100_000;
+
+/// Outputs of the swap transaction we pay for besides our change:
+/// Bob's receive and change outputs, and our receive output.
+const SWAP_OUTPUTS: usize = 3;
+
 pub async fn make_buy_create_swap_payload(
     name: String,
     current_wallet: &Mutex<Option<Wallet>>,
     sell_amount: Amount,
+    consolidate: bool,
 ) -> Result<CreateSwapPayload, Error> {
     let btc_asset_id = {
         let guard = BTC_ASSET_ID.lock().expect_throw("can get lock");
@@ -29,6 +39,7 @@ pub async fn make_buy_create_swap_payload(
         sell_amount,
         usdt_asset_id,
         btc_asset_id,
+        consolidate,
     )
     .await
 }
@@ -37,6 +48,7 @@ pub async fn make_sell_create_swap_payload(
     name: String,
     current_wallet: &Mutex<Option<Wallet>>,
     sell_amount: Amount,
+    consolidate: bool,
 ) -> Result<CreateSwapPayload, Error> {
     let btc_asset_id = {
         let guard = BTC_ASSET_ID.lock().expect_throw("can get lock");
@@ -48,6 +60,7 @@ pub async fn make_sell_create_swap_payload(
         sell_amount,
         btc_asset_id,
         btc_asset_id,
+        consolidate,
     )
     .await
 }
@@ -58,6 +71,7 @@ async fn make_create_swap_payload(
     sell_amount: Amount,
     sell_asset: AssetId,
     fee_asset: AssetId,
+    consolidate: bool,
 ) -> Result<CreateSwapPayload, Error> {
     let wallet = current(&name, current_wallet)
         .await
@@ -67,64 +81,62 @@ async fn make_create_swap_payload(
     let utxos = get_txouts(&wallet, |utxo, txout| {
         Ok({
             let unblinded_txout = txout.unblind(SECP256K1, blinding_key)?;
-            let outpoint = OutPoint {
-                txid: utxo.txid,
-                vout: utxo.vout,
-            };
-            let candidate_asset = unblinded_txout.asset;
 
-            if candidate_asset == sell_asset {
-                Some(coin_selection::Utxo {
-                    outpoint,
-                    value: unblinded_txout.value,
-                    script_pubkey: txout.script_pubkey,
-                    asset: candidate_asset,
-                })
-            } else {
-                log::debug!(
-                    "utxo {} with asset id {} is not the sell asset, ignoring",
-                    outpoint,
-                    candidate_asset
-                );
-                None
-            }
+            Some(coin_selection::Utxo {
+                outpoint: OutPoint {
+                    txid: utxo.txid,
+                    vout: utxo.vout,
+                },
+                value: unblinded_txout.value,
+                script_pubkey: txout.script_pubkey,
+                asset: unblinded_txout.asset,
+                height: utxo.status.block_height,
+                satisfaction_weight: None,
+            })
         })
     })
     .await
     .map_err(Error::GetTxOuts)?;
 
-    let (bobs_fee_rate, fee_offset) = if fee_asset == sell_asset {
-        // Bob currently hardcodes a fee-rate of 1 sat / vbyte, hence
-        // there is no need for us to perform fee estimation. Later
-        // on, both parties should probably agree on a block-target
-        // and use the same estimation service.
-        let bobs_fee_rate = Amount::from_sat(1);
-        let fee_offset = calculate_fee_offset(bobs_fee_rate);
+    // Bob uses the fee rate we propose, as long as it is not below
+    // his own estimate
+    let fee_rate = swap_fee_rate().await.map_err(Error::FeeEstimation)?;
 
-        (bobs_fee_rate, fee_offset)
+    let bobs_fee_rate = if fee_asset == sell_asset {
+        Amount::from_sat(fee_rate)
     } else {
-        (Amount::ZERO, Amount::ZERO)
+        Amount::ZERO
     };
 
-    let output = coin_select(
-        utxos,
-        sell_amount,
+    let outputs = coin_select_many(
+        &utxos,
+        &[(sell_asset, sell_amount)],
+        fee_asset,
         bobs_fee_rate.as_sat() as f32,
-        fee_offset,
+        SWAP_OUTPUTS,
+        coin_selection::Options {
+            consolidation: consolidate.then(|| coin_selection::Consolidation {
+                max_fee_rate_sat_per_vbyte: MAX_CONSOLIDATION_FEE_RATE,
+                max_utxo_value: Amount::from_sat(MAX_CONSOLIDATED_UTXO_VALUE),
+            }),
+            ..coin_selection::Options::default()
+        },
     )
     .map_err(Error::CoinSelection)?;
 
     Ok(CreateSwapPayload {
         address: wallet.get_address(),
-        alice_inputs: output
-            .coins
-            .into_iter()
-            .map(|utxo| SwapUtxo {
+        alice_inputs: outputs
+            .iter()
+            .flat_map(|(_, output)| output.selected_utxos(&utxos))
+            .map(|utxo| AliceInput {
                 outpoint: utxo.outpoint,
                 blinding_key,
             })
             .collect(),
-        amount: output.target_amount,
+        amount: sell_amount.as_sat(),
+        fee_rate: Some(fee_rate),
+        quote: None,
     })
 }
 
@@ -136,17 +148,6 @@ pub enum Error {
     CoinSelection(coin_selection::Error),
     #[error("Failed to get transaction outputs: {0}")]
     GetTxOuts(anyhow::Error),
-}
-
-/// Calculate the fee offset required for the coin selection algorithm.
-///
-/// We are calculating this fee offset here so that we select enough coins to pay for the asset + the fee.
-fn calculate_fee_offset(fee_sats_per_vbyte: Amount) -> Amount {
-    let bobs_outputs = 2; // bob will create two outputs for himself (receive + change)
-    let our_output = 1; // we have one additional output (the change output is priced in by the coin-selection algorithm)
-
-    let fee_offset =
-        ((bobs_outputs + our_output) * avg_vbytes::OUTPUT) * fee_sats_per_vbyte.as_sat();
-
-    Amount::from_sat(fee_offset)
+    #[error("Failed to estimate fee rate: {0}")]
+    FeeEstimation(anyhow::Error),
 }
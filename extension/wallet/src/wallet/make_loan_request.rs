@@ -43,7 +43,7 @@ pub async fn make_loan_request(
         |amount, asset| async move {
             let wallet = current(&name, current_wallet).await?;
 
-            let utxos = get_txouts(&wallet, |utxo, txout| {
+            let (utxos, txouts) = get_txouts(&wallet, |utxo, txout| {
                 Ok({
                     let unblinded_txout = txout.unblind(SECP256K1, blinding_key)?;
                     let outpoint = OutPoint {
@@ -59,6 +59,8 @@ pub async fn make_loan_request(
                                 value: unblinded_txout.value,
                                 script_pubkey: txout.script_pubkey.clone(),
                                 asset: candidate_asset,
+                                height: utxo.status.block_height,
+                                satisfaction_weight: None,
                             },
                             txout,
                         ))
@@ -72,7 +74,9 @@ pub async fn make_loan_request(
                     }
                 })
             })
-            .await?;
+            .await?
+            .into_iter()
+            .unzip::<_, _, Vec<_>, Vec<_>>();
 
             // Bob currently hardcodes a fee-rate of 1 sat / vbyte, hence
             // there is no need for us to perform fee estimation. Later
@@ -82,26 +86,19 @@ pub async fn make_loan_request(
             let fee_offset = calculate_fee_offset(bobs_fee_rate);
 
             let output = coin_select(
-                utxos.iter().map(|(utxo, _)| utxo).cloned().collect(),
+                &utxos,
                 amount,
                 bobs_fee_rate.as_sat() as f32,
                 fee_offset,
+                coin_selection::Options::default(),
             )?;
             let selection = output
                 .coins
                 .iter()
-                .map(|coin| {
-                    let original_txout = utxos
-                        .iter()
-                        .find_map(|(utxo, txout)| (utxo.outpoint == coin.outpoint).then(|| txout))
-                        .expect("same source of utxos")
-                        .clone();
-
-                    Input {
-                        txin: coin.outpoint,
-                        original_txout,
-                        blinding_key,
-                    }
+                .map(|index| Input {
+                    txin: utxos[*index].outpoint,
+                    original_txout: txouts[*index].clone(),
+                    blinding_key,
                 })
                 .collect();
 
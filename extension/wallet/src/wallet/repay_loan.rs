@@ -47,7 +47,7 @@ pub async fn repay_loan(
         |amount, asset| async move {
             let wallet = current(&name, current_wallet).await?;
 
-            let utxos = get_txouts(&wallet, |utxo, txout| {
+            let (utxos, txouts) = get_txouts(&wallet, |utxo, txout| {
                 Ok({
                     let unblinded_txout = txout.unblind(SECP256K1, blinding_key)?;
                     let outpoint = OutPoint {
@@ -63,6 +63,8 @@ pub async fn repay_loan(
                                 value: unblinded_txout.value,
                                 script_pubkey: txout.script_pubkey.clone(),
                                 asset: candidate_asset,
+                                height: utxo.status.block_height,
+                                satisfaction_weight: None,
                             },
                             txout,
                         ))
@@ -76,7 +78,9 @@ pub async fn repay_loan(
                     }
                 })
             })
-            .await?;
+            .await?
+            .into_iter()
+            .unzip::<_, _, Vec<_>, Vec<_>>();
 
             // We are selecting coins with an asset which cannot be
             // used to pay for fees
@@ -84,26 +88,19 @@ pub async fn repay_loan(
             let zero_fee_offset = Amount::ZERO;
 
             let output = coin_select(
-                utxos.iter().map(|(utxo, _)| utxo).cloned().collect(),
+                &utxos,
                 amount,
                 zero_fee_rate,
                 zero_fee_offset,
+                coin_selection::Options::default(),
             )?;
             let selection = output
                 .coins
                 .iter()
-                .map(|coin| {
-                    let original_txout = utxos
-                        .iter()
-                        .find_map(|(utxo, txout)| (utxo.outpoint == coin.outpoint).then(|| txout))
-                        .expect("same source of utxos")
-                        .clone();
-
-                    Input {
-                        txin: coin.outpoint,
-                        original_txout,
-                        blinding_key,
-                    }
+                .map(|index| Input {
+                    txin: utxos[*index].outpoint,
+                    original_txout: txouts[*index].clone(),
+                    blinding_key,
                 })
                 .collect();
 
@@ -1,12 +1,19 @@
 use crate::{
     esplora::broadcast,
-    wallet::{current, get_txouts, Wallet},
+    wallet::{current, get_txouts, swap_fee_rate, Wallet},
 };
-use anyhow::Result;
+use anyhow::{bail, Result};
 use baru::swap::{alice_finalize_transaction, sign_with_key};
 use elements::{secp256k1_zkp::SECP256K1, sighash::SigHashCache, Transaction, Txid};
+use estimate_transaction_size::estimate_virtual_size;
 use futures::lock::Mutex;
 
+/// How much the fee rate of a swap transaction may exceed our current
+/// estimate.
+///
+/// The estimate may have dropped since we proposed a fee rate to Bob.
+const FEE_RATE_TOLERANCE: u64 = 2;
+
 pub(crate) async fn sign_and_send_swap_transaction(
     name: String,
     current_wallet: &Mutex<Option<Wallet>>,
@@ -16,6 +23,9 @@ pub(crate) async fn sign_and_send_swap_transaction(
         .await
         .map_err(Error::LoadWallet)?;
 
+    let fee_rate = swap_fee_rate().await.map_err(Error::FeeEstimation)?;
+    check_fee(&transaction, fee_rate * FEE_RATE_TOLERANCE).map_err(Error::Fee)?;
+
     let txouts = get_txouts(&wallet, |utxo, txout| Ok(Some((utxo, txout))))
         .await
         .map_err(Error::GetTxOuts)?;
@@ -64,12 +74,39 @@ pub(crate) async fn sign_and_send_swap_transaction(
     Ok(txid)
 }
 
+/// Ensure that the `transaction` does not pay more than
+/// `max_fee_rate` sat/vbyte.
+fn check_fee(transaction: &Transaction, max_fee_rate: u64) -> Result<()> {
+    let (fee_outputs, outputs): (Vec<_>, Vec<_>) =
+        transaction.output.iter().partition(|txout| txout.is_fee());
+    let fee = fee_outputs
+        .iter()
+        .filter_map(|txout| txout.value.explicit())
+        .sum::<u64>();
+
+    let virtual_size = estimate_virtual_size(transaction.input.len() as u64, outputs.len() as u64);
+    let max_fee = virtual_size * max_fee_rate;
+    if fee > max_fee {
+        bail!(
+            "transaction pays a fee of {} sat, expected at most {} sat",
+            fee,
+            max_fee
+        )
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Wallet is not loaded: {0}")]
     LoadWallet(anyhow::Error),
     #[error("Failed to get transaction outputs: {0}")]
     GetTxOuts(anyhow::Error),
+    #[error("Failed to estimate fee rate: {0}")]
+    FeeEstimation(anyhow::Error),
+    #[error("Unexpected fee: {0}")]
+    Fee(anyhow::Error),
     #[error("Failed to sign transaction: {0}")]
     Sign(anyhow::Error),
     #[error("Failed to broadcast transaction: {0}")]
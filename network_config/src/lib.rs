@@ -0,0 +1,137 @@
+use elements::{AddressParams, AssetId};
+use serde::Serialize;
+use std::{fmt, str::FromStr};
+
+/// Asset id of L-BTC on Liquid.
+pub const LIQUID_BTC_ASSET_ID: &str =
+    "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
+
+/// Asset id of L-USDt on Liquid.
+pub const LIQUID_USDT_ASSET_ID: &str =
+    "ce091c998b83c78bb71a632313ba3760f1763d9cfcffae02258ffa9865a37bd2";
+
+/// Asset id of tL-BTC on Liquid Testnet.
+pub const LIQUID_TESTNET_BTC_ASSET_ID: &str =
+    "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49";
+
+/// Address parameters of Liquid Testnet, which `elements` does not
+/// know about.
+pub static LIQUID_TESTNET_ADDRESS_PARAMS: AddressParams = AddressParams {
+    p2pkh_prefix: 36,
+    p2sh_prefix: 19,
+    blinded_prefix: 23,
+    bech_hrp: "tex",
+    blech_hrp: "tlq",
+};
+
+/// A network Droplet can run on.
+///
+/// Everything which is the same on every instance of the network is
+/// known here. Asset ids of a regtest chain depend on how its node
+/// was set up and need to be configured by the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Network {
+    Liquid,
+    LiquidTestnet,
+    #[serde(rename = "elements")]
+    ElementsRegtest,
+}
+
+impl Network {
+    pub fn address_params(self) -> &'static AddressParams {
+        match self {
+            Network::Liquid => &AddressParams::LIQUID,
+            Network::LiquidTestnet => &LIQUID_TESTNET_ADDRESS_PARAMS,
+            Network::ElementsRegtest => &AddressParams::ELEMENTS,
+        }
+    }
+
+    /// Asset id of the network's native asset.
+    pub fn btc_asset_id(self) -> Option<AssetId> {
+        let asset_id = match self {
+            Network::Liquid => LIQUID_BTC_ASSET_ID,
+            Network::LiquidTestnet => LIQUID_TESTNET_BTC_ASSET_ID,
+            Network::ElementsRegtest => return None,
+        };
+
+        Some(asset_id.parse().expect("valid asset id"))
+    }
+
+    pub fn usdt_asset_id(self) -> Option<AssetId> {
+        match self {
+            Network::Liquid => Some(LIQUID_USDT_ASSET_ID.parse().expect("valid asset id")),
+            Network::LiquidTestnet | Network::ElementsRegtest => None,
+        }
+    }
+
+    /// Base URL of the public Esplora instance of the network.
+    pub fn esplora_url(self) -> Option<&'static str> {
+        match self {
+            Network::Liquid => Some("https://blockstream.info/liquid/api/"),
+            Network::LiquidTestnet => Some("https://blockstream.info/liquidtestnet/api/"),
+            Network::ElementsRegtest => None,
+        }
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Network::Liquid => "liquid",
+            Network::LiquidTestnet => "liquid-testnet",
+            Network::ElementsRegtest => "elements",
+        };
+
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Network {
+    type Err = UnknownNetwork;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lowercase = s.to_ascii_lowercase();
+        match lowercase.as_str() {
+            "liquid" => Ok(Network::Liquid),
+            "liquid-testnet" | "liquid_testnet" | "liquidtestnet" => Ok(Network::LiquidTestnet),
+            "elements" | "elements-regtest" | "regtest" => Ok(Network::ElementsRegtest),
+            _ => Err(UnknownNetwork(lowercase)),
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Unsupported chain: {0}")]
+pub struct UnknownNetwork(String);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_its_own_names() {
+        for network in [
+            Network::Liquid,
+            Network::LiquidTestnet,
+            Network::ElementsRegtest,
+        ]
+        .iter()
+        {
+            assert_eq!(network.to_string().parse::<Network>().unwrap(), *network);
+        }
+
+        assert_eq!(
+            "ELEMENTS".parse::<Network>().unwrap(),
+            Network::ElementsRegtest
+        );
+        assert!("bitcoin".parse::<Network>().is_err());
+    }
+
+    #[test]
+    fn asset_ids_are_valid() {
+        assert!(Network::Liquid.btc_asset_id().is_some());
+        assert!(Network::Liquid.usdt_asset_id().is_some());
+        assert!(Network::LiquidTestnet.btc_asset_id().is_some());
+    }
+}
@@ -208,7 +208,7 @@ function App() {
     const toast = useToast();
     const path = history.location.pathname;
 
-    const wavesProvider = window.wavesProvider;
+    const wavesProvider = window.droplet;
 
     useEffect(() => {
         if (path === "/app") {
@@ -227,6 +227,12 @@ function App() {
         promiseFn: wavesProvider?.walletStatus,
     });
 
+    const { reload: reloadWalletStatus } = walletStatusAsyncState;
+    useEffect(() => {
+        // e.g. the wallet was unlocked in the extension's pop-up
+        return wavesProvider?.onWalletStateChanged(() => reloadWalletStatus());
+    }, [wavesProvider, reloadWalletStatus]);
+
     let { run: callFaucet, isLoading: isFaucetLoading } = useAsync({
         deferFn: async () => {
             try {
@@ -1,6 +1,7 @@
 import Debug from "debug";
 import React, { ReactElement } from "react";
 import { SSEProvider } from "react-hooks-sse";
+import { Rate } from "./App";
 import { CreateSwapPayload, LoanRequestPayload } from "./waves-provider/wavesProvider";
 
 const debug = Debug("bobtimus");
@@ -19,6 +20,30 @@ export async function postBuyPayload(payload: CreateSwapPayload) {
     return await postPayload(payload, "buy");
 }
 
+export interface LoanOffer {
+    rate: Rate;
+    max_ltv_bps: number;
+    liquidation_ltv_bps: number;
+    min_timelock: number;
+    max_timelock: number;
+    max_principal: number;
+}
+
+export async function getLoanOffer(): Promise<LoanOffer> {
+    let res = await fetch(`/api/loan/offer`, {
+        headers: {
+            Accept: "application/json",
+        },
+    });
+
+    if (res.status !== 200) {
+        debug("failed to get loan offer");
+        throw new Error("failed to get loan offer");
+    }
+
+    return await res.json();
+}
+
 export async function postLoanRequest(payload: LoanRequestPayload) {
     let res = await fetch(`/api/loan/lbtc-lusdt`, {
         method: "POST",
@@ -78,5 +103,5 @@ interface RateProviderProps {
 }
 
 export function BobtimusRateProvider({ children }: RateProviderProps) {
-    return <SSEProvider endpoint="/api/rate/lbtc-lusdt">{children}</SSEProvider>;
+    return <SSEProvider endpoint="/api/rate/stream">{children}</SSEProvider>;
 }
@@ -28,9 +28,6 @@ function Borrow({ dispatch, state, rate, wavesProvider, walletStatusAsyncState }
 
     let { data: walletStatus, reload: reloadWalletStatus, error: walletStatusError } = walletStatusAsyncState;
 
-    // TODO: We should get an up-to-date interest rate from Bobtimus
-    let interestRate = 0.10;
-
     const principalAmount = Number.parseFloat(state.principalAmount);
     let collateralAmount = calculateBetaAmount(
         Asset.USDT,
@@ -38,8 +35,6 @@ function Borrow({ dispatch, state, rate, wavesProvider, walletStatusAsyncState }
         rate,
     );
 
-    let interestAmount = principalAmount * interestRate;
-
     function onPrincipalAmountChange(newAmount: string) {
         dispatch({
             type: "UpdatePrincipalAmount",
@@ -57,13 +52,14 @@ function Borrow({ dispatch, state, rate, wavesProvider, walletStatusAsyncState }
             try {
                 let loanRequest = await wavesProvider.makeLoanRequestPayload(collateralAmount.toString());
                 let loanResponse = await postLoanRequest(loanRequest);
-                let loanTransaction = await wavesProvider.signLoan(loanResponse);
+                await wavesProvider.loanResponseReceived(loanResponse);
+                let loanTransaction = await wavesProvider.signLoan();
                 let txid = await postLoanFinalization(loanTransaction);
 
                 // TODO: Add different page for loaned?
                 history.push(`/trade/swapped/${txid}`);
             } catch (e) {
-                const description = typeof e === "string" ? e : JSON.stringify(e);
+                const description = typeof e === "string" ? e : e.message ?? JSON.stringify(e);
 
                 toast({
                     title: "Error",
@@ -156,16 +152,6 @@ function Borrow({ dispatch, state, rate, wavesProvider, walletStatusAsyncState }
                         isDisabled={true}
                         dataCy={"data-cy-collateral"}
                     />
-                    <p>Interest {interestRate}%:</p>
-                    <NumberInput
-                        currency="₿"
-                        value={interestAmount}
-                        precision={7}
-                        step={0.01}
-                        onAmountChange={() => {}}
-                        isDisabled={true}
-                        dataCy={"data-cy-interest"}
-                    />
                     <p>Loan term (in days): {state.loanTerm}</p>
                 </VStack>
             </Center>
@@ -57,7 +57,7 @@ function Trade({ state, dispatch, rate, walletStatusAsyncState, wavesProvider }:
 
                 history.push(`/trade/swapped/${txid}`);
             } catch (e) {
-                const description = typeof e === "string" ? e : JSON.stringify(e);
+                const description = typeof e === "string" ? e : e.message ?? JSON.stringify(e);
 
                 toast({
                     title: "Error",
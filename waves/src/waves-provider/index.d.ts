@@ -1,8 +1,20 @@
-import { Address, CreateSwapPayload, LoanRequestPayload, LoanTx, Txid, WalletStatus } from "./wavesProvider";
+import {
+    Address,
+    CreateSwapPayload,
+    LoanDetails,
+    LoanRequestPayload,
+    LoanTx,
+    Network,
+    Txid,
+    Utxo,
+    WalletState,
+    WalletStatus,
+} from "./wavesProvider";
+import { LoanOffer } from "../Bobtimus";
 
 declare global {
     interface Window {
-        wavesProvider?: WavesProvider;
+        droplet?: WavesProvider;
     }
 }
 
@@ -19,5 +31,15 @@ export default class WavesProvider {
 
     public async signAndSendSwap(tx_hex: string): Promise<Txid>;
 
-    public async signLoan(loan_response: any): Promise<LoanTx>;
+    public async getLoanOffer(): Promise<LoanOffer>;
+
+    public async loanResponseReceived(loan_response: any): Promise<LoanDetails>;
+
+    public async signLoan(): Promise<LoanTx>;
+
+    public async getUtxos(): Promise<Utxo[]>;
+
+    public async getNetwork(): Promise<Network>;
+
+    public onWalletStateChanged(listener: (state: WalletState) => void): () => void;
 }
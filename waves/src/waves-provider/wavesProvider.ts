@@ -1,13 +1,30 @@
 import { Asset } from "../App";
 
 export interface BalanceEntry {
-    asset: string;
+    assetId: string;
+    ticker: string;
     value: number;
-    ticker?: string;
 }
 
 export type Address = string;
 
+export enum ErrorCode {
+    WalletLocked = "WalletLocked",
+    InsufficientFunds = "InsufficientFunds",
+    InvalidRequest = "InvalidRequest",
+    UserRejected = "UserRejected",
+    Unauthorized = "Unauthorized",
+    UnsupportedVersion = "UnsupportedVersion",
+    Disconnected = "Disconnected",
+    Internal = "Internal",
+}
+
+// What every promise of the provider rejects with.
+export interface RpcError {
+    code: ErrorCode;
+    message: string;
+}
+
 export type Txid = string;
 
 export type LoanTx = string;
@@ -15,6 +32,25 @@ export type LoanTx = string;
 export interface WalletStatus {
     status: Status;
     address?: string;
+    network: Network;
+    // Version of the extension.
+    version: string;
+    features: Feature[];
+    // Whether the user authorized the origin of the page to see the
+    // wallet's UTXOs.
+    authorized: boolean;
+}
+
+export interface WalletState {
+    status: WalletStatus;
+    // Empty while the wallet is locked.
+    balances: BalanceEntry[];
+}
+
+export enum Feature {
+    Swap = "swap",
+    Loan = "loan",
+    SignMessage = "signMessage",
 }
 
 export enum Status {
@@ -27,6 +63,7 @@ export interface CreateSwapPayload {
     alice_inputs: { outpoint: OutPoint; blinding_key: string }[];
     address: string;
     amount: number;
+    fee_rate: number;
 }
 
 export interface LoanRequestPayload {
@@ -39,6 +76,26 @@ export interface LoanRequestPayload {
     borrower_address: string;
 }
 
+export interface Utxo {
+    txid: Txid;
+    vout: number;
+    asset: string;
+    ticker?: string;
+    value: number;
+    confirmed: boolean;
+}
+
+export interface NetworkAsset {
+    assetId: string;
+    ticker: string;
+    precision: number;
+}
+
+export interface Network {
+    chain: "elements" | "liquid" | "liquid-testnet";
+    assets: NetworkAsset[];
+}
+
 export interface OutPoint {
     txid: string;
     vout: number;
@@ -55,3 +112,11 @@ export interface TradeSide {
     balanceBefore: number;
     balanceAfter: number;
 }
+
+export interface LoanDetails {
+    collateral: TradeSide;
+    principal: TradeSide;
+    principalRepayment: number;
+    term: number;
+    txid: Txid;
+}